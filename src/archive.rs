@@ -0,0 +1,167 @@
+// Archive members as entries, for `FROM archive("logs.tar.gz")`.
+//
+// Zip files and tar files (plain or gzip compressed) are read in place:
+// only the member headers are looked at and nothing is extracted. Each
+// member becomes an entry whose `path` is its path inside the archive and
+// whose `root` is the archive itself.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use crate::files::{FileInfo, FileType};
+
+pub fn members(archive: &Path) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let file = BufReader::new(File::open(archive)?);
+    if name.ends_with(".zip") {
+        zip_members(archive, file)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        tar_members(archive, flate2::read::GzDecoder::new(file))
+    } else if name.ends_with(".tar") {
+        tar_members(archive, file)
+    } else {
+        Err(format!("'{}' is not a zip or tar archive", archive.display()).into())
+    }
+}
+
+// an entry for one member; `inner` is its path inside the archive
+struct Member {
+    inner: String,
+    is_dir: bool,
+    size: u64,
+    compressed_size: Option<u64>,
+    modified: DateTime<Utc>,
+    mode: u32,
+    owner: String,
+    group: String,
+}
+
+impl Member {
+    fn into_file_info(self, archive: &Path) -> FileInfo {
+        let inner = self.inner.trim_end_matches('/').to_string();
+        FileInfo {
+            size: self.size,
+            modified: self.modified,
+            // archives keep no access times
+            accessed: self.modified,
+            name: inner.rsplit('/').next().unwrap_or_default().to_string(),
+            file_type: if self.is_dir { FileType::Directory } else { FileType::File },
+            path: inner,
+            owner: self.owner,
+            group: self.group,
+            mode: self.mode & 0o7777,
+            root: archive.display().to_string(),
+            compressed_size: self.compressed_size,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        }
+    }
+}
+
+fn zip_members(archive: &Path, file: BufReader<File>) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    let mut zip = zip::ZipArchive::new(file)?;
+    let mut members = Vec::with_capacity(zip.len());
+    for index in 0..zip.len() {
+        // raw access reads the header without decompressing anything
+        let entry = zip.by_index_raw(index)?;
+        let modified = entry
+            .last_modified()
+            .and_then(|stamp| {
+                NaiveDate::from_ymd_opt(stamp.year().into(), stamp.month().into(), stamp.day().into())?.and_hms_opt(
+                    stamp.hour().into(),
+                    stamp.minute().into(),
+                    stamp.second().into(),
+                )
+            })
+            .map(|stamp| Utc.from_utc_datetime(&stamp))
+            .unwrap_or_default();
+        let is_dir = entry.is_dir();
+        members.push(
+            Member {
+                inner: entry.name().to_string(),
+                is_dir,
+                size: entry.size(),
+                compressed_size: Some(entry.compressed_size()),
+                modified,
+                mode: entry.unix_mode().unwrap_or(if is_dir { 0o755 } else { 0o644 }),
+                owner: String::new(),
+                group: String::new(),
+            }
+            .into_file_info(archive),
+        );
+    }
+    Ok(members)
+}
+
+fn tar_members(archive: &Path, reader: impl Read) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    let mut tar = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        members.push(
+            Member {
+                inner: entry.path()?.to_string_lossy().to_string(),
+                is_dir: header.entry_type().is_dir(),
+                size: header.size()?,
+                // members of a tar are stored whole; a gzip stream is
+                // compressed as one, not per member
+                compressed_size: None,
+                modified: DateTime::from_timestamp(header.mtime()? as i64, 0).unwrap_or_default(),
+                mode: header.mode()?,
+                owner: header.username().ok().flatten().unwrap_or_default().to_string(),
+                group: header.groupname().ok().flatten().unwrap_or_default().to_string(),
+            }
+            .into_file_info(archive),
+        );
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_zip_and_tar_members() {
+        let dir = crate::testing::TempDir::new("archive");
+
+        let mut zip = zip::ZipWriter::new(File::create(dir.join("logs.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("logs/", options).unwrap();
+        zip.start_file("logs/app.log", options).unwrap();
+        zip.write_all(&[b'x'; 1000]).unwrap();
+        zip.finish().unwrap();
+
+        let gz = flate2::write::GzEncoder::new(File::create(dir.join("logs.tar.gz")).unwrap(), Default::default());
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o600);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        tar.append_data(&mut header, "notes/todo.txt", &b"hello"[..]).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let zipped = members(&dir.join("logs.zip")).unwrap();
+        assert_eq!(zipped.len(), 2);
+        assert_eq!((zipped[0].path.as_str(), zipped[0].name.as_str()), ("logs", "logs"));
+        assert_eq!((zipped[1].path.as_str(), zipped[1].size), ("logs/app.log", 1000));
+        assert!(zipped[1].compressed_size.unwrap() < 1000);
+
+        let tarred = members(&dir.join("logs.tar.gz")).unwrap();
+        assert_eq!((tarred[0].name.as_str(), tarred[0].size, tarred[0].mode), ("todo.txt", 5, 0o600));
+        assert_eq!(tarred[0].modified.timestamp(), 1_700_000_000);
+        assert!(members(&dir.join("missing.rar")).is_err());
+    }
+}
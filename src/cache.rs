@@ -0,0 +1,177 @@
+// The metadata cache: values that take reading a file's contents (`hash`,
+// `md5`, `mime`) are kept in `cache_dir()/metadata` keyed by path and field,
+// along with the size and modification time they were read at, so asking
+// again about an unchanged file costs a lookup instead of a read. A changed
+// file's value is replaced and a deleted file's is dropped when the cache is
+// next loaded. Plain metadata is not kept: checking it is still current
+// would take the same stat that reads it. The binary turns it on unless
+// given `--no-cache`; `lsql cache clear` deletes it.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::config;
+use crate::files::FileInfo;
+use crate::spill::{escape, unescape};
+
+// (path, field)
+type Key = (String, String);
+
+// what a value was read from: (size, modified seconds, and nanoseconds)
+type Stamp = (u64, i64, u32);
+
+type Values = HashMap<Key, (Stamp, String)>;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// read from disk on first use; the flag says whether it has unsaved changes
+static CACHE: Mutex<Option<(Values, bool)>> = Mutex::new(None);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// `$XDG_CACHE_HOME/lsql`, falling back to `~/.cache/lsql`
+// (`%LOCALAPPDATA%\lsql` on Windows)
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("lsql"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+            return Some(PathBuf::from(dir).join("lsql"));
+        }
+    }
+    config::home_dir().map(|home| home.join(".cache").join("lsql"))
+}
+
+fn cache_file() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("metadata"))
+}
+
+fn key(file: &FileInfo, field: &str) -> Key {
+    (file.path.clone(), field.to_string())
+}
+
+fn stamp(file: &FileInfo) -> Stamp {
+    (file.size, file.modified.timestamp(), file.modified.timestamp_subsec_nanos())
+}
+
+// one `path field size modified value` line per value, tab separated, with
+// the modification time as `seconds.nanoseconds`
+fn encode((path, field): &Key, (size, seconds, nanos): &Stamp, value: &str) -> String {
+    format!("{}\t{}\t{}\t{}.{:09}\t{}", escape(path), field, size, seconds, nanos, escape(value))
+}
+
+fn decode(line: &str) -> Option<(Key, (Stamp, String))> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [path, field, size, modified, value] = fields[..] else {
+        return None;
+    };
+    let (seconds, nanos) = modified.split_once('.')?;
+    let stamp = (size.parse().ok()?, seconds.parse().ok()?, nanos.parse().ok()?);
+    Some(((unescape(path), field.to_string()), (stamp, unescape(value))))
+}
+
+// a damaged line is skipped rather than losing the rest, and values of
+// files that are gone are dropped; the flag says whether any were
+fn load() -> (Values, bool) {
+    let Some(file) = cache_file().and_then(|path| std::fs::File::open(path).ok()) else {
+        return (HashMap::new(), false);
+    };
+    let mut values: Values = BufReader::new(file).lines().map_while(Result::ok).filter_map(|line| decode(&line)).collect();
+    let count = values.len();
+    values.retain(|(path, _), _| Path::new(path).exists());
+    let pruned = values.len() < count;
+    (values, pruned)
+}
+
+// the `field` of `file` from an earlier run, if the file has not changed since
+pub fn cached(file: &FileInfo, field: &str) -> Option<String> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let mut cache = CACHE.lock().unwrap();
+    let (values, _) = cache.get_or_insert_with(load);
+    match values.get(&key(file, field)) {
+        Some((stamp_then, value)) if *stamp_then == stamp(file) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+// keep `value` for the next run; an empty value is a file that could not be
+// read, which is tried again next time
+pub fn store(file: &FileInfo, field: &str, value: &str) {
+    if !ENABLED.load(Ordering::Relaxed) || value.is_empty() {
+        return;
+    }
+    let mut cache = CACHE.lock().unwrap();
+    let (values, dirty) = cache.get_or_insert_with(load);
+    // a value read before the file changed is replaced, not kept alongside
+    values.insert(key(file, field), (stamp(file), value.to_string()));
+    *dirty = true;
+}
+
+// the cached `field` of `file`, or `compute` it and remember it. The lock is
+// not held while computing, so other threads can look values up meanwhile
+pub fn remember(file: &FileInfo, field: &str, compute: impl FnOnce() -> String) -> String {
+    if let Some(value) = cached(file, field) {
+        return value;
+    }
+    let value = compute();
+    store(file, field, &value);
+    value
+}
+
+// write values learned since the last save, if any
+pub fn save() -> io::Result<()> {
+    let mut cache = CACHE.lock().unwrap();
+    let Some((values, dirty)) = cache.as_mut().filter(|(_, dirty)| *dirty) else {
+        return Ok(());
+    };
+    let Some(path) = cache_file() else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+    // written aside and renamed, so a crash never leaves half a cache
+    let partial = path.with_extension("partial");
+    let mut out = BufWriter::new(std::fs::File::create(&partial)?);
+    for (key, (stamp, value)) in values.iter() {
+        writeln!(out, "{}", encode(key, stamp, value))?;
+    }
+    out.flush()?;
+    drop(out);
+    std::fs::rename(partial, path)?;
+    *dirty = false;
+    Ok(())
+}
+
+// `lsql cache clear`: forget everything, on disk and in memory
+pub fn clear() -> io::Result<String> {
+    *CACHE.lock().unwrap() = None;
+    let Some(path) = cache_file().filter(|path| path.exists()) else {
+        return Ok("the metadata cache is already empty".to_string());
+    };
+    let size = std::fs::metadata(&path)?.len();
+    std::fs::remove_file(&path)?;
+    Ok(format!("removed {} ({})", path.display(), crate::files::human_readable_size(size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_round_trip() {
+        let key = ("/tmp/tab\there.txt".to_string(), "mime".to_string());
+        let stamp = (42, 1_700_000_000, 5);
+        let line = encode(&key, &stamp, "text/plain");
+        assert_eq!(line.split('\t').count(), 5);
+        assert_eq!(decode(&line), Some((key, (stamp, "text/plain".to_string()))));
+        assert_eq!(decode("/tmp/a.txt\tmime\tbig\t0.0\ttext/plain"), None);
+        // the layout before values were keyed by path and field alone
+        assert_eq!(decode("/tmp/a.txt\t42\t1700000000000000000\tmime\ttext/plain"), None);
+    }
+}
@@ -0,0 +1,70 @@
+// Stopping a statement part way, by Ctrl+C in the shell or `--timeout`.
+// Walks call `check` between entries and give up with
+// `LsqlError::Interrupted`, so a long scan ends and the shell returns to its
+// prompt instead of the whole process going away.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::LsqlError;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// when the running statement runs out of time, and the limit it was given
+static DEADLINE: Mutex<Option<(Instant, Duration)>> = Mutex::new(None);
+
+// called before each statement
+pub fn start(timeout: Option<Duration>) {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+    *DEADLINE.lock().unwrap() = timeout.map(|limit| (Instant::now() + limit, limit));
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// an error once the statement should stop
+pub fn check() -> Result<(), LsqlError> {
+    if interrupted() {
+        return Err(LsqlError::Interrupted("cancelled".to_string()));
+    }
+    match *DEADLINE.lock().unwrap() {
+        Some((deadline, limit)) if Instant::now() >= deadline => {
+            let limit = chrono::Duration::from_std(limit).unwrap_or_default();
+            Err(LsqlError::Interrupted(format!("timed out after {}", crate::files::human_duration(limit))))
+        }
+        _ => Ok(()),
+    }
+}
+
+// make Ctrl+C cancel the running statement rather than end the process. The
+// shell's line editor reads keys raw, so at the prompt it never gets here
+#[cfg(unix)]
+pub fn install() {
+    use nix::libc;
+
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+// Ctrl+C still ends the process here
+#[cfg(not(unix))]
+pub fn install() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout() {
+        start(Some(Duration::ZERO));
+        let error = check().unwrap_err();
+        assert_eq!((error.kind(), error.exit_code()), ("interrupted", 130));
+        start(None);
+        assert!(check().is_ok());
+    }
+}
@@ -0,0 +1,321 @@
+// command line flags; anything that is not a flag or subcommand is the query to run
+
+use std::str::FromStr;
+
+use crate::error::LsqlError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subcommand {
+    Version,
+    Wizard,
+    PluginsList,
+    CacheClear,
+    IndexBuild(String),
+    IndexList,
+    IndexDrop(String),
+    GenFixture(crate::fixture::Spec),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Grid,
+    Json,
+    Ndjson,
+    Csv,
+    Markdown,
+    Parquet,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "grid" => Ok(OutputFormat::Grid),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(format!("Unknown format '{}' (expected table, grid, json, ndjson, csv, markdown or parquet)", s)),
+        }
+    }
+}
+
+// how name/path equality treats case
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PathCase {
+    // follow what the filesystem being queried does
+    #[default]
+    Auto,
+    Sensitive,
+    Insensitive,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub subcommand: Option<Subcommand>,
+    pub verbose: bool,
+    pub compute_dir_sizes: bool,
+    pub dry_run: bool,
+    // print a mutating statement as shell commands instead of running it
+    pub emit_script: bool,
+    pub path_case: PathCase,
+    // compare text in WHERE without regard to case, as ILIKE does
+    pub ignore_case: bool,
+    pub format: OutputFormat,
+    pub output: Option<String>,
+    // add to `output` rather than replacing it, for the shell's `>>`
+    pub append: bool,
+    // bytes a query may hold before sorting spills to disk
+    pub max_memory: Option<u64>,
+    // levels below a directory to read when a query has no DEPTH
+    pub max_depth: Option<usize>,
+    // glob patterns for entries whose subtrees are never walked
+    pub exclude: Vec<String>,
+    // throttle walking and lower the process priority
+    pub nice: bool,
+    // explain on stderr why entries failed the WHERE clause
+    pub trace_filter: bool,
+    // DELETE moves entries to the trash instead of removing them
+    pub trash: bool,
+    // show dates as `3 days ago`
+    pub relative_times: bool,
+    // list like `ls -l`: mode, owner, group, size, modified and name, aligned without borders
+    pub long: bool,
+    // sort keys for SELECT statements without ORDER BY
+    pub sort: Vec<crate::files::SortKey>,
+    // ORDER BY and --sort compare text naturally, as ORDER BY ... NATURAL does
+    pub natural_sort: bool,
+    // show sizes as exact byte counts
+    pub bytes: bool,
+    // print totals (files, dirs, size, modified range) after the results
+    pub summary: bool,
+    // start names with an icon for their kind in tables and grids
+    pub icons: bool,
+    // shorten table cells longer than this with `…`
+    pub max_width: Option<usize>,
+    // run DELETE without listing the entries and asking first
+    pub yes: bool,
+    // retry DELETE and UPDATE changes refused for lack of permission with sudo
+    pub sudo: bool,
+    // report entries scanned and matched after each statement
+    pub stats: bool,
+    // stop a statement that has run this long
+    pub timeout: Option<std::time::Duration>,
+    // a FROM path naming a zip or tar file lists its members, as archive() does
+    pub archives: bool,
+    // walk directories even when they have an index
+    pub no_index: bool,
+    // neither read nor write the metadata cache
+    pub no_cache: bool,
+    // skip the size probe before recursive queries
+    pub no_estimate: bool,
+    // keep entries found under more than one overlapping FROM root
+    pub no_dedupe: bool,
+    // print long results in the shell rather than paging them
+    pub no_pager: bool,
+    // a file of statements to run instead of a query or the shell
+    pub file: Option<String>,
+    // run the rest of a --file or `source` script after a statement fails
+    pub keep_going: bool,
+    pub query: Vec<String>,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, LsqlError> {
+    parse_flags(args).map_err(LsqlError::Usage)
+}
+
+fn parse_flags(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter().enumerate();
+    while let Some((index, arg)) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| format!("Flag '{}' expects a value", flag))
+        };
+        match arg.as_str() {
+            "version" if index == 0 => options.subcommand = Some(Subcommand::Version),
+            "--version" | "-V" => options.subcommand = Some(Subcommand::Version),
+            "wizard" if index == 0 => options.subcommand = Some(Subcommand::Wizard),
+            "plugins" if index == 0 => match args.next().map(|(_, action)| action.as_str()) {
+                Some("list") | None => options.subcommand = Some(Subcommand::PluginsList),
+                Some(other) => return Err(format!("Unknown plugins command '{}'", other)),
+            },
+            "cache" if index == 0 => match args.next().map(|(_, action)| action.as_str()) {
+                Some("clear") => options.subcommand = Some(Subcommand::CacheClear),
+                Some(other) => return Err(format!("Unknown cache command '{}'", other)),
+                None => return Err("cache expects a command: lsql cache clear".to_string()),
+            },
+            "index" if index == 0 => {
+                let action = args.next().map(|(_, action)| action.as_str());
+                let dir = args.next().map(|(_, dir)| dir.clone());
+                options.subcommand = Some(match (action, dir) {
+                    (Some("build"), Some(dir)) => Subcommand::IndexBuild(dir),
+                    (Some("drop"), Some(dir)) => Subcommand::IndexDrop(dir),
+                    (Some("list") | None, _) => Subcommand::IndexList,
+                    (Some("build" | "drop"), None) => return Err("index build and index drop take a directory".to_string()),
+                    (Some(other), _) => return Err(format!("Unknown index command '{}'", other)),
+                });
+            }
+            "gen-fixture" if index == 0 => {
+                let rest: Vec<String> = args.by_ref().map(|(_, arg)| arg.clone()).collect();
+                options.subcommand = Some(Subcommand::GenFixture(crate::fixture::Spec::parse(&rest)?));
+            }
+            "--verbose" | "-v" => options.verbose = true,
+            "--compute-dir-sizes" => options.compute_dir_sizes = true,
+            "--dry-run" | "-n" => options.dry_run = true,
+            "--emit-script" => options.emit_script = true,
+            "--no-estimate" => options.no_estimate = true,
+            "--no-pager" => options.no_pager = true,
+            "--keep-going" => options.keep_going = true,
+            "--no-cache" => options.no_cache = true,
+            "--no-index" => options.no_index = true,
+            "--no-dedupe" => options.no_dedupe = true,
+            "--archives" => options.archives = true,
+            "--nice" => options.nice = true,
+            "--trace-filter" => options.trace_filter = true,
+            "--stats" => options.stats = true,
+            "--sudo" => options.sudo = true,
+            "--trash" => options.trash = true,
+            "--yes" | "-y" => options.yes = true,
+            "--relative-times" => options.relative_times = true,
+            "--long" | "-l" => options.long = true,
+            "--icons" => options.icons = true,
+            "--summary" => options.summary = true,
+            "--bytes" => options.bytes = true,
+            "--ignore-case" | "-i" => options.ignore_case = true,
+            "--case-sensitive-paths" => options.path_case = PathCase::Sensitive,
+            "--case-insensitive-paths" => options.path_case = PathCase::Insensitive,
+            "--format" | "-f" => options.format = value(arg)?.parse()?,
+            "--output" | "-o" => options.output = Some(value(arg)?),
+            "--file" => options.file = Some(value(arg)?),
+            "--exclude" => options.exclude.push(value(arg)?),
+            "--max-depth" => {
+                let depth = value(arg)?;
+                match depth.parse() {
+                    Ok(depth) if depth > 0 => options.max_depth = Some(depth),
+                    _ => return Err(format!("--max-depth expects a positive number, got '{}'", depth)),
+                }
+            }
+            "--timeout" => {
+                let limit = crate::filter::parse_duration(&value(arg)?)?;
+                options.timeout = Some(limit.to_std().map_err(|_| format!("--timeout expects a positive duration, got '{}'", limit))?);
+            }
+            "--max-width" => options.max_width = Some(crate::display::parse_width(&value(arg)?)?),
+            "--sort" => options.sort = crate::files::SortKey::parse_list(&value(arg)?)?,
+            "--max-memory" => options.max_memory = Some(crate::filter::parse_size(&value(arg)?)?),
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag '{}'", flag)),
+            _ => options.query.push(arg.clone()),
+        }
+    }
+    if options.file.is_some() && !options.query.is_empty() {
+        return Err("--file runs the statements in a file; give a query or a file, not both".to_string());
+    }
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_format_and_output_flags() {
+        let options = parse_args(&args(&["--format", "parquet", "-o", "out.parquet", "select * from ."])).unwrap();
+        assert_eq!(options.format, OutputFormat::Parquet);
+        assert_eq!(options.output.as_deref(), Some("out.parquet"));
+        assert_eq!(options.query, args(&["select * from ."]));
+        assert!(!options.nice);
+        assert!(parse_args(&args(&["--nice", "show"])).unwrap().nice);
+        assert!(parse_args(&args(&["--stats", "show"])).unwrap().stats);
+        assert!(parse_args(&args(&["--sudo", "show"])).unwrap().sudo);
+        assert!(parse_args(&args(&["--trash", "show"])).unwrap().trash);
+        assert!(parse_args(&args(&["-y", "show"])).unwrap().yes);
+        assert!(parse_args(&args(&["--relative-times", "show"])).unwrap().relative_times);
+        assert!(parse_args(&args(&["-i", "show"])).unwrap().ignore_case);
+        assert!(parse_args(&args(&["-l", "show"])).unwrap().long);
+        assert!(parse_args(&args(&["--icons", "show"])).unwrap().icons);
+        assert!(parse_args(&args(&["--summary", "show"])).unwrap().summary);
+        assert!(parse_args(&args(&["--bytes", "show"])).unwrap().bytes);
+        let options = parse_args(&args(&["--file", "queries.lsql", "--keep-going"])).unwrap();
+        assert_eq!((options.file.as_deref(), options.keep_going), (Some("queries.lsql"), true));
+        assert!(parse_args(&args(&["--file", "queries.lsql", "show"])).is_err());
+        let sort = parse_args(&args(&["--sort", "size:desc,Name", "show"])).unwrap().sort;
+        assert_eq!((sort[0].field.as_str(), sort[0].descending, sort[1].field.as_str(), sort[1].descending), ("size", true, "name", false));
+        assert!(parse_args(&args(&["--sort", "size:down"])).is_err());
+        assert!(parse_args(&args(&["--sort", "sighs"])).is_err());
+        assert!(parse_args(&args(&["--no-cache", "show"])).unwrap().no_cache);
+        assert_eq!(parse_args(&args(&["cache", "clear"])).unwrap().subcommand, Some(Subcommand::CacheClear));
+        assert!(parse_args(&args(&["cache", "flush"])).is_err());
+        assert_eq!(parse_args(&args(&["index", "build", "~/src"])).unwrap().subcommand, Some(Subcommand::IndexBuild("~/src".to_string())));
+        assert_eq!(parse_args(&args(&["index"])).unwrap().subcommand, Some(Subcommand::IndexList));
+        assert!(parse_args(&args(&["index", "drop"])).is_err());
+        assert!(parse_args(&args(&["--no-index", "show"])).unwrap().no_index);
+        assert!(parse_args(&args(&["--archives", "show"])).unwrap().archives);
+    }
+
+    #[test]
+    fn test_gen_fixture() {
+        let options = parse_args(&args(&["gen-fixture", "./sandbox", "--files", "500", "--sizes", "uniform"])).unwrap();
+        match options.subcommand {
+            Some(Subcommand::GenFixture(spec)) => assert_eq!((spec.dir.as_str(), spec.files), ("./sandbox", 500)),
+            other => panic!("expected gen-fixture, got {:?}", other),
+        }
+        assert!(parse_args(&args(&["gen-fixture", "./sandbox", "--sizes", "normal"])).is_err());
+    }
+
+    #[test]
+    fn test_flag_errors() {
+        assert!(parse_args(&args(&["--format"])).is_err());
+        assert!(parse_args(&args(&["--format", "yaml"])).is_err());
+        assert_eq!(parse_args(&args(&["-f", "NDJSON"])).unwrap().format, OutputFormat::Ndjson);
+        assert_eq!(parse_args(&args(&["-f", "grid"])).unwrap().format, OutputFormat::Grid);
+        assert_eq!(parse_args(&args(&["-f", "md"])).unwrap().format, OutputFormat::Markdown);
+        assert!(parse_args(&args(&["--frobnicate"])).is_err());
+    }
+
+    #[test]
+    fn test_exclude_repeats() {
+        let options = parse_args(&args(&["--exclude", "node_modules", "--exclude", "*.min.js", "show"])).unwrap();
+        assert_eq!(options.exclude, args(&["node_modules", "*.min.js"]));
+        assert_eq!(options.query, args(&["show"]));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert_eq!(parse_args(&args(&["--max-depth", "3"])).unwrap().max_depth, Some(3));
+        assert!(parse_args(&args(&["--max-depth", "0"])).is_err());
+        assert!(parse_args(&args(&["--max-depth", "deep"])).is_err());
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let options = parse_args(&args(&["--max-memory", "256mb", "show"])).unwrap();
+        assert_eq!(options.max_memory, Some(256 * 1024 * 1024));
+        assert!(parse_args(&args(&["--max-memory", "lots"])).is_err());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let options = parse_args(&args(&["--timeout", "90s", "show"])).unwrap();
+        assert_eq!(options.timeout, Some(std::time::Duration::from_secs(90)));
+        assert!(parse_args(&args(&["--timeout", "soon"])).is_err());
+    }
+
+    #[test]
+    fn test_path_case_flags() {
+        assert_eq!(parse_args(&args(&["show"])).unwrap().path_case, PathCase::Auto);
+        let options = parse_args(&args(&["--case-sensitive-paths", "show"])).unwrap();
+        assert_eq!(options.path_case, PathCase::Sensitive);
+        let options = parse_args(&args(&["--case-insensitive-paths", "show"])).unwrap();
+        assert_eq!(options.path_case, PathCase::Insensitive);
+    }
+}
@@ -0,0 +1,121 @@
+// locations of per-user lsql files
+
+use std::path::PathBuf;
+
+// `$XDG_CONFIG_HOME/lsql`, falling back to `~/.config/lsql`
+// (`%APPDATA%\lsql` on Windows)
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("lsql"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("APPDATA") {
+            return Some(PathBuf::from(dir).join("lsql"));
+        }
+    }
+    home_dir().map(|home| home.join(".config").join("lsql"))
+}
+
+// what `~` stands for in paths
+pub fn home_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").filter(|h| !h.is_empty());
+    let home = if cfg!(windows) { home.or_else(|| std::env::var_os("USERPROFILE")) } else { home };
+    home.map(PathBuf::from)
+}
+
+// defaults from `config_dir()/config`, one `key = value` per line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    // DELETE moves entries to the trash unless it says PERMANENT
+    pub trash: bool,
+    // dates are shown as `3 days ago`
+    pub relative_times: bool,
+    // names start with an icon for their kind
+    pub icons: bool,
+    // totals are printed after the results
+    pub summary: bool,
+    // sorting text compares runs of digits as numbers
+    pub natural_sort: bool,
+    // characters a table cell may take before it is shortened with `…`
+    pub max_width: Option<usize>,
+}
+
+pub fn parse_settings(contents: &str) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+        let flag = || match value.trim() {
+            "true" | "yes" | "on" => Ok(true),
+            "false" | "no" | "off" => Ok(false),
+            other => Err(format!("line {}: expected true or false, got '{}'", number + 1, other)),
+        };
+        match key.trim() {
+            "trash" => settings.trash = flag()?,
+            "relative_times" => settings.relative_times = flag()?,
+            "icons" => settings.icons = flag()?,
+            "summary" => settings.summary = flag()?,
+            "natural_sort" => settings.natural_sort = flag()?,
+            "max_width" => {
+                let width = crate::display::parse_width(value.trim()).map_err(|e| format!("line {}: {}", number + 1, e))?;
+                settings.max_width = Some(width);
+            }
+            other => return Err(format!("line {}: unknown key '{}'", number + 1, other)),
+        }
+    }
+    Ok(settings)
+}
+
+// the settings as `key = value` lines, in the form the file takes them
+pub fn describe(settings: &Settings) -> String {
+    let keys = [
+        ("trash", settings.trash),
+        ("relative_times", settings.relative_times),
+        ("icons", settings.icons),
+        ("summary", settings.summary),
+        ("natural_sort", settings.natural_sort),
+    ];
+    let mut out: String = keys.iter().map(|(key, value)| format!("{} = {}\n", key, value)).collect();
+    if let Some(width) = settings.max_width {
+        out.push_str(&format!("max_width = {}\n", width));
+    }
+    out
+}
+
+// the user's settings; a missing file means the defaults, and a broken one
+// is reported and ignored rather than stopping lsql from starting
+pub fn load() -> Settings {
+    let Some(path) = config_dir().map(|dir| dir.join("config")) else {
+        return Settings::default();
+    };
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return Settings::default();
+    };
+    parse_settings(&contents).unwrap_or_else(|e| {
+        eprintln!("warning: ignoring {}: {}", path.display(), e);
+        Settings::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        assert_eq!(parse_settings("# defaults\ntrash = yes\n").unwrap(), Settings { trash: true, ..Settings::default() });
+        assert!(parse_settings("relative_times = on").unwrap().relative_times);
+        assert_eq!(parse_settings("").unwrap(), Settings::default());
+        assert!(parse_settings("trash = maybe").is_err());
+        assert!(parse_settings("colour = red").is_err());
+        assert_eq!(parse_settings("max_width = 40").unwrap().max_width, Some(40));
+        assert!(parse_settings("max_width = 2").is_err());
+        let settings = Settings { icons: true, max_width: Some(60), ..Settings::default() };
+        assert_eq!(parse_settings(&describe(&settings)).unwrap(), settings);
+    }
+}
@@ -0,0 +1,987 @@
+use std::error::Error;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use colored::Colorize;
+use comfy_table::{CellAlignment, Table};
+
+use crate::cli::OutputFormat;
+use crate::error::LsqlError;
+use crate::expr;
+use crate::files::{self, FieldKind, FieldValue, FileInfo};
+use crate::parser::{Expression, SelectItem, SelectionType};
+
+// columns shown for `select *`
+pub const DEFAULT_COLUMNS: &[&str] = &["name", "permissions", "size", "modified"];
+
+// columns shown for `select *` with `--long`, in the order `ls -l` uses
+pub const LONG_COLUMNS: &[&str] = &["permissions", "owner", "group", "size", "modified", "name"];
+
+// show dates as `3 days ago` rather than a timestamp
+static RELATIVE_TIMES: AtomicBool = AtomicBool::new(false);
+// `--long`: the `ls -l` columns, aligned without borders
+static LONG_LISTING: AtomicBool = AtomicBool::new(false);
+// `--icons`: names in tables and grids start with an icon for their kind
+static ICONS: AtomicBool = AtomicBool::new(false);
+// `--bytes`: sizes as exact byte counts rather than `1.20 MB`
+static BYTES: AtomicBool = AtomicBool::new(false);
+// `--summary`: a line of totals after tables, long listings and grids
+static SUMMARY: AtomicBool = AtomicBool::new(false);
+// `--max-width`: characters a cell may take in tables, long listings and
+// grids before it is shortened; 0 for no limit
+static MAX_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+// room for a little text on either side of the `…`
+const NARROWEST: usize = 8;
+
+pub fn use_relative_times() {
+    RELATIVE_TIMES.store(true, Ordering::Relaxed);
+}
+
+pub fn use_long_listing() {
+    LONG_LISTING.store(true, Ordering::Relaxed);
+}
+
+pub fn use_icons() {
+    ICONS.store(true, Ordering::Relaxed);
+}
+
+pub fn use_bytes() {
+    BYTES.store(true, Ordering::Relaxed);
+}
+
+pub fn use_summary() {
+    SUMMARY.store(true, Ordering::Relaxed);
+}
+
+pub fn use_max_width(width: usize) {
+    MAX_WIDTH.store(width, Ordering::Relaxed);
+}
+
+// a `--max-width` or `max_width` value
+pub fn parse_width(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(width) if width >= NARROWEST => Ok(width),
+        _ => Err(format!("max width expects a number of characters, at least {}, got '{}'", NARROWEST, text)),
+    }
+}
+
+// `text` in at most `width` characters: paths keep their first directory
+// and as many trailing components as fit (`/home/…/project/file.rs`), other
+// text keeps its start
+pub fn shorten(text: &str, width: usize, is_path: bool) -> String {
+    let length = text.chars().count();
+    if length <= width {
+        return text.to_string();
+    }
+    if !is_path {
+        return text.chars().take(width - 1).chain(['…']).collect();
+    }
+    let separator = if text.contains('/') { "/" } else { "\\" };
+    let parts: Vec<&str> = text.split(separator).collect();
+    // the first directory, with the empty part before a leading `/`
+    let head = if parts[0].is_empty() { 2 } else { 1 };
+    if parts.len() > head + 1 {
+        let mut kept = 0;
+        let mut used = parts[..head].join(separator).chars().count() + 2;
+        // at least one component is left out, or there would be nothing to shorten
+        for part in parts.iter().rev().take(parts.len() - head - 1) {
+            used += part.chars().count() + 1;
+            if used > width {
+                break;
+            }
+            kept += 1;
+        }
+        if kept > 0 {
+            let (first, last) = (parts[..head].join(separator), parts[parts.len() - kept..].join(separator));
+            return format!("{}{}…{}{}", first, separator, separator, last);
+        }
+    }
+    // a single long name: its end tells most
+    let tail: String = text.chars().skip(length + 1 - width).collect();
+    format!("…{}", tail)
+}
+
+// what `--summary` reports, added up row by row as a sink receives them
+#[derive(Debug, Default)]
+pub struct Totals {
+    files: usize,
+    dirs: usize,
+    other: usize,
+    size: u64,
+    modified: Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>,
+}
+
+impl Totals {
+    pub fn add(&mut self, file: &FileInfo) {
+        match file.file_type {
+            files::FileType::File => {
+                self.files += 1;
+                self.size += file.size;
+            }
+            files::FileType::Directory => self.dirs += 1,
+            files::FileType::Other => self.other += 1,
+        }
+        self.modified = Some(match self.modified {
+            Some((oldest, newest)) => (oldest.min(file.modified), newest.max(file.modified)),
+            None => (file.modified, file.modified),
+        });
+    }
+
+    // `3 files, 1 dir, 12.40 KB, modified 2024-01-02 10:00:00 to 2024-05-01 12:00:00`
+    pub fn line(&self) -> String {
+        let count = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+        let mut parts = vec![count(self.files, "file", "files"), count(self.dirs, "dir", "dirs")];
+        if self.other > 0 {
+            parts.push(count(self.other, "other", "others"));
+        }
+        parts.push(match BYTES.load(Ordering::Relaxed) {
+            true => format!("{} bytes", self.size),
+            false => files::human_readable_size(self.size),
+        });
+        if let Some((oldest, newest)) = self.modified {
+            let format = |date: chrono::DateTime<chrono::Utc>| date.format("%Y-%m-%d %H:%M:%S").to_string();
+            parts.push(format!("modified {} to {}", format(oldest), format(newest)));
+        }
+        parts.join(", ")
+    }
+}
+
+// the totals line, when `--summary` asked for one
+fn write_summary(out: &mut dyn Write, totals: &Totals) -> std::io::Result<()> {
+    if SUMMARY.load(Ordering::Relaxed) {
+        writeln!(out, "{}", totals.line())?;
+    }
+    Ok(())
+}
+
+// the icon shown before an entry's name, by type and then extension
+pub fn icon(file: &FileInfo) -> &'static str {
+    match file.file_type {
+        files::FileType::Directory => return "📁",
+        files::FileType::Other => return "🔗",
+        files::FileType::File => {}
+    }
+    match file.extension().to_lowercase().as_str() {
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "bmp" | "ico" | "tiff" | "heic" => "🎨",
+        "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" => "📦",
+        "mp3" | "flac" | "wav" | "ogg" | "m4a" => "🎵",
+        "mp4" | "mkv" | "mov" | "avi" | "webm" => "🎬",
+        "rs" | "py" | "js" | "ts" | "go" | "c" | "h" | "cpp" | "hpp" | "java" | "kt" | "rb" | "php" | "swift" | "cs" | "sh"
+        | "lua" | "zig" => "📜",
+        "md" | "txt" | "pdf" | "doc" | "docx" | "rst" | "org" => "📝",
+        _ => "📄",
+    }
+}
+
+// the fields `select **` shows: all of them but those that read file
+// contents or subtrees, which would make it slow on any real directory
+pub fn cheap_fields() -> impl Iterator<Item = &'static str> {
+    files::FIELDS.iter().copied().filter(|field| !files::EXPENSIVE_FIELDS.iter().any(|(expensive, _)| expensive == field))
+}
+
+// a rendered column: a plain field or a computed expression under its name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub expression: Expression,
+}
+
+impl Column {
+    fn new(item: &SelectItem) -> Self {
+        let name = match (&item.alias, &item.expression) {
+            (Some(alias), _) => alias.clone(),
+            (None, Expression::Field(field)) => field.to_lowercase(),
+            (None, expression) => expression.to_string(),
+        };
+        Column { name, expression: item.expression.clone() }
+    }
+
+    // the value for one entry; plain fields keep their display formatting
+    pub fn cell(&self, file: &FileInfo) -> Result<String, String> {
+        match &self.expression {
+            Expression::Field(field) if RELATIVE_TIMES.load(Ordering::Relaxed) || BYTES.load(Ordering::Relaxed) => {
+                match file.field(field) {
+                    Some(FieldValue::Date(date)) if RELATIVE_TIMES.load(Ordering::Relaxed) => Ok(files::relative_time(date)),
+                    Some(FieldValue::Size(size)) if BYTES.load(Ordering::Relaxed) => Ok(size.to_string()),
+                    _ => Ok(file.display_field(field).unwrap_or_default()),
+                }
+            }
+            Expression::Field(field) => Ok(file.display_field(field).unwrap_or_default()),
+            expression => expr::evaluate(expression, file).map(|value| value.to_text()),
+        }
+    }
+}
+
+// the columns to render, in the order they were selected
+pub fn columns(selection: &SelectionType) -> Result<Vec<Column>, LsqlError> {
+    let defaults = || {
+        let names = if LONG_LISTING.load(Ordering::Relaxed) { LONG_COLUMNS } else { DEFAULT_COLUMNS };
+        names.iter().map(|c| Column::new(&SelectItem::field(c)))
+    };
+    let (mut columns, items): (Vec<Column>, &[SelectItem]) = match selection {
+        SelectionType::All | SelectionType::Files | SelectionType::Directories => (defaults().collect(), &[]),
+        SelectionType::Every => (cheap_fields().map(|c| Column::new(&SelectItem::field(c))).collect(), &[]),
+        SelectionType::Fields(items) => (Vec::new(), items),
+        SelectionType::AllWith(items) => (defaults().collect(), items),
+        SelectionType::Count => return Err("count(*) has no columns to show".into()),
+    };
+    for item in items {
+        expr::check_fields(&item.expression)?;
+        let column = Column::new(item);
+        if columns.iter().any(|c| c.name == column.name) {
+            return Err(format!("Column '{}' is selected more than once", column.name).into());
+        }
+        columns.push(column);
+    }
+    Ok(columns)
+}
+
+fn header(column: &str) -> String {
+    // computed columns without an alias are shown as written
+    if !column.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return column.to_string();
+    }
+    column
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn cells(file: &FileInfo, columns: &[Column]) -> Result<Vec<String>, String> {
+    columns.iter().map(|c| c.cell(file)).collect()
+}
+
+// cells for people to read: with `--icons`, names get their icon
+fn shown_cells(file: &FileInfo, columns: &[Column]) -> Result<Vec<String>, String> {
+    let mut cells = cells(file, columns)?;
+    let width = MAX_WIDTH.load(Ordering::Relaxed);
+    if width > 0 {
+        for (cell, column) in cells.iter_mut().zip(columns) {
+            let is_path = matches!(&column.expression, Expression::Field(field) if field == "path" || field == "root");
+            if !is_numeric(column) {
+                *cell = shorten(cell, width, is_path);
+            }
+        }
+    }
+    if ICONS.load(Ordering::Relaxed) {
+        for (cell, column) in cells.iter_mut().zip(columns) {
+            if matches!(&column.expression, Expression::Field(field) if field == "name") {
+                *cell = format!("{} {}", icon(file), cell);
+            }
+        }
+    }
+    Ok(cells)
+}
+
+// sizes, numbers and durations line up on their last digit
+fn is_numeric(column: &Column) -> bool {
+    matches!(expr::kind(&column.expression), Some(FieldKind::Size | FieldKind::Number | FieldKind::Duration))
+}
+
+// headers, with numbers and dates right-aligned so their digits line up
+fn set_header(table: &mut Table, columns: &[Column]) {
+    table.set_header(columns.iter().map(|c| header(&c.name)));
+    for (i, column) in columns.iter().enumerate() {
+        let right = is_numeric(column) || expr::kind(&column.expression) == Some(FieldKind::Date);
+        if let (true, Some(table_column)) = (right, table.column_mut(i)) {
+            table_column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+}
+
+pub fn table(files: &[FileInfo], columns: &[Column]) -> Result<Table, String> {
+    let mut table = Table::new();
+    set_header(&mut table, columns);
+    for file in files {
+        table.add_row(shown_cells(file, columns)?);
+    }
+    Ok(table)
+}
+
+// what a sink is told once every row has been handed to it
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub rows: usize,
+    pub elapsed: Duration,
+}
+
+// where SELECT rows go. `entry` is called once per row, in order, between
+// `begin` and `finish`, so a sink may write rows as they come or gather
+// them; programs embedding lsql implement it for formats of their own
+pub trait OutputSink {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>>;
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>>;
+    fn finish(&mut self, summary: &Summary) -> Result<(), Box<dyn Error>>;
+}
+
+// the sink for a text `--format`; parquet needs a file and lives in `export`
+pub fn sink(format: OutputFormat, out: Box<dyn Write>) -> Box<dyn OutputSink> {
+    match format {
+        OutputFormat::Table | OutputFormat::Parquet if LONG_LISTING.load(Ordering::Relaxed) => Box::new(LongSink::new(out)),
+        OutputFormat::Table | OutputFormat::Parquet => Box::new(TableSink::new(out)),
+        OutputFormat::Grid => Box::new(GridSink::new(out, terminal_width())),
+        OutputFormat::Csv => Box::new(CsvSink::new(out)),
+        OutputFormat::Markdown => Box::new(MarkdownSink::new(out)),
+        OutputFormat::Json => Box::new(JsonSink::new(out, false)),
+        OutputFormat::Ndjson => Box::new(JsonSink::new(out, true)),
+    }
+}
+
+// the default table; its column widths depend on every row, so rows are
+// gathered and the table is written at the end
+pub struct TableSink<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+    table: Table,
+    totals: Totals,
+}
+
+impl<W: Write> TableSink<W> {
+    pub fn new(out: W) -> Self {
+        TableSink { out, columns: Vec::new(), table: Table::new(), totals: Totals::default() }
+    }
+}
+
+impl<W: Write> OutputSink for TableSink<W> {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        self.columns = columns.to_vec();
+        set_header(&mut self.table, columns);
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        self.table.add_row(shown_cells(file, &self.columns)?);
+        self.totals.add(file);
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "{}", self.table)?;
+        write_summary(&mut self.out, &self.totals)?;
+        Ok(self.out.flush()?)
+    }
+}
+
+// `--long`: one line per entry with columns padded to line up and no header
+// or borders, like `ls -l`, so the output greps cleanly. Numbers are
+// right-aligned; the last column is not padded
+pub struct LongSink<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+    totals: Totals,
+}
+
+impl<W: Write> LongSink<W> {
+    pub fn new(out: W) -> Self {
+        LongSink { out, columns: Vec::new(), rows: Vec::new(), totals: Totals::default() }
+    }
+}
+
+impl<W: Write> OutputSink for LongSink<W> {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        self.rows.push(shown_cells(file, &self.columns)?);
+        self.totals.add(file);
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|i| self.rows.iter().map(|row| row[i].chars().count()).max().unwrap_or(0))
+            .collect();
+        let numeric: Vec<bool> = self.columns.iter().map(is_numeric).collect();
+        for row in &self.rows {
+            let last = row.len().saturating_sub(1);
+            let line: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| match (numeric[i], i == last) {
+                    (true, _) => format!("{:>width$}", cell, width = widths[i]),
+                    (false, false) => format!("{:<width$}", cell, width = widths[i]),
+                    (false, true) => cell.clone(),
+                })
+                .collect();
+            writeln!(self.out, "{}", line.join("  "))?;
+        }
+        write_summary(&mut self.out, &self.totals)?;
+        Ok(self.out.flush()?)
+    }
+}
+
+// columns the terminal has room for; 80 when output is not a terminal, as `ls -C` does
+fn terminal_width() -> usize {
+    let from_env = || std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok());
+    Table::new().width().map(usize::from).or_else(from_env).unwrap_or(80)
+}
+
+// names laid out down then across in as few rows as fit in `width`, as `ls` does
+pub fn grid(names: &[String], width: usize) -> String {
+    const GAP: usize = 2;
+    let lengths: Vec<usize> = names.iter().map(|name| name.chars().count()).collect();
+    let mut rows = names.len().max(1);
+    let mut widths = vec![lengths.iter().copied().max().unwrap_or(0)];
+    // try the most columns the shortest name allows first, down to two
+    let shortest = lengths.iter().copied().min().unwrap_or(0);
+    for columns in (2..=((width + GAP) / (shortest + GAP)).min(names.len())).rev() {
+        let candidate = names.len().div_ceil(columns);
+        let candidate_widths: Vec<usize> = lengths.chunks(candidate).map(|column| column.iter().copied().max().unwrap_or(0)).collect();
+        if candidate_widths.iter().sum::<usize>() + GAP * (candidate_widths.len() - 1) <= width {
+            rows = candidate;
+            widths = candidate_widths;
+            break;
+        }
+    }
+    let mut out = String::new();
+    for row in 0..rows.min(names.len()) {
+        let line: Vec<String> = (row..names.len())
+            .step_by(rows)
+            .enumerate()
+            .map(|(column, index)| format!("{:<width$}", names[index], width = widths[column]))
+            .collect();
+        out.push_str(line.join(&" ".repeat(GAP)).trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+// `--format grid`: just the names, in columns that fill the terminal
+pub struct GridSink<W: Write> {
+    out: W,
+    width: usize,
+    names: Vec<String>,
+    totals: Totals,
+}
+
+impl<W: Write> GridSink<W> {
+    pub fn new(out: W, width: usize) -> Self {
+        GridSink { out, width, names: Vec::new(), totals: Totals::default() }
+    }
+}
+
+impl<W: Write> OutputSink for GridSink<W> {
+    fn begin(&mut self, _columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        let name = match MAX_WIDTH.load(Ordering::Relaxed) {
+            0 => file.name.clone(),
+            width => shorten(&file.name, width, false),
+        };
+        self.names.push(match ICONS.load(Ordering::Relaxed) {
+            true => format!("{} {}", icon(file), name),
+            false => name,
+        });
+        self.totals.add(file);
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        write!(self.out, "{}", grid(&self.names, self.width))?;
+        write_summary(&mut self.out, &self.totals)?;
+        Ok(self.out.flush()?)
+    }
+}
+
+// one line per row with the cells separated by tabs and no header, written
+// as each row comes, for another program to read (the shell's `|`)
+pub struct LinesSink<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+}
+
+impl<W: Write> LinesSink<W> {
+    pub fn new(out: W) -> Self {
+        LinesSink { out, columns: Vec::new() }
+    }
+}
+
+impl<W: Write> OutputSink for LinesSink<W> {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "{}", cells(file, &self.columns)?.join("\t"))?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        Ok(self.out.flush()?)
+    }
+}
+
+// comma separated values with a header line, quoted where needed (RFC 4180)
+pub struct CsvSink<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(out: W) -> Self {
+        CsvSink { out, columns: Vec::new() }
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// sizes as byte counts and durations as seconds, so other programs can sort
+// and sum them; anything else as the table shows it
+fn csv_value(column: &Column, file: &FileInfo) -> Result<String, String> {
+    match expr::kind(&column.expression) {
+        Some(FieldKind::Size | FieldKind::Duration) => match expr::evaluate(&column.expression, file)? {
+            FieldValue::Size(size) => Ok(size.to_string()),
+            FieldValue::Duration(duration) => Ok(duration.num_seconds().to_string()),
+            other => Ok(other.to_text()),
+        },
+        _ => column.cell(file),
+    }
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        self.columns = columns.to_vec();
+        let names: Vec<String> = columns.iter().map(|c| csv_field(&c.name)).collect();
+        writeln!(self.out, "{}", names.join(","))?;
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        let row = self.columns.iter().map(|c| csv_value(c, file).map(|value| csv_field(&value))).collect::<Result<Vec<String>, String>>()?;
+        writeln!(self.out, "{}", row.join(","))?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        Ok(self.out.flush()?)
+    }
+}
+
+// a GitHub flavored Markdown table, cells as the table shows them and
+// sizes and numbers right aligned; rows are written as they come
+pub struct MarkdownSink<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+}
+
+impl<W: Write> MarkdownSink<W> {
+    pub fn new(out: W) -> Self {
+        MarkdownSink { out, columns: Vec::new() }
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+impl<W: Write> OutputSink for MarkdownSink<W> {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        self.columns = columns.to_vec();
+        let names: Vec<String> = columns.iter().map(|c| markdown_cell(&header(&c.name))).collect();
+        let rules: Vec<&str> = columns
+            .iter()
+            .map(|c| if is_numeric(c) { "---:" } else { "---" })
+            .collect();
+        writeln!(self.out, "| {} |", names.join(" | "))?;
+        writeln!(self.out, "| {} |", rules.join(" | "))?;
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        let row: Vec<String> = cells(file, &self.columns)?.iter().map(|cell| markdown_cell(cell)).collect();
+        writeln!(self.out, "| {} |", row.join(" | "))?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        Ok(self.out.flush()?)
+    }
+}
+
+// one JSON object per row keyed by column name, either in an array or, for
+// `lines`, one object per line (ndjson). Sizes are plain byte counts, dates
+// RFC 3339 and durations seconds, for other programs rather than people
+pub struct JsonSink<W: Write> {
+    out: W,
+    columns: Vec<Column>,
+    lines: bool,
+    rows: usize,
+}
+
+impl<W: Write> JsonSink<W> {
+    pub fn new(out: W, lines: bool) -> Self {
+        JsonSink { out, columns: Vec::new(), lines, rows: 0 }
+    }
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_value(column: &Column, file: &FileInfo) -> Result<String, String> {
+    let value = match expr::evaluate(&column.expression, file)? {
+        FieldValue::Size(size) => size.to_string(),
+        FieldValue::Number(number) if number.is_finite() => number.to_string(),
+        FieldValue::Number(_) => "null".to_string(),
+        FieldValue::Bool(flag) => flag.to_string(),
+        FieldValue::Duration(duration) => duration.num_seconds().to_string(),
+        FieldValue::Date(date) => json_string(&date.to_rfc3339()),
+        other => json_string(&other.to_text()),
+    };
+    Ok(value)
+}
+
+impl<W: Write> OutputSink for JsonSink<W> {
+    fn begin(&mut self, columns: &[Column]) -> Result<(), Box<dyn Error>> {
+        self.columns = columns.to_vec();
+        if !self.lines {
+            write!(self.out, "[")?;
+        }
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+        let fields = self
+            .columns
+            .iter()
+            .map(|c| Ok(format!("{}: {}", json_string(&c.name), json_value(c, file)?)))
+            .collect::<Result<Vec<String>, String>>()?;
+        let object = format!("{{{}}}", fields.join(", "));
+        match (self.lines, self.rows) {
+            (true, _) => writeln!(self.out, "{}", object)?,
+            (false, 0) => write!(self.out, "\n  {}", object)?,
+            (false, _) => write!(self.out, ",\n  {}", object)?,
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, _summary: &Summary) -> Result<(), Box<dyn Error>> {
+        if !self.lines {
+            writeln!(self.out, "{}]", if self.rows > 0 { "\n" } else { "" })?;
+        }
+        Ok(self.out.flush()?)
+    }
+}
+
+// the entries a DELETE is about to remove, the first `shown` of them
+pub fn doomed(files: &[FileInfo], shown: usize) -> Result<String, String> {
+    let columns: Vec<Column> = ["path", "type", "size"].iter().map(|c| Column::new(&SelectItem::field(c))).collect();
+    let mut out = table(&files[..files.len().min(shown)], &columns)?.to_string();
+    if files.len() > shown {
+        out.push_str(&format!("\n... and {} more", files.len() - shown));
+    }
+    Ok(out)
+}
+
+// DUPLICATES output: one row per file, numbered by the group it belongs to
+pub fn duplicates(groups: &[Vec<FileInfo>]) -> Table {
+    let mut table = Table::new();
+    table.set_header(["Group", "Size", "Path"]);
+    for (index, group) in groups.iter().enumerate() {
+        for file in group {
+            table.add_row([(index + 1).to_string(), file.human_readable_size(), file.path.clone()]);
+        }
+    }
+    table
+}
+
+// bytes per extension, largest first, as reported after a DELETE
+pub fn size_breakdown(totals: &[(String, usize, u64)]) -> Table {
+    let mut table = Table::new();
+    table.set_header(["Extension", "Entries", "Size"]);
+    for (ext, count, bytes) in totals {
+        table.add_row([ext.clone(), count.to_string(), crate::files::human_readable_size(*bytes)]);
+    }
+    table
+}
+
+// one step in turning one name into another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    Keep(char),
+    Remove(char),
+    Insert(char),
+}
+
+// a character-level diff from `old` to `new`, through their longest common subsequence
+pub fn char_diff(old: &str, new: &str) -> Vec<Edit> {
+    let (old, new): (Vec<char>, Vec<char>) = (old.chars().collect(), new.chars().collect());
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Keep(old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            edits.push(Edit::Insert(new[j]));
+            j += 1;
+        } else {
+            edits.push(Edit::Remove(old[i]));
+            i += 1;
+        }
+    }
+    edits
+}
+
+// move a pending run of changed characters onto `out`, colored
+fn push_run(out: &mut String, run: &mut String, paint: fn(&str) -> colored::ColoredString) {
+    if !run.is_empty() {
+        out.push_str(&paint(run).to_string());
+        run.clear();
+    }
+}
+
+// `old → new` with removed characters in red and inserted ones in green
+pub fn rename_diff(old: &str, new: &str) -> String {
+    let removed_style: fn(&str) -> colored::ColoredString = |run| run.red().bold().underline();
+    let inserted_style: fn(&str) -> colored::ColoredString = |run| run.green().bold();
+    let (mut before, mut after) = (String::new(), String::new());
+    let (mut removed, mut inserted) = (String::new(), String::new());
+    for edit in char_diff(old, new) {
+        match edit {
+            Edit::Keep(c) => {
+                push_run(&mut before, &mut removed, removed_style);
+                push_run(&mut after, &mut inserted, inserted_style);
+                before.push(c);
+                after.push(c);
+            }
+            Edit::Remove(c) => removed.push(c),
+            Edit::Insert(c) => inserted.push(c),
+        }
+    }
+    push_run(&mut before, &mut removed, removed_style);
+    push_run(&mut after, &mut inserted, inserted_style);
+    format!("{} → {}", before, after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(names: &[&str]) -> Vec<SelectItem> {
+        names.iter().map(|n| SelectItem::field(n)).collect()
+    }
+
+    fn names(selection: &SelectionType) -> Vec<String> {
+        columns(selection).unwrap().into_iter().map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn test_columns_keep_requested_order() {
+        let selection = SelectionType::Fields(fields(&["size", "owner"]));
+        assert_eq!(names(&selection), ["size", "owner"]);
+    }
+
+    #[test]
+    fn test_columns_expand_wildcard_and_reject_unknown() {
+        assert_eq!(names(&SelectionType::All), DEFAULT_COLUMNS);
+        let selection = SelectionType::AllWith(fields(&["owner"]));
+        assert_eq!(names(&selection).last().map(String::as_str), Some("owner"));
+        assert!(columns(&SelectionType::Fields(fields(&["colour"]))).is_err());
+        let every = names(&SelectionType::Every);
+        assert!(every.iter().any(|name| name == "owner") && !every.iter().any(|name| name == "hash"));
+    }
+
+    #[test]
+    fn test_computed_column_names() {
+        let upper = Expression::Function("upper".to_string(), vec![Expression::Field("name".to_string())]);
+        let selection = SelectionType::Fields(vec![
+            SelectItem { expression: upper.clone(), alias: None },
+            SelectItem { expression: upper, alias: Some("shout".to_string()) },
+        ]);
+        assert_eq!(names(&selection), ["upper(name)", "shout"]);
+        let unknown = Expression::Function("upper".to_string(), vec![Expression::Field("colour".to_string())]);
+        assert!(columns(&SelectionType::Fields(vec![SelectItem { expression: unknown, alias: None }])).is_err());
+    }
+
+    #[test]
+    fn test_columns_reject_duplicates() {
+        assert!(columns(&SelectionType::Fields(fields(&["name", "size", "NAME"]))).is_err());
+        assert!(columns(&SelectionType::AllWith(fields(&["size"]))).is_err());
+    }
+
+    #[test]
+    fn test_char_diff() {
+        use Edit::*;
+        assert_eq!(
+            char_diff("My Song.mp3", "My_Song.mp3")[2..4],
+            [Insert('_'), Remove(' ')]
+        );
+        let edits = char_diff("IMG_001.JPG", "img_001.jpg");
+        let kept: String = edits.iter().filter_map(|e| if let Keep(c) = e { Some(*c) } else { None }).collect();
+        assert_eq!(kept, "_001.");
+        assert_eq!(char_diff("same", "same"), "same".chars().map(Keep).collect::<Vec<_>>());
+    }
+
+    fn file(name: &str) -> FileInfo {
+        FileInfo {
+            size: 10,
+            modified: "2024-05-01T12:00:00Z".parse().unwrap(),
+            accessed: "2024-05-01T12:00:00Z".parse().unwrap(),
+            name: name.to_string(),
+            file_type: crate::files::FileType::File,
+            path: format!("./{}", name),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mode: 0o644,
+            root: ".".to_string(),
+            compressed_size: None,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_doomed_is_capped() {
+        let files: Vec<FileInfo> = (0..5).map(|i| file(&format!("{}.o", i))).collect();
+        let listing = doomed(&files, 3).unwrap();
+        assert!(listing.contains("./2.o") && !listing.contains("./3.o"));
+        assert!(listing.ends_with("... and 2 more"));
+    }
+
+    // what a sink wrote for two files
+    fn written(make: fn(&mut Vec<u8>) -> Box<dyn OutputSink + '_>) -> String {
+        let columns = columns(&SelectionType::Fields(fields(&["name", "size", "modified"]))).unwrap();
+        let mut out = Vec::new();
+        let mut sink = make(&mut out);
+        sink.begin(&columns).unwrap();
+        sink.entry(&file("a.rs")).unwrap();
+        sink.entry(&file("say \"hi\", ok.txt")).unwrap();
+        sink.finish(&Summary { rows: 2, elapsed: Duration::ZERO }).unwrap();
+        drop(sink);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_sinks() {
+        assert_eq!(
+            written(|out| Box::new(CsvSink::new(out))),
+            "name,size,modified\na.rs,10,2024-05-01 12:00:00\n\"say \"\"hi\"\", ok.txt\",10,2024-05-01 12:00:00\n"
+        );
+        let ndjson = written(|out| Box::new(JsonSink::new(out, true)));
+        assert_eq!(
+            ndjson.lines().next(),
+            Some(r#"{"name": "a.rs", "size": 10, "modified": "2024-05-01T12:00:00+00:00"}"#)
+        );
+        assert!(ndjson.contains(r#""name": "say \"hi\", ok.txt""#));
+        let json = written(|out| Box::new(JsonSink::new(out, false)));
+        assert!(json.starts_with("[\n  {") && json.ends_with("}\n]\n") && json.matches("},\n").count() == 1);
+        assert!(written(|out| Box::new(TableSink::new(out))).contains("| a.rs "));
+        assert_eq!(
+            written(|out| Box::new(LinesSink::new(out))),
+            "a.rs\t10 B\t2024-05-01 12:00:00\nsay \"hi\", ok.txt\t10 B\t2024-05-01 12:00:00\n"
+        );
+        assert_eq!(
+            written(|out| Box::new(LongSink::new(out))),
+            "a.rs              10 B  2024-05-01 12:00:00\nsay \"hi\", ok.txt  10 B  2024-05-01 12:00:00\n"
+        );
+        assert_eq!(
+            written(|out| Box::new(MarkdownSink::new(out))).lines().take(3).collect::<Vec<_>>(),
+            ["| Name | Size | Modified |", "| --- | ---: | --- |", "| a.rs | 10 B | 2024-05-01 12:00:00 |"]
+        );
+        assert_eq!(markdown_cell("a|b\\c"), "a\\|b\\\\c");
+    }
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("/home/me/src/project/file.rs", 40, true), "/home/me/src/project/file.rs");
+        assert_eq!(shorten("/home/me/src/project/file.rs", 24, true), "/home/…/project/file.rs");
+        assert_eq!(shorten("/home/me/src/project/file.rs", 16, true), "/home/…/file.rs");
+        assert_eq!(shorten("src/a/b/main.rs", 12, true), "…a/b/main.rs");
+        assert_eq!(shorten(r"C:\Users\me\Documents\notes.txt", 20, true), r"C:\…\notes.txt");
+        assert_eq!(shorten("/a_very_long_directory_name_here", 10, true), "…name_here");
+        assert_eq!(shorten("a rather long description", 10, false), "a rather …");
+    }
+
+    #[test]
+    fn test_table_alignment() {
+        let columns = columns(&SelectionType::Fields(fields(&["name", "bytes"]))).unwrap();
+        let table = table(&[file("a.rs")], &columns).unwrap().to_string();
+        assert!(table.contains("| a.rs |    10 |"), "{}", table);
+    }
+
+    #[test]
+    fn test_grid() {
+        let names: Vec<String> = ["a.rs", "bb.rs", "c.rs", "main.rs", "e"].iter().map(|n| n.to_string()).collect();
+        assert_eq!(grid(&names, 80), "a.rs  bb.rs  c.rs  main.rs  e\n");
+        // down then across, each column as wide as its longest name
+        assert_eq!(grid(&names, 16), "a.rs   main.rs\nbb.rs  e\nc.rs\n");
+        assert_eq!(grid(&names, 3).lines().count(), 5);
+        assert_eq!(grid(&[], 80), "");
+    }
+
+    #[test]
+    fn test_icons() {
+        assert_eq!(icon(&file("main.RS")), "📜");
+        assert_eq!(icon(&file("backup.tar.gz")), "📦");
+        assert_eq!(icon(&file("Makefile")), "📄");
+        assert_eq!(icon(&FileInfo { file_type: crate::files::FileType::Directory, ..file("photos.png") }), "📁");
+    }
+
+    #[test]
+    fn test_totals() {
+        let mut totals = Totals::default();
+        assert_eq!(totals.line(), "0 files, 0 dirs, 0 B");
+        totals.add(&file("a.rs"));
+        totals.add(&FileInfo { size: 2038, modified: "2024-01-02T10:00:00Z".parse().unwrap(), ..file("b.rs") });
+        totals.add(&FileInfo { file_type: crate::files::FileType::Directory, ..file("src") });
+        assert_eq!(totals.line(), "2 files, 1 dir, 2.00 KB, modified 2024-01-02 10:00:00 to 2024-05-01 12:00:00");
+    }
+
+    #[test]
+    fn test_header() {
+        assert_eq!(header("dir_size"), "Dir Size");
+    }
+}
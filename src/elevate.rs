@@ -0,0 +1,95 @@
+// Permission failures in DELETE and UPDATE, gathered instead of reported one
+// by one. A statement that hits a root-owned tree would otherwise print the
+// same error for every entry; this collects them, names a few and either
+// suggests `--sudo` or, with it, retries just those entries through sudo.
+
+use std::io;
+use std::process::Command;
+
+// refused entries listed by name before the rest are summed up
+const SHOWN: usize = 3;
+
+pub fn is_denied(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::PermissionDenied
+}
+
+// entries the OS refused, each with the shell command that would redo the
+// refused change and whatever the caller needs once it has gone through
+pub struct Refused<T> {
+    entries: Vec<(String, String, T)>,
+}
+
+impl<T> Default for Refused<T> {
+    fn default() -> Self {
+        Refused { entries: Vec::new() }
+    }
+}
+
+impl<T> Refused<T> {
+    pub fn push(&mut self, path: &str, command: String, payload: T) {
+        self.entries.push((path.to_string(), command, payload));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // report the refusals once; with `sudo`, rerun each refused command under
+    // sudo (which asks for a password at most once) and hand back the payloads
+    // of those that succeeded
+    pub fn settle(self, sudo: bool) -> Vec<T> {
+        if self.entries.is_empty() {
+            return Vec::new();
+        }
+        if !sudo {
+            eprintln!("{}", self.summary());
+            eprintln!("hint: rerun with --sudo to retry just these entries with elevated privileges");
+            return Vec::new();
+        }
+        eprintln!("permission denied for {} entries; retrying them with sudo", self.entries.len());
+        let mut done = Vec::new();
+        for (path, command, payload) in self.entries {
+            match Command::new("sudo").args(["--", "sh", "-c", &command]).status() {
+                Ok(status) if status.success() => done.push(payload),
+                Ok(status) => eprintln!("skipped {}: sudo exited with {}", path, status),
+                Err(e) => {
+                    eprintln!("could not run sudo: {}", e);
+                    break;
+                }
+            }
+        }
+        done
+    }
+
+    fn summary(&self) -> String {
+        let mut out = format!("permission denied for {} entries:", self.entries.len());
+        for (path, _, _) in self.entries.iter().take(SHOWN) {
+            out.push_str(&format!("\n  {}", path));
+        }
+        if self.entries.len() > SHOWN {
+            out.push_str(&format!("\n  ... and {} more", self.entries.len() - SHOWN));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_collapses() {
+        let mut refused = Refused::default();
+        for name in ["a", "b", "c", "d", "e"] {
+            refused.push(name, format!("rm -f -- '{}'", name), ());
+        }
+        assert_eq!(refused.len(), 5);
+        assert_eq!(refused.summary(), "permission denied for 5 entries:\n  a\n  b\n  c\n  ... and 2 more");
+        assert!(is_denied(&io::Error::from(io::ErrorKind::PermissionDenied)));
+        assert!(!is_denied(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+}
@@ -0,0 +1,171 @@
+// The error type statements, the parser and the command line report, so
+// callers can tell a typo in a query from a file they may not read and exit
+// with a code that says which it was.
+
+use std::error::Error;
+use std::io;
+
+use thiserror::Error;
+
+use crate::display::json_string;
+
+#[derive(Debug, Error)]
+pub enum LsqlError {
+    // input the grammar could not read, `position` bytes into it
+    #[error("{message}")]
+    Parse { message: String, position: usize },
+    // a bad command line flag or flag value
+    #[error("{0}")]
+    Usage(String),
+    // a field no entry has, or one this build lacks the feature for
+    #[error("{0}")]
+    InvalidField(String),
+    // a literal that does not fit its field, such as `size > lots`
+    #[error("{0}")]
+    InvalidValue(String),
+    #[error("{0}")]
+    Permission(#[source] io::Error),
+    #[error("{0}")]
+    Io(#[source] io::Error),
+    // one of several statements on a line failed
+    #[error("statement {index} failed: {source}")]
+    Statement { index: usize, source: Box<LsqlError> },
+    // stopped part way by Ctrl+C or --timeout
+    #[error("{0}")]
+    Interrupted(String),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl LsqlError {
+    // the process exit code for this error; 0 is success
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LsqlError::Failed(_) => 1,
+            LsqlError::Usage(_) | LsqlError::Parse { .. } => 2,
+            LsqlError::InvalidField(_) | LsqlError::InvalidValue(_) => 3,
+            LsqlError::Io(_) => 4,
+            LsqlError::Permission(_) => 5,
+            // as a shell reports a command ended by SIGINT
+            LsqlError::Interrupted(_) => 130,
+            LsqlError::Statement { source, .. } => source.exit_code(),
+        }
+    }
+
+    // a stable name for the kind of error, for output other programs read
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LsqlError::Parse { .. } => "parse",
+            LsqlError::Usage(_) => "usage",
+            LsqlError::InvalidField(_) => "invalid_field",
+            LsqlError::InvalidValue(_) => "invalid_value",
+            LsqlError::Permission(_) => "permission",
+            LsqlError::Io(_) => "io",
+            LsqlError::Statement { source, .. } => source.kind(),
+            LsqlError::Interrupted(_) => "interrupted",
+            LsqlError::Failed(_) => "failed",
+        }
+    }
+
+    // the error as one line of JSON for --format json, so wrappers need not
+    // scrape the text: its kind, exit code, message and, when known, the
+    // statement that failed and the byte offset the parser stopped at
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            format!("\"kind\": {}", json_string(self.kind())),
+            format!("\"exit_code\": {}", self.exit_code()),
+        ];
+        let mut error = self;
+        if let LsqlError::Statement { index, source } = self {
+            fields.push(format!("\"statement\": {}", index));
+            error = source;
+        }
+        // the first line; a parse error goes on to draw where it stopped
+        let message = error.to_string();
+        fields.push(format!("\"message\": {}", json_string(message.lines().next().unwrap_or_default())));
+        if let LsqlError::Parse { position, .. } = error {
+            fields.push(format!("\"position\": {}", position));
+            if let Some(hint) = message.lines().find_map(|line| line.strip_prefix("hint: ")) {
+                fields.push(format!("\"hint\": {}", json_string(hint)));
+            }
+        }
+        format!("{{\"error\": {{{}}}}}", fields.join(", "))
+    }
+}
+
+impl From<io::Error> for LsqlError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => LsqlError::Permission(error),
+            _ => LsqlError::Io(error),
+        }
+    }
+}
+
+impl From<String> for LsqlError {
+    fn from(message: String) -> Self {
+        LsqlError::Failed(message)
+    }
+}
+
+impl From<&str> for LsqlError {
+    fn from(message: &str) -> Self {
+        LsqlError::Failed(message.to_string())
+    }
+}
+
+// statements still return boxed errors; recover the kind where there is one
+impl From<Box<dyn Error>> for LsqlError {
+    fn from(error: Box<dyn Error>) -> Self {
+        let error = match error.downcast::<LsqlError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<io::Error>() {
+            Ok(error) => return (*error).into(),
+            Err(error) => error,
+        };
+        match error.downcast::<walkdir::Error>() {
+            Ok(error) => {
+                let message = error.to_string();
+                match error.into_io_error() {
+                    Some(io) => io::Error::new(io.kind(), message).into(),
+                    None => LsqlError::Failed(message),
+                }
+            }
+            Err(error) => LsqlError::Failed(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds_and_exit_codes() {
+        let denied: Box<dyn Error> = Box::new(io::Error::from(io::ErrorKind::PermissionDenied));
+        let denied = LsqlError::from(denied);
+        assert_eq!((denied.kind(), denied.exit_code()), ("permission", 5));
+        let wrapped = LsqlError::Statement { index: 2, source: Box::new(LsqlError::InvalidField("Unknown field 'x'".into())) };
+        assert_eq!(wrapped.to_string(), "statement 2 failed: Unknown field 'x'");
+        assert_eq!(wrapped.exit_code(), 3);
+        let boxed: Box<dyn Error> = LsqlError::Usage("bad flag".into()).into();
+        assert_eq!(LsqlError::from(boxed).kind(), "usage");
+        assert_eq!(LsqlError::from("cancelled").exit_code(), 1);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let wrapped = LsqlError::Statement { index: 2, source: Box::new(LsqlError::InvalidField("Unknown field 'x'".into())) };
+        assert_eq!(
+            wrapped.to_json(),
+            r#"{"error": {"kind": "invalid_field", "exit_code": 3, "statement": 2, "message": "Unknown field 'x'"}}"#
+        );
+        let parse = LsqlError::Parse { message: "could not parse \"x\"\n  x\n  ^\nhint: quote it".into(), position: 7 };
+        assert_eq!(
+            parse.to_json(),
+            r#"{"error": {"kind": "parse", "exit_code": 2, "message": "could not parse \"x\"", "position": 7, "hint": "quote it"}}"#
+        );
+    }
+}
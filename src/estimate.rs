@@ -0,0 +1,200 @@
+// A quick look at how big a tree is before walking all of it.
+//
+// Recursive work (directory sizes, DEPTH) reads every entry below the root,
+// which can take minutes on a home directory or a mounted share. A shallow
+// probe counts the first few levels and extrapolates one more, which is
+// enough to tell a few thousand entries from a few million and warn first.
+
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::cli::Options;
+use crate::display;
+use crate::files::FIELDS;
+use crate::fs::Detail;
+use crate::parser::{Command, ConditionNode, Expression, SelectionType, WhereClause};
+
+// levels read by the probe
+const PROBE_DEPTH: usize = 3;
+
+// estimates above this ask before continuing
+pub const WARN_ABOVE: u64 = 250_000;
+
+// roughly how many entries live below `root`
+pub fn estimate(root: &Path) -> u64 {
+    let mut per_level = [0u64; PROBE_DEPTH];
+    for entry in WalkDir::new(root).min_depth(1).max_depth(PROBE_DEPTH).into_iter().flatten() {
+        per_level[entry.depth() - 1] += 1;
+    }
+    let observed: u64 = per_level.iter().sum();
+    let (previous, last) = (per_level[PROBE_DEPTH - 2], per_level[PROBE_DEPTH - 1]);
+    if previous == 0 || last == 0 {
+        return observed;
+    }
+    // assume the next level grows the way the last one did
+    observed + last * last / previous
+}
+
+// 2.3M, 12.4K or 512
+pub fn approximate(count: u64) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=999_999 => format!("{:.1}K", count as f64 / 1e3),
+        _ => format!("{:.1}M", count as f64 / 1e6),
+    }
+}
+
+fn condition_mentions(condition: &ConditionNode, field: &str) -> bool {
+    match condition {
+        ConditionNode::Condition(clause) => {
+            let (WhereClause::Equal(name, _)
+            | WhereClause::NotEqual(name, _)
+            | WhereClause::LessThan(name, _)
+            | WhereClause::LessThanOrEqual(name, _)
+            | WhereClause::GreaterThan(name, _)
+            | WhereClause::GreaterThanOrEqual(name, _)
+            | WhereClause::Contains(name, _)
+            | WhereClause::Like(name, _)
+            | WhereClause::ILike(name, _)
+            | WhereClause::UnknownOperator(name, _)) = clause;
+            name.eq_ignore_ascii_case(field)
+        }
+        ConditionNode::And(left, right) | ConditionNode::Or(left, right) => {
+            condition_mentions(left, field) || condition_mentions(right, field)
+        }
+        ConditionNode::Not(inner) => condition_mentions(inner, field),
+    }
+}
+
+fn expression_mentions(expression: &Expression, field: &str) -> bool {
+    match expression {
+        Expression::Literal(_) => false,
+        Expression::Field(name) => name.eq_ignore_ascii_case(field),
+        Expression::Function(_, args) => args.iter().any(|arg| expression_mentions(arg, field)),
+        Expression::Arithmetic(left, _, right) => {
+            expression_mentions(left, field) || expression_mentions(right, field)
+        }
+    }
+}
+
+// whether a statement's columns, WHERE or ORDER BY use `field`
+pub fn mentions(command: &Command, field: &str) -> bool {
+    let where_clause = match command {
+        Command::Select { where_clause, .. }
+        | Command::Duplicates { where_clause, .. }
+        | Command::DeleteFiles { where_clause, .. }
+        | Command::Exists { where_clause, .. } => where_clause,
+        _ => return false,
+    };
+    if where_clause.as_ref().is_some_and(|c| condition_mentions(c, field)) {
+        return true;
+    }
+    let Command::Select { selection, order_by, .. } = command else {
+        return false;
+    };
+    let items = match selection {
+        SelectionType::Fields(items) | SelectionType::AllWith(items) => items.as_slice(),
+        _ => &[],
+    };
+    items.iter().any(|item| expression_mentions(&item.expression, field))
+        || order_by.iter().flatten().any(|name| name.eq_ignore_ascii_case(field))
+}
+
+// fields that need nothing beyond an entry's name, type and path. mime, hash
+// and md5 are missing on purpose: the cache keys them by size and mtime
+const BY_NAME: &[&str] = &["name", "path", "ext", "type", "root", "depth"];
+
+// how much of each entry `command` reads: its columns (the default ones for
+// `*`), WHERE and ORDER BY. Only SELECT reads less than everything
+pub fn detail(command: &Command, options: &Options) -> Detail {
+    let Command::Select { selection, .. } = command else {
+        return Detail::Owner;
+    };
+    let columns = display::columns(selection).unwrap_or_default();
+    let keys = crate::pipeline::sort_keys(command, options).unwrap_or_default();
+    let sorts_on = |field: &str| keys.iter().any(|key| key.field == field);
+    let uses = |field: &str| {
+        mentions(command, field) || sorts_on(field) || columns.iter().any(|c| expression_mentions(&c.expression, field))
+    };
+    // --summary adds up sizes and dates whatever is selected
+    let mut detail = if options.compute_dir_sizes || options.summary { Detail::Metadata } else { Detail::Name };
+    for field in FIELDS.iter().filter(|field| uses(field)) {
+        let needs = match *field {
+            "owner" | "group" => Detail::Owner,
+            // Windows keeps the hidden flag in the attributes
+            "is_hidden" if !cfg!(windows) => Detail::Name,
+            field if BY_NAME.contains(&field) => Detail::Name,
+            _ => Detail::Metadata,
+        };
+        detail = detail.max(needs);
+    }
+    detail
+}
+
+// whether running `command` reads below the directories it lists
+pub fn is_recursive(command: &Command, options: &Options) -> bool {
+    let Command::Select { depth, .. } = command else {
+        return false;
+    };
+    let sorts_on_dir_size = crate::pipeline::sort_keys(command, options).iter().flatten().any(|key| key.field == "dir_size");
+    options.compute_dir_sizes || depth.or(options.max_depth).unwrap_or(1) > 1 || mentions(command, "dir_size") || sorts_on_dir_size
+}
+
+// probe `roots` and, if they look large, ask before going on; without a
+// terminal to ask on, warn and carry on so scripts are not blocked
+pub fn confirm(roots: &[PathBuf]) -> bool {
+    let count: u64 = roots.iter().map(|root| estimate(root)).sum();
+    if count <= WARN_ABOVE {
+        return true;
+    }
+    let under = match roots {
+        [root] => root.display().to_string(),
+        _ => format!("{} directories", roots.len()),
+    };
+    let message = format!("~{} entries under {}", approximate(count), under);
+    crate::shell::ask(&format!("{}; continue?", message)).unwrap_or_else(|| {
+        eprintln!("warning: {}; this may take a while (--no-estimate skips this check)", message);
+        true
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approximate() {
+        assert_eq!(approximate(512), "512");
+        assert_eq!(approximate(12_400), "12.4K");
+        assert_eq!(approximate(2_300_000), "2.3M");
+    }
+
+    #[test]
+    fn test_is_recursive() {
+        let command = |query: &str| crate::parser::parse(query).unwrap().1.remove(0);
+        let options = Options::default();
+        assert!(!is_recursive(&command("select * from . where size > 1mb"), &options));
+        assert!(is_recursive(&command("select name, dir_size from ."), &options));
+        assert!(is_recursive(&command("select * where not dir_size < 1gb"), &options));
+        assert!(is_recursive(&command("select * order by dir_size"), &options));
+        assert!(is_recursive(&command("select * from . depth 4"), &options));
+        let options = Options { compute_dir_sizes: true, ..Default::default() };
+        assert!(is_recursive(&command("select * from ."), &options));
+    }
+
+    #[test]
+    fn test_detail() {
+        let command = |query: &str| crate::parser::parse(query).unwrap().1.remove(0);
+        let options = Options::default();
+        assert_eq!(detail(&command("select name from ."), &options), Detail::Name);
+        assert_eq!(detail(&command("select path from . where ext = 'rs' order by name"), &options), Detail::Name);
+        assert_eq!(detail(&command("select name from . where size > 1mb"), &options), Detail::Metadata);
+        assert_eq!(detail(&command("select name from . order by modified"), &options), Detail::Metadata);
+        assert_eq!(detail(&command("select count(*) from . where owner = 'root'"), &options), Detail::Owner);
+        assert_eq!(detail(&command("select * from ."), &options), Detail::Metadata);
+        assert_eq!(detail(&command("delete from . where name = 'x'"), &options), Detail::Owner);
+        let options = Options { compute_dir_sizes: true, ..Default::default() };
+        assert_eq!(detail(&command("select name from ."), &options), Detail::Metadata);
+    }
+}
@@ -0,0 +1,223 @@
+// EXPLAIN: what a statement would read, test and keep, worked out without
+// walking anything beyond a shallow probe of the directories it lists.
+
+use std::error::Error;
+
+use crate::estimate;
+use crate::fs::{self, Detail, Root, Walk};
+use crate::parser::{Command, ConditionNode, SelectionType};
+use crate::State;
+
+// the plan for `command`, one `label: detail` line per step
+pub fn plan(state: &State, command: &Command) -> Result<String, Box<dyn Error>> {
+    let (statement, from_paths, depth, exclude, where_clause) = match command {
+        Command::Select { from_paths, depth, exclude, where_clause, .. } => {
+            ("select", from_paths, depth, exclude, where_clause)
+        }
+        Command::Duplicates { from_paths, depth, exclude, where_clause } => {
+            ("duplicates", from_paths, depth, exclude, where_clause)
+        }
+        Command::DeleteFiles { from_paths, depth, exclude, where_clause, .. } => {
+            ("delete", from_paths, depth, exclude, where_clause)
+        }
+        _ => return Err("EXPLAIN describes SELECT, DUPLICATES and DELETE statements".into()),
+    };
+    let mut lines = vec![
+        ("statement", statement.to_string()),
+        ("canonical", crate::normalize::canonical(command)),
+        ("fingerprint", crate::normalize::fingerprint(command)),
+    ];
+    if let Command::Select { selection, .. } = command {
+        lines.push(("columns", columns(selection)?));
+    }
+
+    let roots = from_paths.as_deref().map(|paths| fs::resolve_all_roots(state, paths)).transpose()?;
+    if fs::reads_listing(&state.options, roots.as_deref(), *depth, exclude) {
+        lines.push(("from", format!("the session's listing of {}", state.path.display())));
+        lines.push(("scan", format!("{} entries already in memory", state.listing.len())));
+    } else {
+        let walk = Walk::new(&state.options, *depth, exclude)?;
+        let roots = roots.unwrap_or_else(|| vec![Root::Directory(state.path.clone())]);
+        lines.push(("from", roots.iter().map(describe_root).collect::<Vec<_>>().join(", ")));
+        lines.push(("walk", describe_walk(&walk, &state.options.exclude, exclude)));
+        let stat = match estimate::detail(command, &state.options) {
+            Detail::Name => "none, names and types come from the directory listing",
+            Detail::Metadata => "once per entry",
+            Detail::Owner => "once per entry, with owner and group names looked up",
+        };
+        lines.push(("stat", stat.to_string()));
+        if let (Command::Select { .. }, false) = (command, state.options.no_index) {
+            for root in roots.iter().filter(|root| matches!(root, Root::Directory(_))) {
+                if let Some(note) = index_note(root.path()) {
+                    lines.push(("index", note));
+                }
+            }
+        }
+        lines.push(("scan", scan_estimate(&roots, walk.depth())));
+    }
+
+    lines.push(("where", where_clause.as_ref().map_or("none, every entry is kept".to_string(), ConditionNode::to_string)));
+    let mut reads: Vec<String> = crate::files::EXPENSIVE_FIELDS
+        .iter()
+        .filter(|(field, _)| estimate::mentions(command, field))
+        .map(|(field, cost)| format!("{} ({})", cost, field))
+        .collect();
+    if state.options.compute_dir_sizes {
+        reads.push("walks the subtree of every directory (--compute-dir-sizes)".to_string());
+    }
+    for provider in crate::plugins::providers() {
+        if estimate::mentions(command, provider.name()) {
+            reads.push(format!("runs a plugin command per entry ({})", provider.name()));
+        }
+    }
+    if !reads.is_empty() {
+        lines.push(("reads", reads.join("; ")));
+    }
+
+    match command {
+        Command::Select { order_by, distinct, limit, offset, .. } => {
+            if let Some(keys) = crate::pipeline::sort_keys(command, &state.options) {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|key| {
+                        let natural = if key.natural { " natural" } else { "" };
+                        format!("{}{} {}", key.field, natural, if key.descending { "desc" } else { "asc" })
+                    })
+                    .collect();
+                let sort = match state.options.max_memory {
+                    Some(budget) => format!("spilling to disk past {}", crate::files::human_readable_size(budget)),
+                    None => "in memory".to_string(),
+                };
+                let from = if order_by.is_none() { " (--sort)" } else { "" };
+                lines.push(("order by", format!("{}{}, {}", keys.join(", "), from, sort)));
+            }
+            if *distinct {
+                lines.push(("distinct", "drops rows repeating an earlier row's columns".to_string()));
+            }
+            match (limit, offset) {
+                (Some(limit), Some(offset)) => lines.push(("limit", format!("{} rows after skipping {}", limit, offset))),
+                (Some(limit), None) => lines.push(("limit", format!("{} rows", limit))),
+                (None, Some(offset)) => lines.push(("offset", format!("skips {} rows", offset))),
+                (None, None) => {}
+            }
+        }
+        Command::Duplicates { .. } => {
+            lines.push(("then", "hashes the files that share a size with another".to_string()));
+        }
+        Command::DeleteFiles { permanent, .. } => {
+            let then = if state.options.trash && !permanent {
+                "moves matching entries to the trash"
+            } else {
+                "removes matching entries, asking before non-empty directories"
+            };
+            lines.push(("then", then.to_string()));
+        }
+        _ => {}
+    }
+
+    let width = lines.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    Ok(lines.iter().map(|(label, detail)| format!("{:>width$}: {}\n", label, detail, width = width)).collect())
+}
+
+fn columns(selection: &SelectionType) -> Result<String, Box<dyn Error>> {
+    if let SelectionType::Count = selection {
+        return Ok("count(*)".to_string());
+    }
+    let columns = crate::display::columns(selection)?;
+    Ok(columns.iter().map(|column| column.name.as_str()).collect::<Vec<_>>().join(", "))
+}
+
+fn describe_root(root: &Root) -> String {
+    match root {
+        Root::Directory(dir) => dir.display().to_string(),
+        Root::Archive(file) => format!("archive {}", file.display()),
+        Root::Listed(_, paths) => format!("{} paths from stdin", paths.len()),
+    }
+}
+
+fn describe_walk(walk: &Walk, options_exclude: &[String], exclude: &[String]) -> String {
+    let levels = match walk.depth() {
+        1 => "1 level".to_string(),
+        crate::parser::UNLIMITED => "every level".to_string(),
+        depth => format!("{} levels", depth),
+    };
+    let patterns: Vec<&String> = options_exclude.iter().chain(exclude).collect();
+    if patterns.is_empty() {
+        return levels;
+    }
+    let patterns = patterns.iter().map(|pattern| pattern.as_str()).collect::<Vec<_>>().join(", ");
+    format!("{}, skipping {} and everything below them", levels, patterns)
+}
+
+// whether a directory is read from its index, or walked because the tree
+// changed after the index was built
+#[cfg(feature = "index")]
+fn index_note(dir: &std::path::Path) -> Option<String> {
+    let (_, indexed, built) = crate::index::covering(dir)?;
+    let built_ago = crate::files::relative_time(built);
+    Some(if crate::index::changed_since(dir, &indexed, built) {
+        format!("{} changed after its index was built {}, so it is walked instead", dir.display(), built_ago)
+    } else {
+        format!("{} from its index, built {}", dir.display(), built_ago)
+    })
+}
+
+#[cfg(not(feature = "index"))]
+fn index_note(_dir: &std::path::Path) -> Option<String> {
+    None
+}
+
+// how many entries the walk will look at: exact for a single level, a probe
+// of the first few levels otherwise
+fn scan_estimate(roots: &[Root], depth: usize) -> String {
+    let mut count = 0;
+    let mut archives = 0;
+    for root in roots {
+        match root {
+            Root::Directory(dir) if depth == 1 => {
+                count += std::fs::read_dir(dir).map_or(0, |entries| entries.count() as u64);
+            }
+            Root::Directory(dir) => count += estimate::estimate(dir),
+            Root::Archive(_) => archives += 1,
+            Root::Listed(_, paths) => count += paths.len() as u64,
+        }
+    }
+    let mut out = if depth == 1 {
+        format!("{} entries", count)
+    } else {
+        format!("~{} entries before EXCLUDE", estimate::approximate(count))
+    };
+    if archives > 0 {
+        out.push_str(&format!(", plus the members of {} archive(s), read whole", archives));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Options;
+
+    #[test]
+    fn test_plan() {
+        let dir = crate::testing::TempDir::new("explain");
+        std::fs::create_dir_all(dir.join("node_modules")).unwrap();
+        std::fs::write(dir.join("a.rs"), "").unwrap();
+        let state = State { listing: Vec::new(), path: dir.to_path_buf(), previous: Vec::new(), options: Options::default() };
+        let command = |query: &str| crate::parser::parse(query).unwrap().1.remove(0);
+
+        let out = plan(&state, &command("select name from . exclude node_modules where ext = 'rs' limit 3")).unwrap();
+        assert!(out.contains("columns: name"), "{}", out);
+        assert!(out.contains("1 level, skipping node_modules"), "{}", out);
+        assert!(out.contains("scan: 2 entries"), "{}", out);
+        assert!(out.contains("where: ext = \"rs\""), "{}", out);
+        assert!(out.contains("limit: 3 rows"), "{}", out);
+
+        let out = plan(&state, &command("select name, dir_size order by size desc")).unwrap();
+        assert!(out.contains("0 entries already in memory"), "{}", out);
+        assert!(out.contains("(dir_size)"), "{}", out);
+        assert!(out.contains("order by: size desc, in memory"), "{}", out);
+
+        assert!(plan(&state, &command("pwd")).is_err());
+    }
+}
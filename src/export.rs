@@ -0,0 +1,199 @@
+// writers for file-based output formats
+
+#[cfg(feature = "parquet")]
+pub fn write_parquet(
+    files: &[crate::files::FileInfo],
+    columns: &[crate::display::Column],
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::Arc;
+
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    use crate::expr;
+    use crate::files::{FieldKind, FieldValue};
+
+    let mut kinds = Vec::new();
+    let mut schema = String::from("message lsql {\n");
+    for column in columns {
+        let kind = expr::kind(&column.expression)
+            .ok_or_else(|| format!("Unknown field '{}'", column.name))?;
+        // computed columns are named after their expression, which is not a valid identifier
+        let name: String = column
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+            .collect();
+        let declaration = match kind {
+            FieldKind::Text => format!("binary {} (UTF8)", name),
+            FieldKind::Size => format!("int64 {} (INTEGER(64,false))", name),
+            // whole seconds
+            FieldKind::Duration => format!("int64 {} (INTEGER(64,true))", name),
+            FieldKind::Date => format!("int64 {} (TIMESTAMP(MILLIS,true))", name),
+            FieldKind::Bool => format!("boolean {}", name),
+            FieldKind::Mode => format!("int32 {} (INTEGER(32,false))", name),
+            FieldKind::Number => format!("double {}", name),
+        };
+        schema.push_str(&format!("  required {};\n", declaration));
+        kinds.push(kind);
+    }
+    schema.push('}');
+
+    let schema = Arc::new(parse_message_type(&schema)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(std::fs::File::create(path)?, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+    for (column, kind) in columns.iter().zip(kinds) {
+        let values = files
+            .iter()
+            .map(|file| expr::evaluate(&column.expression, file))
+            .collect::<Result<Vec<FieldValue>, String>>()?;
+        let mut column_writer = row_group
+            .next_column()?
+            .ok_or("parquet schema has fewer columns than selected")?;
+        match kind {
+            FieldKind::Text => {
+                let batch: Vec<ByteArray> = values
+                    .iter()
+                    .map(|value| match value {
+                        FieldValue::Text(text) => ByteArray::from(text.as_str()),
+                        _ => ByteArray::from(""),
+                    })
+                    .collect();
+                column_writer.typed::<ByteArrayType>().write_batch(&batch, None, None)?;
+            }
+            FieldKind::Size | FieldKind::Date | FieldKind::Duration => {
+                let batch: Vec<i64> = values
+                    .iter()
+                    .map(|value| match value {
+                        FieldValue::Size(size) => *size as i64,
+                        FieldValue::Date(date) => date.timestamp_millis(),
+                        FieldValue::Duration(duration) => duration.num_seconds(),
+                        _ => 0,
+                    })
+                    .collect();
+                column_writer.typed::<Int64Type>().write_batch(&batch, None, None)?;
+            }
+            FieldKind::Bool => {
+                let batch: Vec<bool> = values
+                    .iter()
+                    .map(|value| matches!(value, FieldValue::Bool(true)))
+                    .collect();
+                column_writer.typed::<BoolType>().write_batch(&batch, None, None)?;
+            }
+            FieldKind::Mode => {
+                let batch: Vec<i32> = values
+                    .iter()
+                    .map(|value| match value {
+                        FieldValue::Mode(mode) => *mode as i32,
+                        _ => 0,
+                    })
+                    .collect();
+                column_writer.typed::<Int32Type>().write_batch(&batch, None, None)?;
+            }
+            FieldKind::Number => {
+                let batch: Vec<f64> = values
+                    .iter()
+                    .map(|value| match value {
+                        FieldValue::Number(number) => *number,
+                        _ => 0.0,
+                    })
+                    .collect();
+                column_writer.typed::<DoubleType>().write_batch(&batch, None, None)?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn write_parquet(
+    _files: &[crate::files::FileInfo],
+    _columns: &[crate::display::Column],
+    _path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    crate::features::require("parquet").map_err(Into::into)
+}
+
+// gathers the rows, since a parquet file is written column by column
+pub struct ParquetSink {
+    path: std::path::PathBuf,
+    columns: Vec<crate::display::Column>,
+    files: Vec<crate::files::FileInfo>,
+}
+
+impl ParquetSink {
+    pub fn new(path: std::path::PathBuf) -> Self {
+        ParquetSink { path, columns: Vec::new(), files: Vec::new() }
+    }
+}
+
+impl crate::display::OutputSink for ParquetSink {
+    fn begin(&mut self, columns: &[crate::display::Column]) -> Result<(), Box<dyn std::error::Error>> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn entry(&mut self, file: &crate::files::FileInfo) -> Result<(), Box<dyn std::error::Error>> {
+        self.files.push(file.clone());
+        Ok(())
+    }
+
+    fn finish(&mut self, summary: &crate::display::Summary) -> Result<(), Box<dyn std::error::Error>> {
+        write_parquet(&self.files, &self.columns, &self.path)?;
+        println!("wrote {} rows to {}", summary.rows, self.path.display());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "parquet"))]
+mod tests {
+    use super::*;
+    use crate::files::{FileInfo, FileType};
+
+    #[test]
+    fn test_write_parquet_round_trip() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let files = vec![FileInfo {
+            size: 42,
+            modified: chrono::Utc::now(),
+            accessed: chrono::Utc::now(),
+            name: "a.txt".to_string(),
+            file_type: FileType::File,
+            path: "./a.txt".to_string(),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mode: 0o644,
+            root: ".".to_string(),
+            compressed_size: None,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        }];
+        let selection = crate::parser::parse("select name, bytes, modified, is_hidden, mode, size / 2 as half")
+            .map(|(_, mut commands)| match commands.remove(0) {
+                crate::parser::Command::Select { selection, .. } => selection,
+                other => panic!("unexpected command {:?}", other),
+            })
+            .unwrap();
+        let columns = crate::display::columns(&selection).unwrap();
+        let dir = crate::testing::TempDir::new("parquet");
+        let path = dir.join("test.parquet");
+        write_parquet(&files, &columns, &path).unwrap();
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+        assert_eq!(reader.metadata().file_metadata().schema_descr().num_columns(), 6);
+    }
+}
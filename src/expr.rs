@@ -0,0 +1,188 @@
+// evaluating expressions against a single entry
+
+use chrono::Utc;
+
+use crate::error::LsqlError;
+use crate::files::{current_user, field_kind, is_field, unknown_field, FieldKind, FieldValue, FileInfo};
+use crate::parser::{Expression, Operator};
+
+// every function `evaluate` understands, for `help functions`
+pub const FUNCTIONS: &[(&str, &str)] = &[
+    ("now()", "the current time"),
+    ("me()", "the user running lsql"),
+    ("lower(text)", "text in lower case"),
+    ("upper(text)", "text in upper case"),
+    ("replace(text, from, to)", "text with every `from` replaced by `to`"),
+    ("concat(text, ...)", "all arguments joined together"),
+];
+
+fn expect_args(name: &str, args: &[Expression], count: usize) -> Result<(), String> {
+    if args.len() == count {
+        Ok(())
+    } else {
+        Err(format!("{}() takes {} argument(s), got {}", name, count, args.len()))
+    }
+}
+
+fn number(value: &FieldValue) -> Result<f64, String> {
+    match value {
+        FieldValue::Size(size) => Ok(*size as f64),
+        FieldValue::Mode(mode) => Ok(*mode as f64),
+        FieldValue::Number(number) => Ok(*number),
+        FieldValue::Duration(duration) => Ok(duration.num_seconds() as f64),
+        FieldValue::Text(text) => text
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a number", text)),
+        other => Err(format!("'{}' is not a number", other.to_text())),
+    }
+}
+
+// functions that take no arguments and do not depend on the entry,
+// so they can stand in for a literal, e.g. `where owner = me()`
+pub fn constant(name: &str) -> Option<FieldValue> {
+    match name.to_lowercase().as_str() {
+        "now" => Some(FieldValue::Date(Utc::now())),
+        "me" => Some(FieldValue::Text(current_user())),
+        _ => None,
+    }
+}
+
+// the kind of value an expression produces, known before any entry is seen
+pub fn kind(expression: &Expression) -> Option<FieldKind> {
+    match expression {
+        Expression::Literal(_) => Some(FieldKind::Text),
+        Expression::Field(name) => field_kind(name),
+        Expression::Function(name, _) if name == "now" => Some(FieldKind::Date),
+        Expression::Function(..) => Some(FieldKind::Text),
+        Expression::Arithmetic(..) => Some(FieldKind::Number),
+    }
+}
+
+// reject references to fields that do not exist before evaluating anything
+pub fn check_fields(expression: &Expression) -> Result<(), LsqlError> {
+    match expression {
+        Expression::Literal(_) => Ok(()),
+        Expression::Field(name) if is_field(name) => Ok(()),
+        Expression::Field(name) => Err(LsqlError::InvalidField(unknown_field(name))),
+        Expression::Function(_, args) => args.iter().try_for_each(check_fields),
+        Expression::Arithmetic(left, _, right) => {
+            check_fields(left)?;
+            check_fields(right)
+        }
+    }
+}
+
+pub fn evaluate(expression: &Expression, file: &FileInfo) -> Result<FieldValue, String> {
+    match expression {
+        Expression::Literal(text) => Ok(FieldValue::Text(text.clone())),
+        Expression::Field(name) => file
+            .field(name)
+            .ok_or_else(|| unknown_field(name)),
+        Expression::Function(name, args) => {
+            let text = |index: usize| evaluate(&args[index], file).map(|v| v.to_text());
+            match name.as_str() {
+                "now" | "me" => {
+                    expect_args(name, args, 0)?;
+                    Ok(constant(name).unwrap_or(FieldValue::Text(String::new())))
+                }
+                "lower" => {
+                    expect_args(name, args, 1)?;
+                    Ok(FieldValue::Text(text(0)?.to_lowercase()))
+                }
+                "upper" => {
+                    expect_args(name, args, 1)?;
+                    Ok(FieldValue::Text(text(0)?.to_uppercase()))
+                }
+                "replace" => {
+                    expect_args(name, args, 3)?;
+                    Ok(FieldValue::Text(text(0)?.replace(&text(1)?, &text(2)?)))
+                }
+                "concat" => {
+                    let parts = (0..args.len()).map(text).collect::<Result<Vec<String>, String>>()?;
+                    Ok(FieldValue::Text(parts.concat()))
+                }
+                _ => Err(format!("Unknown function '{}'", name)),
+            }
+        }
+        Expression::Arithmetic(left, operator, right) => {
+            let left = number(&evaluate(left, file)?)?;
+            let right = number(&evaluate(right, file)?)?;
+            let result = match operator {
+                Operator::Add => left + right,
+                Operator::Subtract => left - right,
+                Operator::Multiply => left * right,
+                Operator::Divide if right == 0.0 => return Err("division by zero".to_string()),
+                Operator::Divide => left / right,
+            };
+            Ok(FieldValue::Number(result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::FileType;
+
+    fn file(name: &str) -> FileInfo {
+        FileInfo {
+            size: 10,
+            modified: Utc::now(),
+            accessed: Utc::now(),
+            name: name.to_string(),
+            file_type: FileType::File,
+            path: format!("./{}", name),
+            owner: "root".to_string(),
+            group: "root".to_string(),
+            mode: 0o644,
+            root: ".".to_string(),
+            compressed_size: None,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        }
+    }
+
+    fn call(name: &str, args: Vec<Expression>) -> Expression {
+        Expression::Function(name.to_string(), args)
+    }
+
+    #[test]
+    fn test_string_functions() {
+        let name = || Expression::Field("name".to_string());
+        let literal = |s: &str| Expression::Literal(s.to_string());
+        let file = file("My Song.mp3");
+        let replaced = call("replace", vec![name(), literal(" "), literal("_")]);
+        assert_eq!(evaluate(&replaced, &file), Ok(FieldValue::Text("My_Song.mp3".to_string())));
+        let lowered = call("lower", vec![name()]);
+        assert_eq!(evaluate(&lowered, &file), Ok(FieldValue::Text("my song.mp3".to_string())));
+        let joined = call("concat", vec![literal("old_"), name()]);
+        assert_eq!(evaluate(&joined, &file), Ok(FieldValue::Text("old_My Song.mp3".to_string())));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let size = Box::new(Expression::Field("size".to_string()));
+        let number = |n: &str| Box::new(Expression::Literal(n.to_string()));
+        let halved = Expression::Arithmetic(size.clone(), Operator::Divide, number("4"));
+        assert_eq!(evaluate(&halved, &file("a")), Ok(FieldValue::Number(2.5)));
+        assert_eq!(FieldValue::Number(2.5).to_text(), "2.50");
+        let by_zero = Expression::Arithmetic(size, Operator::Divide, number("0"));
+        assert!(evaluate(&by_zero, &file("a")).is_err());
+        let text = Expression::Arithmetic(Box::new(Expression::Field("name".to_string())), Operator::Add, number("1"));
+        assert!(evaluate(&text, &file("a")).is_err());
+        assert_eq!(kind(&text), Some(FieldKind::Number));
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let file = file("a");
+        assert!(evaluate(&call("lower", vec![]), &file).is_err());
+        assert!(evaluate(&call("frobnicate", vec![]), &file).is_err());
+    }
+}
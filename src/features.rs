@@ -0,0 +1,97 @@
+// Optional subsystems compiled in through Cargo features.
+//
+// Heavyweight pieces (archive readers, content hashing, the tree index, mime
+// sniffing, columnar export, line editing in the shell) live behind features
+// so a `--no-default-features` build stays small. `lsql version --verbose` reports what the running binary was
+// built with, and code paths that need a feature call `require` to surface a
+// readable error when it is missing.
+
+pub struct Feature {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub enabled: bool,
+}
+
+pub const FEATURES: &[Feature] = &[
+    Feature {
+        name: "archive",
+        description: "query the contents of zip and tar archives",
+        enabled: cfg!(feature = "archive"),
+    },
+    Feature {
+        name: "hash",
+        description: "content hashes and duplicate detection",
+        enabled: cfg!(feature = "hash"),
+    },
+    Feature {
+        name: "index",
+        description: "`lsql index`: saved tree metadata SELECT reads instead of walking",
+        enabled: cfg!(feature = "index"),
+    },
+    Feature {
+        name: "mime",
+        description: "file type detection from magic bytes",
+        enabled: cfg!(feature = "mime"),
+    },
+    Feature {
+        name: "parquet",
+        description: "export results as parquet files",
+        enabled: cfg!(feature = "parquet"),
+    },
+    Feature {
+        name: "shell",
+        description: "line editing, history and completion in the interactive shell",
+        enabled: cfg!(feature = "shell"),
+    },
+    Feature {
+        name: "trash",
+        description: "DELETE into the system trash",
+        enabled: cfg!(feature = "trash"),
+    },
+];
+
+pub fn is_enabled(name: &str) -> bool {
+    FEATURES.iter().any(|f| f.name == name && f.enabled)
+}
+
+pub fn require(name: &str) -> Result<(), String> {
+    if is_enabled(name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "this build of lsql was compiled without the `{}` feature (rebuild with `--features {}`)",
+            name, name
+        ))
+    }
+}
+
+pub fn version(verbose: bool) -> String {
+    let mut out = format!("lsql {}", env!("CARGO_PKG_VERSION"));
+    if verbose {
+        out.push_str("\n\nfeatures:");
+        for feature in FEATURES {
+            let mark = if feature.enabled { "+" } else { "-" };
+            out.push_str(&format!("\n  {} {:<8} {}", mark, feature.name, feature.description));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbose_version_lists_features() {
+        let out = version(true);
+        for feature in FEATURES {
+            assert!(out.contains(feature.name));
+        }
+        assert!(!version(false).contains("features:"));
+    }
+
+    #[test]
+    fn test_require_reports_missing_feature() {
+        assert!(require("no-such-feature").is_err());
+    }
+}
@@ -1,7 +1,15 @@
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::path::Path;
+
 use chrono::Utc;
-use comfy_table::Table;
 
-#[derive(Debug, Copy, Clone)]
+use crate::error::LsqlError;
+use crate::filter::{self, FilterSettings};
+use crate::plugins;
+use crate::parser::ConditionNode;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FileType {
     Directory,
     File,
@@ -19,35 +27,666 @@ pub enum FilePermission {
 pub struct FileInfo {
     pub size: u64,
     pub modified: chrono::DateTime<Utc>,
+    // last access time; as fresh as the filesystem keeps it (see relatime)
+    pub accessed: chrono::DateTime<Utc>,
     pub name: String,
     pub file_type: FileType,
     pub path: String,
+    pub owner: String,
+    pub group: String,
+    pub mode: u32,
+    // the directory this entry was found under, e.g. one of several FROM paths
+    pub root: String,
+    // bytes an archive member takes inside its archive, or a file takes on
+    // a compressing filesystem; None when unknown
+    pub compressed_size: Option<u64>,
+    // Windows file attributes (FILE_ATTRIBUTE_*); 0 on other platforms
+    pub attributes: u32,
+    // inode number and hard link count; 0 and 1 where the platform has none
+    pub inode: u64,
+    pub nlink: u64,
+    // recursive size of a directory, computed on first use
+    pub dir_size: OnceCell<u64>,
+    // content type sniffed from the file's first bytes, read on first use
+    pub mime: OnceCell<String>,
+    pub digests: Digests,
+    // lines and words of a text file, read on first use
+    pub counts: OnceCell<(u64, u64)>,
+}
+
+// hex digests of a file's contents, each read on first use
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "hash"), allow(dead_code))]
+pub struct Digests {
+    sha256: OnceCell<String>,
+    md5: OnceCell<String>,
+}
+
+// resolve the owning user and group names, falling back to the numeric
+// ids when they have no entry in the user database
+#[cfg(unix)]
+pub fn owner_and_group(metadata: &std::fs::Metadata) -> (String, String) {
+    use nix::unistd::{Gid, Group, Uid, User};
+    use std::os::unix::fs::MetadataExt;
+
+    let owner = User::from_uid(Uid::from_raw(metadata.uid()))
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .unwrap_or_else(|| metadata.uid().to_string());
+    let group = Group::from_gid(Gid::from_raw(metadata.gid()))
+        .ok()
+        .flatten()
+        .map(|group| group.name)
+        .unwrap_or_else(|| metadata.gid().to_string());
+    (owner, group)
+}
+
+// the name of the user running lsql, in the same form `owner` reports
+#[cfg(unix)]
+pub fn current_user() -> String {
+    use nix::unistd::{Uid, User};
+
+    let uid = Uid::current();
+    User::from_uid(uid)
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .unwrap_or_else(|| uid.to_string())
+}
+
+#[cfg(not(unix))]
+pub fn current_user() -> String {
+    std::env::var("USERNAME").unwrap_or_default()
+}
+
+// the space a file's data occupies on disk, when the filesystem stores it in
+// fewer bytes than its length (transparent compression, sparse files). Block
+// rounding on small files is ignored, so this never exceeds the length.
+#[cfg(unix)]
+pub fn allocated_size(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    metadata.is_file().then(|| (metadata.blocks() * 512).min(metadata.len()))
+}
+
+// NTFS compression is only visible through GetCompressedFileSize
+#[cfg(not(unix))]
+pub fn allocated_size(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+// the inode number and how many hard links point at it
+#[cfg(unix)]
+pub fn links(metadata: &std::fs::Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.ino(), metadata.nlink())
+}
+
+// the file index and link count need an open handle on Windows
+#[cfg(not(unix))]
+pub fn links(_metadata: &std::fs::Metadata) -> (u64, u64) {
+    (0, 1)
 }
 
+// permission bits (e.g. 0o755) without the file type bits
+#[cfg(unix)]
+pub fn mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+// best effort: only the readonly attribute is available, and on a
+// directory Explorer uses it to mark customized folders rather than to stop
+// writes, so directories are always writable here
+#[cfg(not(unix))]
+pub fn mode(metadata: &std::fs::Metadata) -> u32 {
+    if metadata.is_dir() {
+        0o755
+    } else if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+
+#[cfg(windows)]
+pub fn attributes(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::windows::fs::MetadataExt;
+    metadata.file_attributes()
+}
+
+#[cfg(not(windows))]
+pub fn attributes(_metadata: &std::fs::Metadata) -> u32 {
+    0
+}
+
+// ownership is not exposed through std on other platforms
+#[cfg(not(unix))]
+pub fn owner_and_group(_metadata: &std::fs::Metadata) -> (String, String) {
+    (String::new(), String::new())
+}
+
+// every field a query can reference
+pub const FIELDS: &[&str] = &[
+    "name", "path", "ext", "type", "size", "modified", "accessed", "age", "is_hidden", "is_system", "owner", "group", "inode", "nlink",
+    "permissions", "mode", "dir_size", "bytes", "root", "mime", "hash", "md5", "compressed_size", "ratio",
+    "lines", "words", "depth",
+];
+
+// fields that cost more than an entry's metadata, and what reading them does
+pub const EXPENSIVE_FIELDS: &[(&str, &str)] = &[
+    ("dir_size", "walks the subtree of every directory"),
+    ("mime", "opens every file to sniff its type"),
+    ("hash", "reads every file in full"),
+    ("md5", "reads every file in full"),
+    ("lines", "reads every file in full"),
+    ("words", "reads every file in full"),
+];
+
+// fields that only exist when lsql is built with a feature
+const FEATURE_FIELDS: &[(&str, &str)] = &[("mime", "mime"), ("hash", "hash"), ("md5", "hash")];
+
+// one line about each built-in field, for `help fields`
+pub fn describe_field(name: &str) -> Option<&'static str> {
+    let description = match name {
+        "name" => "file name, including the extension",
+        "path" => "full path of the entry",
+        "ext" => "extension without the dot; empty for directories",
+        "type" => "file, dir or other",
+        "size" => "size in bytes, shown humanized; compare with units like 10kb",
+        "modified" => "last modification time, compared as YYYY-MM-DD [HH:MM:SS]",
+        "accessed" => "last access time, compared like modified; filesystems mounted noatime or relatime update it rarely",
+        "age" => "time since the last modification, shown as e.g. 3 days ago; compare with durations like 30d",
+        "is_hidden" => "true for names starting with a dot, and on Windows for entries with the hidden attribute",
+        "is_system" => "true for entries Windows marks as system files; always false elsewhere",
+        "inode" => "inode number, shared by hard links to the same file; 0 off Unix",
+        "nlink" => "number of hard links to the entry, so nlink > 1 finds hard-linked files; 1 off Unix",
+        "owner" => "owning user",
+        "group" => "owning group",
+        "permissions" => "symbolic permissions such as rwxr-xr-x",
+        "mode" => "permission bits in octal, such as 644",
+        "dir_size" => "recursive size of a directory, computed only when used",
+        "bytes" => "the size as a plain number",
+        "root" => "the FROM directory the entry was found under",
+        "depth" => "levels below root: 1 for the directory's own entries, 2 for theirs and so on",
+        "compressed_size" => "bytes an archive member or a file on a compressing filesystem actually takes",
+        "ratio" => "compressed_size / size, e.g. 0.25 for a file stored in a quarter of its length",
+        "mime" => "content type from the file's magic bytes, e.g. image/png; read only when used",
+        "hash" => "sha256 of a file's contents in hex; read only when used, empty for directories",
+        "md5" => "md5 of a file's contents in hex; read only when used, empty for directories",
+        "lines" => "newlines in a text file, as wc -l counts them; read only when used, 0 for binary files",
+        "words" => "whitespace separated words in a text file, as wc -w counts them; read only when used",
+        _ => return None,
+    };
+    Some(description)
+}
+
+// the error for a field a file could not provide, naming the missing
+// feature when the field needs one
+pub fn unknown_field(name: &str) -> String {
+    let lower = name.to_lowercase();
+    FEATURE_FIELDS
+        .iter()
+        .find(|(field, _)| *field == lower)
+        .and_then(|(_, feature)| crate::features::require(feature).err())
+        .unwrap_or_else(|| format!("Unknown field '{}'", name))
+}
+
+// fields whose feature this build lacks are not fields, so using one reports
+// the missing feature through `unknown_field`
+pub fn is_field(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    let available = FEATURE_FIELDS.iter().find(|(field, _)| *field == lower).is_none_or(|(_, feature)| crate::features::is_enabled(feature));
+    (FIELDS.contains(&lower.as_str()) && available) || plugins::find(name).is_some()
+}
+
+pub fn human_readable_size(size: u64) -> String {
+    let kb = 1024;
+    let mb = kb * 1024;
+    let gb = mb * 1024;
+    let tb = gb * 1024;
+    if size < kb {
+        format!("{} B", size)
+    } else if size < mb {
+        format!("{:.2} KB", size as f64 / kb as f64)
+    } else if size < gb {
+        format!("{:.2} MB", size as f64 / mb as f64)
+    } else if size < tb {
+        format!("{:.2} GB", size as f64 / gb as f64)
+    } else {
+        format!("{:.2} TB", size as f64 / tb as f64)
+    }
+}
+
+// a span in its largest whole unit: `45 seconds`, `1 hour`, `3 days`; months
+// count 30 days and years 365, as in durations typed into queries
+pub fn human_duration(duration: chrono::Duration) -> String {
+    const UNITS: &[(&str, i64)] = &[
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+    let seconds = duration.num_seconds().abs();
+    let (unit, length) = UNITS.iter().find(|(_, length)| seconds >= *length).unwrap_or(&("second", 1));
+    let count = seconds / length;
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+// how long ago `date` was, e.g. `3 days ago`, or `in 2 hours` for the future
+pub fn relative_time(date: chrono::DateTime<Utc>) -> String {
+    let elapsed = Utc::now() - date;
+    if elapsed.num_seconds().abs() < 60 {
+        "just now".to_string()
+    } else if elapsed.num_seconds() > 0 {
+        format!("{} ago", human_duration(elapsed))
+    } else {
+        format!("in {}", human_duration(elapsed))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum FieldValue {
+    Text(String),
+    Size(u64),
+    Date(chrono::DateTime<Utc>),
+    Bool(bool),
+    Mode(u32),
+    // the result of arithmetic in a computed column
+    Number(f64),
+    // time since something happened, e.g. `age`
+    Duration(chrono::Duration),
+}
+
+impl FieldValue {
+    // the plain text form of a value, as used by string functions
+    pub fn to_text(&self) -> String {
+        match self {
+            FieldValue::Text(text) => text.clone(),
+            FieldValue::Size(size) => size.to_string(),
+            FieldValue::Date(date) => date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            FieldValue::Bool(flag) => flag.to_string(),
+            FieldValue::Mode(mode) => format!("{:o}", mode),
+            FieldValue::Number(number) if number.fract() == 0.0 => format!("{:.0}", number),
+            FieldValue::Number(number) => format!("{:.2}", number),
+            FieldValue::Duration(duration) => human_duration(*duration),
+        }
+    }
+}
+
+// the kind of value a field holds, for writers that need a schema up front
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldKind {
+    Text,
+    Size,
+    Date,
+    Bool,
+    Mode,
+    Number,
+    Duration,
+}
+
+pub fn field_kind(name: &str) -> Option<FieldKind> {
+    let kind = match name.to_lowercase().as_str() {
+        "size" | "bytes" | "dir_size" | "compressed_size" => FieldKind::Size,
+        "modified" | "accessed" => FieldKind::Date,
+        "is_hidden" | "is_system" => FieldKind::Bool,
+        "ratio" | "inode" | "nlink" | "lines" | "words" | "depth" => FieldKind::Number,
+        "age" => FieldKind::Duration,
+        "mode" => FieldKind::Mode,
+        other if is_field(other) => FieldKind::Text,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+impl FileType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileType::Directory => "dir",
+            FileType::File => "file",
+            FileType::Other => "other",
+        }
+    }
+}
 
 impl FileInfo {
-    pub fn human_readable_size(&self) -> String {
-        let size = self.size;
-        let kb = 1024;
-        let mb = kb * 1024;
-        let gb = mb * 1024;
-        let tb = gb * 1024;
-        if size < kb {
-            format!("{} B", size)
-        } else if size < mb {
-            format!("{:.2} KB", size as f64 / kb as f64)
-        } else if size < gb {
-            format!("{:.2} MB", size as f64 / mb as f64)
-        } else if size < tb {
-            format!("{:.2} GB", size as f64 / gb as f64)
+    pub fn extension(&self) -> String {
+        match self.file_type {
+            FileType::File => std::path::Path::new(&self.name)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    // symbolic permissions like `rwxr-xr-x`
+    pub fn permissions(&self) -> String {
+        let mut out = String::with_capacity(9);
+        for shift in [6, 3, 0] {
+            let bits = (self.mode >> shift) & 0o7;
+            out.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+            out.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+            out.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+        }
+        out
+    }
+
+    // like `du`: the total size of every file below a directory,
+    // or the file's own size for anything else
+    pub fn dir_size(&self) -> u64 {
+        *self.dir_size.get_or_init(|| match self.file_type {
+            FileType::Directory => walkdir::WalkDir::new(&self.path)
+                .into_iter()
+                .inspect(|_| crate::nice::pace())
+                .filter_map(Result::ok)
+                .filter_map(|entry| entry.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum(),
+            _ => self.size,
+        })
+    }
+
+    // the content type of a file from its magic bytes: `text/plain` when the
+    // header is plain UTF-8, `application/octet-stream` when nothing matches
+    #[cfg(feature = "mime")]
+    pub fn mime(&self) -> Option<String> {
+        let mime = self.mime.get_or_init(|| match self.file_type {
+            FileType::Directory => "inode/directory".to_string(),
+            FileType::Other => String::new(),
+            FileType::File => crate::cache::remember(self, "mime", || sniff(std::path::Path::new(&self.path))),
+        });
+        Some(mime.clone())
+    }
+
+    #[cfg(not(feature = "mime"))]
+    pub fn mime(&self) -> Option<String> {
+        None
+    }
+
+    #[cfg(feature = "hash")]
+    pub fn hash(&self) -> Option<String> {
+        Some(self.digest(&self.digests.sha256, "hash", crate::hash::sha256))
+    }
+
+    #[cfg(feature = "hash")]
+    pub fn md5(&self) -> Option<String> {
+        Some(self.digest(&self.digests.md5, "md5", crate::hash::md5))
+    }
+
+    #[cfg(not(feature = "hash"))]
+    pub fn hash(&self) -> Option<String> {
+        None
+    }
+
+    #[cfg(not(feature = "hash"))]
+    pub fn md5(&self) -> Option<String> {
+        None
+    }
+
+    // unreadable files and anything that is not a file hash to empty text
+    #[cfg(feature = "hash")]
+    fn digest(&self, cell: &OnceCell<String>, field: &str, compute: fn(&std::path::Path) -> std::io::Result<String>) -> String {
+        cell.get_or_init(|| match self.file_type {
+            FileType::File => crate::cache::remember(self, field, || compute(std::path::Path::new(&self.path)).unwrap_or_default()),
+            _ => String::new(),
+        })
+        .clone()
+    }
+
+    // lines and words as `wc -l` and `wc -w` count them; 0 for directories,
+    // unreadable files and binary ones (any NUL byte)
+    pub fn text_counts(&self) -> (u64, u64) {
+        *self.counts.get_or_init(|| match self.file_type {
+            FileType::File => {
+                let counts = crate::cache::remember(self, "counts", || {
+                    let (lines, words) = count_text(std::path::Path::new(&self.path)).unwrap_or_default();
+                    format!("{} {}", lines, words)
+                });
+                let mut numbers = counts.split(' ').map(|n| n.parse().unwrap_or(0));
+                (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+            }
+            _ => (0, 0),
+        })
+    }
+
+    // the stored size, or the plain size when nothing is known to compress it
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size.unwrap_or(self.size)
+    }
+
+    // how much of its length an entry takes when stored; 1 for empty files
+    pub fn ratio(&self) -> f64 {
+        if self.size == 0 {
+            1.0
         } else {
-            format!("{:.2} TB", size as f64 / tb as f64)
+            self.compressed_size() as f64 / self.size as f64
         }
     }
 
+    // levels below `root`, as walkdir counts them; archive members count from
+    // the top of the archive
+    pub fn depth(&self) -> usize {
+        let path = Path::new(&self.path);
+        path.strip_prefix(&self.root).unwrap_or(path).components().count()
+    }
+
+    // roughly how many bytes this entry occupies, for --max-memory
+    pub fn estimated_size(&self) -> u64 {
+        (std::mem::size_of::<Self>()
+            + self.name.capacity()
+            + self.path.capacity()
+            + self.owner.capacity()
+            + self.group.capacity()
+            + self.root.capacity()) as u64
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.name.starts_with('.') || self.attributes & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_SYSTEM != 0
+    }
+
+    // the value of a queryable field, or None if no such field exists
+    pub fn field(&self, name: &str) -> Option<FieldValue> {
+        let value = match name.to_lowercase().as_str() {
+            "name" => FieldValue::Text(self.name.clone()),
+            "path" => FieldValue::Text(self.path.clone()),
+            "ext" => FieldValue::Text(self.extension()),
+            "type" => FieldValue::Text(self.file_type.as_str().to_string()),
+            // `bytes` is the same value as `size` but never humanized for display
+            "size" | "bytes" => FieldValue::Size(self.size),
+            "modified" => FieldValue::Date(self.modified),
+            "accessed" => FieldValue::Date(self.accessed),
+            "age" => FieldValue::Duration(Utc::now() - self.modified),
+            "is_hidden" => FieldValue::Bool(self.is_hidden()),
+            "is_system" => FieldValue::Bool(self.is_system()),
+            "owner" => FieldValue::Text(self.owner.clone()),
+            "group" => FieldValue::Text(self.group.clone()),
+            "permissions" => FieldValue::Text(self.permissions()),
+            "mode" => FieldValue::Mode(self.mode),
+            "root" => FieldValue::Text(self.root.clone()),
+            "dir_size" => FieldValue::Size(self.dir_size()),
+            "compressed_size" => FieldValue::Size(self.compressed_size()),
+            "ratio" => FieldValue::Number(self.ratio()),
+            // exact below 2^53, far beyond the inode numbers filesystems hand out
+            "inode" => FieldValue::Number(self.inode as f64),
+            "nlink" => FieldValue::Number(self.nlink as f64),
+            "depth" => FieldValue::Number(self.depth() as f64),
+            "mime" => FieldValue::Text(self.mime()?),
+            "hash" => FieldValue::Text(self.hash()?),
+            "md5" => FieldValue::Text(self.md5()?),
+            "lines" => FieldValue::Number(self.text_counts().0 as f64),
+            "words" => FieldValue::Number(self.text_counts().1 as f64),
+            _ => FieldValue::Text(plugins::find(name)?.value(self).unwrap_or_default()),
+        };
+        Some(value)
+    }
+
+    pub fn human_readable_size(&self) -> String {
+        human_readable_size(self.size)
+    }
+
     pub fn human_readable_modified(&self) -> String {
         self.modified.format("%Y-%m-%d %H:%M:%S").to_string()
     }
+
+    // a field formatted for display, or None if no such field exists
+    pub fn display_field(&self, name: &str) -> Option<String> {
+        let text = match name.to_lowercase().as_str() {
+            "size" => self.human_readable_size(),
+            "modified" => self.human_readable_modified(),
+            "accessed" => self.accessed.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "age" => relative_time(self.modified),
+            "mode" => format!("{:o}", self.mode),
+            "dir_size" => human_readable_size(self.dir_size()),
+            "compressed_size" => human_readable_size(self.compressed_size()),
+            _ => self.field(name)?.to_text(),
+        };
+        Some(text)
+    }
+}
+
+#[cfg(feature = "mime")]
+fn sniff(path: &std::path::Path) -> String {
+    use std::io::Read;
+
+    // the longest signature infer looks for fits in this much
+    let mut header = Vec::with_capacity(8192);
+    let read = std::fs::File::open(path).and_then(|file| file.take(8192).read_to_end(&mut header));
+    if read.is_err() {
+        return String::new();
+    }
+    match infer::get(&header) {
+        Some(kind) => kind.mime_type().to_string(),
+        None if header.is_empty() => "inode/x-empty".to_string(),
+        None if !header.contains(&0) && std::str::from_utf8(&header).is_ok() => "text/plain".to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+fn count_text(path: &std::path::Path) -> std::io::Result<(u64, u64)> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = [0u8; 64 * 1024];
+    let (mut lines, mut words, mut in_word) = (0, 0, false);
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok((lines, words));
+        }
+        for &byte in &buffer[..read] {
+            match byte {
+                0 => return Ok((0, 0)),
+                b'\n' => lines += 1,
+                _ => {}
+            }
+            let space = byte.is_ascii_whitespace();
+            words += (!space && !in_word) as u64;
+            in_word = !space;
+        }
+    }
+}
+
+// one key to sort on, from ORDER BY or `--sort size:desc,name`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+    // text compares runs of digits as numbers
+    pub natural: bool,
+}
+
+impl SortKey {
+    // `size:desc,name:asc`; a key without a direction sorts ascending
+    pub fn parse_list(text: &str) -> Result<Vec<SortKey>, String> {
+        text.split(',')
+            .map(|key| {
+                let (field, direction) = key.trim().split_once(':').unwrap_or((key.trim(), "asc"));
+                let descending = match direction.to_lowercase().as_str() {
+                    "asc" => false,
+                    "desc" => true,
+                    other => return Err(format!("Unknown sort direction '{}' (expected asc or desc)", other)),
+                };
+                if !is_field(field) {
+                    return Err(unknown_field(field));
+                }
+                Ok(SortKey { field: field.to_lowercase(), descending, natural: false })
+            })
+            .collect()
+    }
+}
+
+// the first key that differs decides, each in its own direction
+pub fn compare_keys(a: &FileInfo, b: &FileInfo, keys: &[SortKey]) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = match (a.field(&key.field), b.field(&key.field)) {
+                (Some(FieldValue::Text(a)), Some(FieldValue::Text(b))) if key.natural => compare_natural(&a, &b),
+                (a, b) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            };
+            if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+// `file2` before `file10`: runs of digits compare by value, the rest as
+// text; strings that only differ in leading zeros fall back to plain order
+pub fn compare_natural(a: &str, b: &str) -> Ordering {
+    let (mut left, mut right) = (a, b);
+    loop {
+        let (Some(l), Some(r)) = (left.chars().next(), right.chars().next()) else {
+            return left.len().cmp(&right.len()).then_with(|| a.cmp(b));
+        };
+        let ordering = if l.is_ascii_digit() && r.is_ascii_digit() {
+            let l_end = left.find(|c: char| !c.is_ascii_digit()).unwrap_or(left.len());
+            let r_end = right.find(|c: char| !c.is_ascii_digit()).unwrap_or(right.len());
+            let (l_digits, r_digits) = (left[..l_end].trim_start_matches('0'), right[..r_end].trim_start_matches('0'));
+            let ordering = l_digits.len().cmp(&r_digits.len()).then_with(|| l_digits.cmp(r_digits));
+            (left, right) = (&left[l_end..], &right[r_end..]);
+            ordering
+        } else {
+            (left, right) = (&left[l.len_utf8()..], &right[r.len_utf8()..]);
+            l.cmp(&r)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// ORDER BY: the first field that differs decides
+pub fn compare_by(a: &FileInfo, b: &FileInfo, fields: &[String], descending: bool) -> Ordering {
+    let ordering = fields
+        .iter()
+        .map(|field| {
+            a.field(field)
+                .partial_cmp(&b.field(field))
+                .unwrap_or(Ordering::Equal)
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal);
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
 }
 
 #[derive(Debug)]
@@ -60,21 +699,98 @@ impl FileQuerySet {
         FileQuerySet { result: files }
     }
 
-    pub fn table_them(&self) -> Table{
-        let mut table = Table::new();
-        table
-        .set_header(vec![
-            "Name",
-            "Size",
-            "Modified",
-        ]);
-        for file in &self.result {
-            table.add_row(vec![
-                file.name.clone(),
-                file.human_readable_size(),
-                file.human_readable_modified(),
-            ]);
-        };
-        return table;
+    pub fn filter(self, condition: &ConditionNode, settings: &FilterSettings) -> Result<Self, LsqlError> {
+        let mut result = Vec::new();
+        for file in self.result {
+            if filter::keep(&file, condition, settings)? {
+                result.push(file);
+            }
+        }
+        Ok(FileQuerySet { result })
+    }
+
+    pub fn order_by(mut self, fields: &[String], descending: bool) -> Result<Self, LsqlError> {
+        for field in fields {
+            if !is_field(field) {
+                return Err(LsqlError::InvalidField(unknown_field(field)));
+            }
+        }
+        self.result.sort_by(|a, b| compare_by(a, b, fields, descending));
+        Ok(self)
+    }
+
+    // keep the first entry for every distinct key, preserving order
+    pub fn distinct<K, F>(self, key: F) -> Result<Self, String>
+    where
+        K: Eq + std::hash::Hash,
+        F: Fn(&FileInfo) -> Result<K, String>,
+    {
+        let mut seen = std::collections::HashSet::new();
+        let mut result = Vec::new();
+        for file in self.result {
+            if seen.insert(key(&file)?) {
+                result.push(file);
+            }
+        }
+        Ok(FileQuerySet { result })
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.result.drain(..offset.min(self.result.len()));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.result.truncate(limit);
+        self
+    }
+
+    pub fn files(&self) -> &[FileInfo] {
+        &self.result
+    }
+
+    pub fn into_files(self) -> Vec<FileInfo> {
+        self.result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_natural() {
+        let mut names = vec!["file10.txt", "file2.txt", "File1.txt", "file02.txt", "file1.txt", "v1.10", "v1.9"];
+        names.sort_by(|a, b| compare_natural(a, b));
+        assert_eq!(names, ["File1.txt", "file1.txt", "file02.txt", "file2.txt", "file10.txt", "v1.9", "v1.10"]);
+        assert_eq!(compare_natural("a", "a"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_bytes_field() {
+        let dir = crate::testing::TempDir::new("bytes-field");
+        std::fs::write(dir.join("big.bin"), vec![0u8; 1536]).unwrap();
+        std::fs::write(dir.join("small.bin"), "12").unwrap();
+        let files = crate::fs::list_dir_contents(&dir, &Default::default()).unwrap();
+        let big = files.iter().find(|file| file.name == "big.bin").unwrap();
+        assert_eq!(big.display_field("bytes").unwrap(), "1536");
+        assert_eq!(big.display_field("size").unwrap(), human_readable_size(1536));
+        assert!(matches!(big.field("BYTES"), Some(FieldValue::Size(1536))));
+
+        let command = crate::parser::parse("select * where bytes > 1000").unwrap().1.remove(0);
+        let crate::parser::Command::Select { where_clause: Some(condition), .. } = command else { panic!("not a select") };
+        let kept = FileQuerySet::new(files).filter(&condition, &Default::default()).unwrap();
+        assert_eq!(kept.files().iter().map(|file| file.name.as_str()).collect::<Vec<_>>(), ["big.bin"]);
+    }
+
+    #[test]
+    fn test_feature_fields_follow_the_build() {
+        for (field, feature) in FEATURE_FIELDS {
+            let enabled = crate::features::is_enabled(feature);
+            assert_eq!(is_field(field), enabled, "{}", field);
+            assert_eq!(unknown_field(field).contains(&format!("`{}` feature", feature)), !enabled, "{}", field);
+        }
+        assert!(is_field("NAME"));
+        assert_eq!(unknown_field("nope"), "Unknown field 'nope'");
     }
 }
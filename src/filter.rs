@@ -0,0 +1,551 @@
+use std::cmp::Ordering;
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use colored::Colorize;
+
+use crate::error::LsqlError;
+use crate::files::{unknown_field, FieldValue, FileInfo};
+use crate::parser::{ConditionNode, WhereClause};
+
+// fields that name an entry on disk and follow the filesystem's case rules
+const PATH_FIELDS: &[&str] = &["name", "path", "ext"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilterSettings {
+    // compare name/path/ext with = and != ignoring case, like a
+    // case-insensitive filesystem would
+    pub case_insensitive_paths: bool,
+    // --ignore-case: every text comparison ignores case
+    pub ignore_case: bool,
+}
+
+pub fn matches(file: &FileInfo, condition: &ConditionNode, settings: &FilterSettings) -> Result<bool, LsqlError> {
+    match condition {
+        ConditionNode::Condition(clause) => evaluate(file, clause, settings),
+        ConditionNode::And(left, right) => {
+            Ok(matches(file, left, settings)? && matches(file, right, settings)?)
+        }
+        ConditionNode::Or(left, right) => {
+            Ok(matches(file, left, settings)? || matches(file, right, settings)?)
+        }
+        ConditionNode::Not(inner) => Ok(!matches(file, inner, settings)?),
+    }
+}
+
+// --trace-filter explains this many rejected entries per statement
+const TRACE_LIMIT: usize = 25;
+
+static TRACE: AtomicBool = AtomicBool::new(false);
+static TRACED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn enable_trace() {
+    TRACE.store(true, atomic::Ordering::Relaxed);
+}
+
+// called before each statement so every statement gets its own sample
+pub fn reset_trace() {
+    TRACED.store(0, atomic::Ordering::Relaxed);
+}
+
+// `matches`, and with --trace-filter say on stderr which part of the
+// condition turned an entry away
+pub fn keep(file: &FileInfo, condition: &ConditionNode, settings: &FilterSettings) -> Result<bool, LsqlError> {
+    let matched = matches(file, condition, settings)?;
+    crate::stats::tested(matched);
+    if !matched && TRACE.load(atomic::Ordering::Relaxed) {
+        let traced = TRACED.fetch_add(1, atomic::Ordering::Relaxed);
+        if traced < TRACE_LIMIT {
+            let reason = rejection(file, condition, settings)?.unwrap_or_default();
+            eprintln!("{} {}: {}", "rejected".yellow(), file.path, reason);
+        } else if traced == TRACE_LIMIT {
+            eprintln!("{} further rejections are not traced", "rejected".yellow());
+        }
+    }
+    Ok(matched)
+}
+
+// the part of `condition` that rejects `file`, or None if it matches
+pub fn rejection(file: &FileInfo, condition: &ConditionNode, settings: &FilterSettings) -> Result<Option<String>, LsqlError> {
+    Ok(match condition {
+        ConditionNode::Condition(clause) if !evaluate(file, clause, settings)? => {
+            let field = clause_field(clause);
+            let actual = file.display_field(field).unwrap_or_default();
+            Some(format!("{} is false ({} is {:?})", clause, field, actual))
+        }
+        ConditionNode::Condition(_) => None,
+        ConditionNode::And(left, right) => match rejection(file, left, settings)? {
+            Some(reason) => Some(reason),
+            None => rejection(file, right, settings)?,
+        },
+        ConditionNode::Or(left, right) => match (rejection(file, left, settings)?, rejection(file, right, settings)?) {
+            (Some(left), Some(right)) => Some(format!("neither {} nor {}", left, right)),
+            _ => None,
+        },
+        ConditionNode::Not(inner) => matches(file, inner, settings)?.then(|| format!("{} holds, so {} is false", inner, condition)),
+    })
+}
+
+fn clause_field(clause: &WhereClause) -> &str {
+    match clause {
+        WhereClause::Equal(field, _)
+        | WhereClause::NotEqual(field, _)
+        | WhereClause::LessThan(field, _)
+        | WhereClause::LessThanOrEqual(field, _)
+        | WhereClause::GreaterThan(field, _)
+        | WhereClause::GreaterThanOrEqual(field, _)
+        | WhereClause::Contains(field, _)
+        | WhereClause::Like(field, _)
+        | WhereClause::ILike(field, _)
+        | WhereClause::UnknownOperator(field, _) => field,
+    }
+}
+
+fn evaluate(file: &FileInfo, clause: &WhereClause, settings: &FilterSettings) -> Result<bool, LsqlError> {
+    let (field, value) = match clause {
+        WhereClause::Equal(field, value)
+        | WhereClause::NotEqual(field, value)
+        | WhereClause::LessThan(field, value)
+        | WhereClause::LessThanOrEqual(field, value)
+        | WhereClause::GreaterThan(field, value)
+        | WhereClause::GreaterThanOrEqual(field, value)
+        | WhereClause::Contains(field, value)
+        | WhereClause::Like(field, value)
+        | WhereClause::ILike(field, value) => (field, value),
+        WhereClause::UnknownOperator(field, _) => {
+            return Err(LsqlError::InvalidValue(format!("Unknown operator in condition on '{}'", field)))
+        }
+    };
+    let actual = file
+        .field(field)
+        .ok_or_else(|| LsqlError::InvalidField(unknown_field(field)))?;
+    if let WhereClause::Contains(..) | WhereClause::Like(..) | WhereClause::ILike(..) = clause {
+        return match actual {
+            FieldValue::Text(text) => Ok(text_matches(clause, &text, value, settings)),
+            _ => Err(LsqlError::InvalidValue(format!("{} only applies to text fields, not '{}'", operator_name(clause), field))),
+        };
+    }
+    let is_equality = matches!(clause, WhereClause::Equal(..) | WhereClause::NotEqual(..));
+    let ordering = match actual {
+        FieldValue::Text(text)
+            if settings.ignore_case
+                || (is_equality
+                    && settings.case_insensitive_paths
+                    && PATH_FIELDS.contains(&field.to_lowercase().as_str())) =>
+        {
+            text.to_lowercase().cmp(&value.to_lowercase())
+        }
+        actual => compare(&actual, value).map_err(LsqlError::InvalidValue)?,
+    };
+    Ok(match clause {
+        WhereClause::Equal(..) => ordering == Ordering::Equal,
+        WhereClause::NotEqual(..) => ordering != Ordering::Equal,
+        WhereClause::LessThan(..) => ordering == Ordering::Less,
+        WhereClause::LessThanOrEqual(..) => ordering != Ordering::Greater,
+        WhereClause::GreaterThan(..) => ordering == Ordering::Greater,
+        WhereClause::GreaterThanOrEqual(..) => ordering != Ordering::Less,
+        WhereClause::Contains(..) | WhereClause::Like(..) | WhereClause::ILike(..) | WhereClause::UnknownOperator(..) => false,
+    })
+}
+
+fn operator_name(clause: &WhereClause) -> &'static str {
+    match clause {
+        WhereClause::Like(..) => "LIKE",
+        WhereClause::ILike(..) => "ILIKE",
+        _ => "CONTAINS",
+    }
+}
+
+// CONTAINS, LIKE and ILIKE on a text field; with --ignore-case the first two
+// ignore case as well
+fn text_matches(clause: &WhereClause, text: &str, value: &str, settings: &FilterSettings) -> bool {
+    let ignore_case = settings.ignore_case || matches!(clause, WhereClause::ILike(..));
+    match clause {
+        WhereClause::Contains(..) if ignore_case => text.to_lowercase().contains(&value.to_lowercase()),
+        WhereClause::Contains(..) => text.contains(value),
+        _ => wildcard_match(value, text, ignore_case),
+    }
+}
+
+// one piece of a LIKE pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Wildcard {
+    // `*` or `%`: any run of characters, including none
+    Any,
+    // `?` or `_`: exactly one character
+    One,
+    Literal(char),
+}
+
+// a backslash makes the character after it literal, so `100\%` matches
+// only "100%"
+fn wildcards(pattern: &str) -> Vec<Wildcard> {
+    let mut out = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '*' | '%' => Wildcard::Any,
+            '?' | '_' => Wildcard::One,
+            '\\' => Wildcard::Literal(chars.next().unwrap_or('\\')),
+            c => Wildcard::Literal(c),
+        });
+    }
+    out
+}
+
+// LIKE patterns: `*` or `%` stands for any run of characters and `?` or `_`
+// for exactly one; everything else, including characters that are special
+// in regular expressions or globs, matches itself
+pub fn wildcard_match(pattern: &str, text: &str, ignore_case: bool) -> bool {
+    let fold = |c: char| if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c };
+    let pattern = wildcards(pattern);
+    let text: Vec<char> = text.chars().map(fold).collect();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and how much text it has taken so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(Wildcard::Any) => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(Wildcard::One) => {
+                p += 1;
+                t += 1;
+            }
+            Some(Wildcard::Literal(c)) if fold(*c) == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the last `*` take one more character and retry
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&piece| piece == Wildcard::Any)
+}
+
+// compare a field's value against the literal from the query,
+// reading the literal as the same kind of value as the field
+fn compare(actual: &FieldValue, literal: &str) -> Result<Ordering, String> {
+    match actual {
+        FieldValue::Text(text) => Ok(text.as_str().cmp(literal)),
+        FieldValue::Size(size) => Ok(size.cmp(&parse_size(literal)?)),
+        FieldValue::Date(date) => Ok(date.cmp(&parse_date(literal)?)),
+        FieldValue::Bool(flag) => {
+            let expected: bool = literal
+                .to_lowercase()
+                .parse()
+                .map_err(|_| format!("Invalid boolean '{}'", literal))?;
+            Ok(flag.cmp(&expected))
+        }
+        FieldValue::Mode(mode) => {
+            let expected = u32::from_str_radix(literal, 8)
+                .map_err(|_| format!("Invalid octal mode '{}'", literal))?;
+            Ok(mode.cmp(&expected))
+        }
+        FieldValue::Duration(duration) => Ok(duration.cmp(&parse_duration(literal)?)),
+        FieldValue::Number(number) => {
+            let expected: f64 = literal
+                .parse()
+                .map_err(|_| format!("Invalid number '{}'", literal))?;
+            number
+                .partial_cmp(&expected)
+                .ok_or_else(|| format!("Cannot compare {} with {}", number, expected))
+        }
+    }
+}
+
+// sizes like `512`, `10kb` or `1.5GB`, using the same 1024-based units
+// the table displays
+pub fn parse_size(literal: &str) -> Result<u64, String> {
+    let lower = literal.trim().to_lowercase();
+    let split = lower
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let multiplier: u64 = match unit.trim() {
+        "" | "b" => 1,
+        "kb" | "k" => 1024,
+        "mb" | "m" => 1024 * 1024,
+        "gb" | "g" => 1024 * 1024 * 1024,
+        "tb" | "t" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("Invalid size '{}'", literal)),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}'", literal))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+// durations like `7d`, `2 weeks` or `90 min`; a month counts 30 days and a
+// year 365
+pub fn parse_duration(literal: &str) -> Result<chrono::Duration, String> {
+    let lower = literal.trim().to_lowercase();
+    let split = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let invalid = || format!("Invalid duration '{}', expected e.g. 7d or \"2 weeks\"", literal);
+    let number: i64 = number.parse().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "wk" | "wks" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        "mo" | "month" | "months" => 30 * 24 * 60 * 60,
+        "y" | "yr" | "yrs" | "year" | "years" => 365 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(seconds).and_then(chrono::Duration::try_seconds).ok_or_else(invalid)
+}
+
+// a relative date, `now() - 7d` or `now() + 1h`, worked out against the
+// current time; None when `literal` is not one
+fn relative_date(literal: &str) -> Option<Result<chrono::DateTime<Utc>, String>> {
+    let offset = literal.trim().strip_prefix("now()")?.trim_start();
+    if offset.is_empty() {
+        return Some(Ok(Utc::now()));
+    }
+    let (sign, amount) = offset.split_at(offset.find(|c: char| !c.is_whitespace()).map_or(0, |i| i + 1));
+    let date = parse_duration(amount).and_then(|duration| {
+        let date = match sign.trim() {
+            "-" => Utc::now().checked_sub_signed(duration),
+            "+" => Utc::now().checked_add_signed(duration),
+            _ => return Err(format!("Invalid date '{}', expected now() - <duration>", literal)),
+        };
+        date.ok_or_else(|| format!("Date '{}' is out of range", literal))
+    });
+    Some(date)
+}
+
+pub fn parse_date(literal: &str) -> Result<chrono::DateTime<Utc>, String> {
+    if let Some(date) = relative_date(literal) {
+        return date;
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(literal, "%Y-%m-%d %H:%M:%S") {
+        return Ok(Utc.from_utc_datetime(&datetime));
+    }
+    NaiveDate::parse_from_str(literal, "%Y-%m-%d")
+        .map(|date| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default()))
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", literal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::FileType;
+    use crate::parser::parse;
+    use crate::parser::Command;
+
+    fn file(name: &str) -> FileInfo {
+        FileInfo {
+            size: 10,
+            modified: Utc::now(),
+            accessed: Utc::now(),
+            name: name.to_string(),
+            file_type: FileType::File,
+            path: format!("./{}", name),
+            owner: "root".to_string(),
+            group: "wheel".to_string(),
+            mode: 0o644,
+            root: ".".to_string(),
+            compressed_size: None,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        }
+    }
+
+    fn condition(query: &str) -> ConditionNode {
+        match parse(query).unwrap().1.remove(0) {
+            Command::Select { where_clause, .. } => where_clause.unwrap(),
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_not_group() {
+        let cond = condition("select * where not (ext = \"tmp\" or is_hidden = true)");
+        assert!(matches(&file("main.rs"), &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&file("cache.tmp"), &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&file(".env"), &cond, &FilterSettings::default()).unwrap());
+    }
+
+    #[test]
+    fn test_owner_and_group() {
+        let cond = condition("select * where owner = \"root\" and group != 'staff'");
+        assert!(matches(&file("main.rs"), &cond, &FilterSettings::default()).unwrap());
+    }
+
+    #[test]
+    fn test_permission_queries() {
+        let check = |query| matches(&file("a"), &condition(query), &FilterSettings::default()).unwrap();
+        assert!(check("select * where mode = 644"));
+        assert!(check("select * where permissions = 'rw-r--r--'"));
+        assert!(!check("select * where permissions contains 'x'"));
+    }
+
+    #[test]
+    fn test_hard_links() {
+        let cond = condition("select * where nlink > 1 and inode = 4242");
+        let linked = FileInfo { inode: 4242, nlink: 2, ..file("a") };
+        assert!(matches(&linked, &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&FileInfo { inode: 4242, ..file("a") }, &cond, &FilterSettings::default()).unwrap());
+    }
+
+    #[cfg(feature = "mime")]
+    #[test]
+    fn test_mime_ignores_extension() {
+        let dir = crate::testing::TempDir::new("mime");
+        std::fs::write(dir.join("photo.txt"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        std::fs::write(dir.join("notes"), "just text").unwrap();
+        let with_path = |name: &str| FileInfo { path: dir.join(name).display().to_string(), ..file(name) };
+        let cond = condition("select * where mime contains 'image/'");
+        assert!(matches(&with_path("photo.txt"), &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&with_path("notes"), &cond, &FilterSettings::default()).unwrap());
+        assert_eq!(with_path("notes").mime().as_deref(), Some("text/plain"));
+    }
+
+    #[test]
+    fn test_depth() {
+        let entry = |path: &str| FileInfo { path: path.to_string(), root: "/home/me".to_string(), ..file("x") };
+        assert_eq!(entry("/home/me/notes.txt").depth(), 1);
+        assert_eq!(entry("/home/me/src/bin/main.rs").depth(), 3);
+        // archive members are relative to the archive
+        assert_eq!(FileInfo { path: "logs/app.log".to_string(), root: "backup.zip".to_string(), ..file("x") }.depth(), 2);
+        let cond = condition("select * where depth <= 2");
+        assert!(!matches(&entry("/home/me/src/bin/main.rs"), &cond, &FilterSettings::default()).unwrap());
+    }
+
+    #[test]
+    fn test_text_counts() {
+        let dir = crate::testing::TempDir::new("counts");
+        std::fs::write(dir.join("poem.txt"), "two words\n  and three more\n").unwrap();
+        std::fs::write(dir.join("blob.bin"), b"a\nb\0c\n").unwrap();
+        let with_path = |name: &str| FileInfo { path: dir.join(name).display().to_string(), ..file(name) };
+        assert_eq!(with_path("poem.txt").text_counts(), (2, 5));
+        assert_eq!(with_path("blob.bin").text_counts(), (0, 0));
+        let cond = condition("select * where lines = 2 and words > 4");
+        assert!(matches(&with_path("poem.txt"), &cond, &FilterSettings::default()).unwrap());
+    }
+
+    #[test]
+    fn test_rejection_names_the_failing_condition() {
+        let cond = condition("select * where ext = \"rs\" and (size > 1kb or name contains 'main')");
+        let settings = FilterSettings::default();
+        assert_eq!(rejection(&file("main.rs"), &cond, &settings).unwrap(), None);
+        assert_eq!(
+            rejection(&file("notes.txt"), &cond, &settings).unwrap().unwrap(),
+            "ext = \"rs\" is false (ext is \"txt\")"
+        );
+        assert_eq!(
+            rejection(&file("lib.rs"), &cond, &settings).unwrap().unwrap(),
+            "neither size > \"1kb\" is false (size is \"10 B\") nor name CONTAINS \"main\" is false (name is \"lib.rs\")"
+        );
+        let cond = condition("select * where not (ext = 'rs')");
+        assert!(rejection(&file("lib.rs"), &cond, &settings).unwrap().unwrap().ends_with("so NOT ext = \"rs\" is false"));
+    }
+
+    #[test]
+    fn test_compression_fields() {
+        let stored = FileInfo { size: 1000, compressed_size: Some(250), ..file("app.log") };
+        let cond = condition("select * where ratio < 0.5 and compressed_size <= 250");
+        assert!(matches(&stored, &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&file("plain.txt"), &cond, &FilterSettings::default()).unwrap());
+        assert_eq!(stored.field("ratio"), Some(FieldValue::Number(0.25)));
+    }
+
+    #[test]
+    fn test_accessed_dates() {
+        let stale = FileInfo { accessed: "2021-06-01T00:00:00Z".parse().unwrap(), ..file("old.log") };
+        let cond = condition("select * where accessed < \"2022-01-01\"");
+        assert!(matches(&stale, &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&file("new.log"), &cond, &FilterSettings::default()).unwrap());
+    }
+
+    #[test]
+    fn test_relative_dates() {
+        let week_old = FileInfo { modified: Utc::now() - chrono::Duration::days(8), ..file("old.log") };
+        for query in ["select * where modified > now() - 7d", "select * where modified within \"1 week\""] {
+            let cond = condition(query);
+            assert!(matches(&file("new.log"), &cond, &FilterSettings::default()).unwrap(), "{}", query);
+            assert!(!matches(&week_old, &cond, &FilterSettings::default()).unwrap(), "{}", query);
+        }
+        assert_eq!(parse_duration("2 weeks"), Ok(chrono::Duration::days(14)));
+        assert_eq!(parse_duration("90min"), Ok(chrono::Duration::minutes(90)));
+        assert!(parse_duration("3 fortnights").is_err());
+        let cond = condition("select * where age > 7d");
+        assert!(matches(&week_old, &cond, &FilterSettings::default()).unwrap());
+        assert!(!matches(&file("new.log"), &cond, &FilterSettings::default()).unwrap());
+        assert_eq!(week_old.display_field("age").as_deref(), Some("1 week ago"));
+        assert_eq!(crate::files::human_duration(chrono::Duration::hours(49)), "2 days");
+        assert!(parse("select * where modified > now() - 3 fortnights").map_or(true, |(rest, _)| !rest.is_empty()));
+        // `now()` is the time of the comparison, not of parsing
+        let cond = condition("select * where modified < now()");
+        let later = file("later.log");
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(matches(&later, &cond, &FilterSettings::default()).unwrap());
+        let cond = condition("select * where modified within soon");
+        assert!(matches!(matches(&file("a"), &cond, &FilterSettings::default()), Err(LsqlError::InvalidValue(_))));
+    }
+
+    #[test]
+    fn test_like_and_ignore_case() {
+        let check = |query, name, settings: &FilterSettings| matches(&file(name), &condition(query), settings).unwrap();
+        let default = FilterSettings::default();
+        assert!(check("select * where name like '*.jpg'", "cat.jpg", &default));
+        assert!(!check("select * where name like '*.jpg'", "cat.JPG", &default));
+        assert!(check("select * where name ilike \"*.JPG\"", "cat.jpg", &default));
+        assert!(check("select * where name like 'c?t.*'", "cat.jpg", &default));
+        assert!(!check("select * where name like 'c?t'", "cart", &default));
+        assert!(check("select * where name like '*a*b*'", "xaxxbx", &default));
+        assert!(check("select * where name like 'report_20%.pdf'", "report_2023.pdf", &default));
+        assert!(check("select * where name like 'c++ (draft)*'", "c++ (draft) v2.txt", &default));
+        assert!(check("select * where name like '[1].txt'", "[1].txt", &default));
+        assert!(check("select * where name like '100\\\\%'", "100%", &default));
+        assert!(!check("select * where name like '100\\\\%'", "1000", &default));
+        let ignore_case = FilterSettings { ignore_case: true, ..FilterSettings::default() };
+        assert!(check("select * where name like '*.jpg'", "cat.JPG", &ignore_case));
+        assert!(check("select * where owner = 'ROOT' and name contains 'AT'", "cat", &ignore_case));
+        assert!(!check("select * where owner = 'ROOT'", "cat", &default));
+        assert!(matches!(
+            matches(&file("a"), &condition("select * where size like '1*'"), &default),
+            Err(LsqlError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_size_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10kb"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5GB"), Ok(3 * 512 * 1024 * 1024));
+        assert!(parse_size("5 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_case_insensitive_paths() {
+        let cond = condition("select * where name = \"readme.md\"");
+        let insensitive = FilterSettings { case_insensitive_paths: true, ..FilterSettings::default() };
+        assert!(!matches(&file("README.md"), &cond, &FilterSettings::default()).unwrap());
+        assert!(matches(&file("README.md"), &cond, &insensitive).unwrap());
+        // only equality follows the filesystem, other fields keep exact comparisons
+        let cond = condition("select * where owner = \"ROOT\"");
+        assert!(!matches(&file("README.md"), &cond, &insensitive).unwrap());
+    }
+
+    #[test]
+    fn test_unknown_field_is_an_error() {
+        let cond = condition("select * where colour = 'red'");
+        assert!(matches(&file("main.rs"), &cond, &FilterSettings::default()).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+// `gen-fixture`: a synthetic directory tree to try queries on, DELETE and
+// UPDATE above all, without risking real files. The same seed always builds
+// the same layout (names, sizes, contents and ages), so a bug report can name
+// the command that produced the tree it was seen in.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::files::human_readable_size;
+
+// how file sizes are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sizes {
+    // mostly small files and a few large ones, like a real home directory
+    Zipf,
+    Uniform,
+    Empty,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    pub dir: String,
+    pub files: usize,
+    pub depth: usize,
+    pub sizes: Sizes,
+    pub seed: u64,
+}
+
+impl Spec {
+    // `DIR [--files n] [--depth n] [--sizes zipf|uniform|empty] [--seed n]`
+    pub fn parse(args: &[String]) -> Result<Spec, String> {
+        let mut spec = Spec { dir: String::new(), files: 100, depth: 3, sizes: Sizes::Zipf, seed: 1 };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("Flag '{}' expects a value", arg));
+            let number = |text: &String| text.parse().map_err(|_| format!("{} expects a number, got '{}'", arg, text));
+            match arg.as_str() {
+                "--files" => spec.files = number(value()?)? as usize,
+                "--depth" => spec.depth = number(value()?)? as usize,
+                "--seed" => spec.seed = number(value()?)?,
+                "--sizes" => {
+                    spec.sizes = match value()?.to_lowercase().as_str() {
+                        "zipf" => Sizes::Zipf,
+                        "uniform" => Sizes::Uniform,
+                        "empty" => Sizes::Empty,
+                        other => return Err(format!("Unknown size distribution '{}' (expected zipf, uniform or empty)", other)),
+                    }
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown gen-fixture flag '{}'", flag)),
+                dir if spec.dir.is_empty() => spec.dir = dir.to_string(),
+                extra => return Err(format!("gen-fixture takes one directory, got '{}' as well", extra)),
+            }
+        }
+        if spec.dir.is_empty() {
+            return Err("gen-fixture needs a directory to create, e.g. lsql gen-fixture ./sandbox".to_string());
+        }
+        Ok(spec)
+    }
+}
+
+// extensions with how often they are picked
+const EXTENSIONS: &[(&str, u64)] = &[
+    ("txt", 15), ("rs", 12), ("log", 10), ("jpg", 10), ("md", 8), ("json", 8), ("png", 8),
+    ("csv", 7), ("o", 6), ("tmp", 6), ("mp3", 4), ("zip", 3), ("JPG", 2), ("bak", 2),
+];
+const STEMS: &[&str] = &["report", "notes", "main", "photo", "data", "backup", "draft", "cache", "song", "index"];
+const DIR_NAMES: &[&str] = &["src", "docs", "assets", "build", "logs", "cache", "data", "photos", "tmp", "archive"];
+
+// splitmix64: small, fast and the same everywhere, which is all a fixture needs
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+
+    fn weighted<'a>(&mut self, choices: &[(&'a str, u64)]) -> &'a str {
+        let total: u64 = choices.iter().map(|(_, weight)| weight).sum();
+        let mut pick = self.next() % total;
+        for (choice, weight) in choices {
+            if pick < *weight {
+                return choice;
+            }
+            pick -= weight;
+        }
+        choices[0].0
+    }
+}
+
+// largest file a fixture writes
+const MAX_SIZE: u64 = 8 * 1024 * 1024;
+
+fn draw_size(sizes: Sizes, rng: &mut Rng) -> u64 {
+    match sizes {
+        // Pareto tail: half the files under 1kb, one in a few thousand over 1mb
+        Sizes::Zipf => ((512.0 * (1.0 - rng.unit()).powf(-1.0 / 1.1)) as u64).min(MAX_SIZE),
+        Sizes::Uniform => rng.next() % (64 * 1024),
+        Sizes::Empty => 0,
+    }
+}
+
+// `size` bytes drawn from `seed`, so equal seeds give identical files
+fn write_contents(path: &Path, seed: u64, size: u64) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut rng = Rng(seed);
+    let mut left = size;
+    while left > 0 {
+        let bytes = rng.next().to_le_bytes();
+        let take = left.min(bytes.len() as u64) as usize;
+        out.write_all(&bytes[..take])?;
+        left -= take as u64;
+    }
+    out.flush()
+}
+
+// build the tree `spec` describes and say what was made. Only a new or
+// empty directory is written into, so a typo cannot litter real files
+pub fn generate(spec: &Spec) -> Result<String, Box<dyn Error>> {
+    let root = Path::new(&spec.dir);
+    if root.exists() && std::fs::read_dir(root)?.next().is_some() {
+        return Err(format!("{} is not empty; gen-fixture only writes into a new or empty directory", spec.dir).into());
+    }
+    std::fs::create_dir_all(root)?;
+    let mut rng = Rng(spec.seed);
+    let now = SystemTime::now();
+    let mut dirs: Vec<(PathBuf, usize)> = vec![(root.to_path_buf(), 0)];
+    let mut written: Vec<(u64, u64)> = Vec::new();
+    let mut total = 0;
+    for index in 0..spec.files {
+        // now and then a new directory, below one that is not yet at the depth limit
+        let (mut dir, level) = dirs[rng.below(dirs.len())].clone();
+        if level < spec.depth && rng.chance(0.25) {
+            dir = dir.join(format!("{}{}", DIR_NAMES[rng.below(DIR_NAMES.len())], dirs.len()));
+            std::fs::create_dir(&dir)?;
+            dirs.push((dir.clone(), level + 1));
+        }
+        let extension = rng.weighted(EXTENSIONS);
+        let mut name = format!("{}_{}.{}", STEMS[rng.below(STEMS.len())], index, extension);
+        if rng.chance(0.05) {
+            name.insert(0, '.');
+        }
+        // one file in twenty repeats an earlier one, for DUPLICATES to find
+        let (seed, size) = match written.len() {
+            0 => (rng.next(), draw_size(spec.sizes, &mut rng)),
+            count if rng.chance(0.05) => written[rng.below(count)],
+            _ => (rng.next(), draw_size(spec.sizes, &mut rng)),
+        };
+        let path = dir.join(name);
+        write_contents(&path, seed, size)?;
+        // last modified up to three years back, most of them within months
+        let days = (-90.0 * (1.0 - rng.unit()).ln()).min(3.0 * 365.0);
+        let age = Duration::from_secs((days * 24.0 * 60.0 * 60.0) as u64);
+        File::options().write(true).open(&path)?.set_modified(now - age)?;
+        written.push((seed, size));
+        total += size;
+    }
+    Ok(format!(
+        "created {} files in {} directories under {} ({})",
+        spec.files,
+        dirs.len(),
+        spec.dir,
+        human_readable_size(total)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(root: &Path) -> Vec<(String, u64)> {
+        let mut entries: Vec<(String, u64)> = walkdir::WalkDir::new(root)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(root).unwrap().display().to_string();
+                (relative, entry.metadata().unwrap().len() * entry.file_type().is_file() as u64)
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn test_same_seed_same_tree() {
+        let args: Vec<String> = ["sandbox", "--files", "40", "--depth", "2", "--seed", "7"].iter().map(|a| a.to_string()).collect();
+        let spec = Spec::parse(&args).unwrap();
+        assert_eq!((spec.files, spec.depth, spec.sizes, spec.seed), (40, 2, Sizes::Zipf, 7));
+        assert!(Spec::parse(&["--files".to_string(), "9".to_string()]).is_err());
+
+        let base = crate::testing::TempDir::new("fixture");
+        let (first, second) = (base.join("a"), base.join("b"));
+        generate(&Spec { dir: first.display().to_string(), ..spec.clone() }).unwrap();
+        generate(&Spec { dir: second.display().to_string(), ..spec.clone() }).unwrap();
+        let tree = listing(&first);
+        assert_eq!(tree, listing(&second));
+        assert_eq!(walkdir::WalkDir::new(&first).into_iter().filter(|e| e.as_ref().unwrap().file_type().is_file()).count(), 40);
+        assert!(tree.iter().all(|(path, _)| Path::new(path).components().count() <= 3));
+        // never into a directory that already has something in it
+        assert!(generate(&Spec { dir: first.display().to_string(), ..spec }).is_err());
+    }
+}
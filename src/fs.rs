@@ -0,0 +1,1259 @@
+// walking directories and running statements against what was found
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use walkdir::WalkDir;
+
+use crate::cli::{Options, PathCase};
+use crate::config;
+use crate::display;
+use crate::elevate::{self, Refused};
+use crate::estimate;
+use crate::expr;
+use crate::files::{self, FieldValue, FileInfo, FileQuerySet, FileType};
+use crate::filter::{self, parse_date, FilterSettings};
+use crate::nice;
+use crate::parser::{Command, FromItem, SelectionType};
+use crate::progress::Progress;
+use crate::script;
+use crate::stats;
+use crate::State;
+
+// the session's listing: the directory's own entries, whatever --max-depth
+// and --exclude say, which statements that use them walk for themselves.
+// Only the directory is read; each statement stats the entries for the
+// fields it uses
+pub fn listing(path: &Path) -> Result<Vec<walkdir::DirEntry>, Box<dyn Error>> {
+    Ok(Walk::new(&Options::default(), None, &[])?.over(path).collect::<walkdir::Result<_>>()?)
+}
+
+// the session's listing with every field read
+pub fn list_dir_contents(path: &Path, options: &Options) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    let root = path.display().to_string();
+    listing(path)?.iter().map(|entry| file_info(entry, &root, options, Detail::Owner)).collect()
+}
+
+// the entries of the session's listing, not yet stat'ed
+pub fn listed(state: &State) -> impl Iterator<Item = Found> + '_ {
+    let root = state.path.display().to_string();
+    state.listing.iter().map(move |entry| Found::Walked(entry.clone(), root.clone()))
+}
+
+// how far below each root a walk goes and which subtrees it skips
+#[derive(Debug, Clone)]
+pub struct Walk {
+    depth: usize,
+    exclude: Vec<glob::Pattern>,
+}
+
+impl Walk {
+    // a query's DEPTH and EXCLUDE on top of --max-depth and --exclude; without
+    // either a walk reads one level, the directory's own entries
+    pub fn new(options: &Options, depth: Option<usize>, exclude: &[String]) -> Result<Self, Box<dyn Error>> {
+        if depth == Some(0) {
+            return Err("DEPTH must be at least 1".into());
+        }
+        let exclude = options
+            .exclude
+            .iter()
+            .chain(exclude)
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|e| format!("Invalid exclude pattern '{}': {}", pattern, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Walk { depth: depth.or(options.max_depth).unwrap_or(1), exclude })
+    }
+
+    // levels below each root the walk reads
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // patterns with a `/` match the path below the root, others just the name
+    fn excludes(&self, path: &Path, root: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+        self.exclude.iter().any(|pattern| {
+            if pattern.as_str().contains('/') {
+                pattern.matches(&relative)
+            } else {
+                pattern.matches(&name)
+            }
+        })
+    }
+
+    // whether walking `root` would find `path`: it is deep enough and neither
+    // it nor a directory above it is excluded
+    pub fn reaches(&self, path: &Path, root: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        let depth = relative.components().count();
+        depth > 0 && depth <= self.depth && !path.ancestors().take(depth).any(|above| self.excludes(above, root))
+    }
+
+    // the entries below `root`; an excluded directory is not descended into.
+    // Only failing to read `root` itself is an error: an unreadable entry or
+    // directory below it is reported on stderr and skipped, as `find` does
+    pub fn over(self, root: &Path) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> {
+        let base = root.to_path_buf();
+        WalkDir::new(root)
+            .min_depth(1)
+            .max_depth(self.depth)
+            .into_iter()
+            .filter_entry(move |entry| {
+                let excluded = self.excludes(entry.path(), &base);
+                if excluded {
+                    stats::pruned();
+                }
+                !excluded
+            })
+            .filter(|entry| match entry {
+                Err(e) if e.depth() > 0 => {
+                    let reason = e.io_error().map_or_else(|| e.to_string(), ToString::to_string);
+                    match e.path() {
+                        Some(path) => eprintln!("warning: skipped {}: {}", path.display(), reason),
+                        None => eprintln!("warning: skipped an entry: {}", reason),
+                    }
+                    stats::unreadable();
+                    false
+                }
+                _ => true,
+            })
+            .inspect(|_| nice::pace())
+    }
+}
+
+// the entries a walk finds below a directory, read one at a time
+pub fn entries<'a>(
+    path: &Path,
+    walk: Walk,
+    options: &'a Options,
+) -> impl Iterator<Item = Result<FileInfo, Box<dyn Error>>> + 'a {
+    let root = path.display().to_string();
+    walk.over(path).map(move |entry| file_info(&entry?, &root, options, Detail::Owner))
+}
+
+// how much of an entry a statement reads. Names and types come with the
+// directory listing; anything more costs a stat per entry, and owner and
+// group names a user database lookup on top of that
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Detail {
+    Name,
+    Metadata,
+    Owner,
+}
+
+fn file_info(entry: &walkdir::DirEntry, root: &str, options: &Options, detail: Detail) -> Result<FileInfo, Box<dyn Error>> {
+    if detail == Detail::Name {
+        return Ok(named(entry, root));
+    }
+    let metadata = entry.metadata()?;
+    let file_type = if metadata.is_dir() {
+        FileType::Directory
+    } else if metadata.is_file() {
+        FileType::File
+    } else {
+        FileType::Other
+    };
+    let last_modified = DateTime::<Utc>::from(metadata.modified()?);
+    // not every platform records access times
+    let last_accessed = metadata.accessed().map(DateTime::<Utc>::from).unwrap_or(last_modified);
+    let (owner, group) = match detail {
+        Detail::Owner => files::owner_and_group(&metadata),
+        _ => Default::default(),
+    };
+    let (inode, nlink) = files::links(&metadata);
+    let mut file_info = FileInfo {
+        size: metadata.len(),
+        modified: last_modified,
+        accessed: last_accessed,
+        name: entry.file_name().to_string_lossy().to_string(),
+        path: entry.path().display().to_string(),
+        file_type,
+        owner,
+        group,
+        mode: files::mode(&metadata),
+        root: root.to_string(),
+        compressed_size: files::allocated_size(&metadata),
+        attributes: files::attributes(&metadata),
+        inode,
+        nlink,
+        dir_size: Default::default(),
+        mime: Default::default(),
+        digests: Default::default(),
+        counts: Default::default(),
+    };
+    if options.compute_dir_sizes {
+        if let FileType::Directory = file_info.file_type {
+            file_info.size = file_info.dir_size();
+        }
+    }
+    Ok(file_info)
+}
+
+// an entry's type as its directory listing gives it, without a stat
+fn listed_type(entry: &walkdir::DirEntry) -> FileType {
+    match entry.file_type() {
+        kind if kind.is_dir() => FileType::Directory,
+        kind if kind.is_file() => FileType::File,
+        _ => FileType::Other,
+    }
+}
+
+// an entry as far as its directory listing tells, for statements that read
+// nothing else; the fields that need a stat are left empty
+fn named(entry: &walkdir::DirEntry, root: &str) -> FileInfo {
+    let file_type = listed_type(entry);
+    FileInfo {
+        size: 0,
+        modified: DateTime::UNIX_EPOCH,
+        accessed: DateTime::UNIX_EPOCH,
+        name: entry.file_name().to_string_lossy().to_string(),
+        path: entry.path().display().to_string(),
+        file_type,
+        owner: String::new(),
+        group: String::new(),
+        mode: 0,
+        root: root.to_string(),
+        compressed_size: None,
+        attributes: 0,
+        inode: 0,
+        nlink: 1,
+        dir_size: Default::default(),
+        mime: Default::default(),
+        digests: Default::default(),
+        counts: Default::default(),
+    }
+}
+
+// whether the filesystem holding `dir` ignores case, probed by looking up an
+// entry under a different case; falls back to the platform default when the
+// directory has nothing to probe with
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let probe = std::fs::read_dir(dir).ok().and_then(|entries| {
+        entries.filter_map(Result::ok).find_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let swapped: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_lowercase() {
+                        c.to_uppercase().next().unwrap_or(c)
+                    } else {
+                        c.to_lowercase().next().unwrap_or(c)
+                    }
+                })
+                .collect();
+            (swapped != name).then(|| (entry.path(), dir.join(swapped)))
+        })
+    });
+    match probe {
+        Some((original, swapped)) => same_entry(&original, &swapped),
+        None => cfg!(any(target_os = "macos", target_os = "windows")),
+    }
+}
+
+// whether `a` and `b` name the same file, by device and inode
+#[cfg(unix)]
+fn same_entry(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// elsewhere by the path each resolves to, which on Windows comes back from
+// the file itself and so in the case it is stored under; equal sizes and
+// times say nothing about two files being one
+#[cfg(not(unix))]
+fn same_entry(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+pub fn filter_settings(options: &Options, root: &Path) -> FilterSettings {
+    let case_insensitive_paths = match options.path_case {
+        PathCase::Auto => is_case_insensitive(root),
+        PathCase::Sensitive => false,
+        PathCase::Insensitive => true,
+    };
+    FilterSettings { case_insensitive_paths, ignore_case: options.ignore_case }
+}
+
+type Entries<'a> = Box<dyn Iterator<Item = Result<FileInfo, Box<dyn Error>>> + 'a>;
+
+// an entry as the walk found it, before its metadata is read
+pub enum Found {
+    // a directory entry, with the FROM directory it was found under
+    Walked(walkdir::DirEntry, String),
+    // an entry from an index or an archive
+    Read(Box<FileInfo>),
+}
+
+impl Found {
+    pub fn stat(self, options: &Options, detail: Detail) -> Result<FileInfo, Box<dyn Error>> {
+        match self {
+            Found::Walked(entry, root) => file_info(&entry, &root, options, detail),
+            Found::Read(file) => Ok(*file),
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Found::Walked(entry, _) => entry.path().to_path_buf(),
+            Found::Read(file) => PathBuf::from(&file.path),
+        }
+    }
+
+    // known before the stat, so entries of an unwanted type need none
+    pub fn file_type(&self) -> FileType {
+        match self {
+            Found::Walked(entry, _) => listed_type(entry),
+            Found::Read(file) => file.file_type,
+        }
+    }
+}
+
+pub type FoundEntries<'a> = Box<dyn Iterator<Item = Result<Found, Box<dyn Error>>> + 'a>;
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => match config::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches(['/', '\\'])),
+            None => PathBuf::from(path),
+        },
+        _ => PathBuf::from(path),
+    }
+}
+
+// the directories a FROM path names, relative to the session's directory:
+// `~` is expanded, and a glob such as `~/projects/**/src` may name several,
+// each included once however many ways it was matched
+pub fn resolve_roots(state: &State, path: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    resolve_matching(state, path, Path::is_dir)
+}
+
+// like `resolve_roots`, keeping the glob matches `keep` accepts
+fn resolve_matching(state: &State, path: &str, keep: fn(&Path) -> bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if path == "*:" {
+        let drives = drives();
+        if drives.is_empty() {
+            return Err("`*:` stands for every drive letter, which only exists on Windows".into());
+        }
+        return Ok(drives);
+    }
+    let path = state.path.join(drive_root(&expand_home(path)));
+    let pattern = path.to_string_lossy();
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![std::fs::canonicalize(&path)?]);
+    }
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for matched in glob::glob(&pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))? {
+        let matched = std::fs::canonicalize(matched?)?;
+        if keep(&matched) && !roots.contains(&matched) {
+            roots.push(matched);
+        }
+    }
+    if roots.is_empty() {
+        return Err(format!("No directory matches '{}'", pattern).into());
+    }
+    Ok(roots)
+}
+
+// every drive letter with a filesystem behind it, as `C:\`
+#[cfg(windows)]
+pub fn drives() -> Vec<PathBuf> {
+    (b'A'..=b'Z')
+        .map(|letter| PathBuf::from(format!("{}:\\", letter as char)))
+        .filter(|drive| drive.is_dir())
+        .collect()
+}
+
+#[cfg(not(windows))]
+pub fn drives() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+// a bare `D:` means the drive's root rather than the current directory on
+// that drive, which is what Windows would make of it
+fn drive_root(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    let bytes = text.as_bytes();
+    if cfg!(windows) && bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        return PathBuf::from(format!("{}\\", text));
+    }
+    path.to_path_buf()
+}
+
+// where a statement's entries come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Root {
+    Directory(PathBuf),
+    Archive(PathBuf),
+    // FROM STDIN: the paths piped in, and the directory relative ones are under
+    Listed(PathBuf, Vec<PathBuf>),
+}
+
+impl Root {
+    pub fn path(&self) -> &Path {
+        match self {
+            Root::Directory(path) | Root::Archive(path) | Root::Listed(path, _) => path,
+        }
+    }
+
+    // what the walk finds below this root; archive members come with their
+    // metadata already read
+    fn found(&self, walk: Walk, indexed: bool, verbose: bool) -> FoundEntries<'static> {
+        match self {
+            Root::Directory(dir) if indexed => match indexed_entries(dir, &walk, verbose) {
+                Some(Ok(files)) => Box::new(files.into_iter().map(|file| Ok(Found::Read(Box::new(file))))),
+                Some(Err(e)) => Box::new(std::iter::once(Err(e))),
+                None => self.found(walk, false, verbose),
+            },
+            Root::Directory(dir) => {
+                let root = dir.display().to_string();
+                Box::new(walk.over(dir).map(move |entry| Ok(Found::Walked(entry?, root.clone()))))
+            }
+            Root::Archive(file) => match archive_members(file) {
+                Ok(members) => Box::new(members.into_iter().map(|member| Ok(Found::Read(Box::new(member))))),
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            },
+            // each listed path is an entry of its own, under its parent directory
+            Root::Listed(dir, paths) => {
+                // `find .` lists ./a, which should read as dir/a
+                let paths: Vec<PathBuf> = paths
+                    .iter()
+                    .map(|path| dir.join(path).components().filter(|c| *c != std::path::Component::CurDir).collect())
+                    .collect();
+                Box::new(paths.into_iter().map(|path| {
+                    let parent = path.parent().unwrap_or(&path).display().to_string();
+                    let entry = WalkDir::new(&path).max_depth(0).into_iter().next().ok_or("empty walk")??;
+                    Ok(Found::Walked(entry, parent))
+                }))
+            }
+        }
+    }
+}
+
+// set once the query itself was read from stdin, leaving no paths to read
+static QUERY_FROM_STDIN: AtomicBool = AtomicBool::new(false);
+
+static STDIN_PATHS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+// everything piped to stdin, as the statements to run
+pub fn read_query_from_stdin() -> std::io::Result<String> {
+    QUERY_FROM_STDIN.store(true, Ordering::Relaxed);
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+// the paths piped to stdin, read on first use so every statement of a script
+// sees the same list
+fn stdin_paths() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if let Some(paths) = STDIN_PATHS.get() {
+        return Ok(paths.clone());
+    }
+    if QUERY_FROM_STDIN.load(Ordering::Relaxed) {
+        return Err("FROM STDIN reads paths from stdin, which already held the query".into());
+    }
+    if std::io::stdin().is_terminal() {
+        return Err("FROM STDIN reads paths piped in, as in `fd -e log | lsql \"select * from stdin\"`".into());
+    }
+    let mut bytes = Vec::new();
+    std::io::stdin().read_to_end(&mut bytes)?;
+    Ok(STDIN_PATHS.get_or_init(|| split_paths(&String::from_utf8_lossy(&bytes))).clone())
+}
+
+// paths separated by NULs, as `find -print0` writes them, or else by newlines
+fn split_paths(text: &str) -> Vec<PathBuf> {
+    let separator = if text.contains('\0') { '\0' } else { '\n' };
+    text.split(separator)
+        .map(|path| path.trim_end_matches('\r'))
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+#[cfg(feature = "archive")]
+fn archive_members(file: &Path) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    crate::archive::members(file)
+}
+
+#[cfg(not(feature = "archive"))]
+fn archive_members(_file: &Path) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    Err(crate::features::require("archive").unwrap_err().into())
+}
+
+#[cfg(feature = "index")]
+fn indexed_entries(dir: &Path, walk: &Walk, verbose: bool) -> Option<Result<Vec<FileInfo>, Box<dyn Error>>> {
+    crate::index::entries(dir, walk, verbose)
+}
+
+// without the `index` feature every directory is walked
+#[cfg(not(feature = "index"))]
+fn indexed_entries(_dir: &Path, _walk: &Walk, _verbose: bool) -> Option<Result<Vec<FileInfo>, Box<dyn Error>>> {
+    None
+}
+
+// a file whose name says it is a zip or tar archive
+fn is_archive(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    path.is_file() && [".zip", ".tar", ".tar.gz", ".tgz"].iter().any(|suffix| name.ends_with(suffix))
+}
+
+// the roots of a FROM list, in the order given, each included once
+pub fn resolve_all_roots(state: &State, items: &[FromItem]) -> Result<Vec<Root>, Box<dyn Error>> {
+    let mut roots: Vec<Root> = Vec::new();
+    for item in items {
+        let resolved = match item {
+            // with --archives a plain path may name an archive too
+            FromItem::Path(path) if state.options.archives => resolve_matching(state, path, |p| p.is_dir() || is_archive(p))?
+                .into_iter()
+                .map(|path| if is_archive(&path) { Root::Archive(path) } else { Root::Directory(path) })
+                .collect(),
+            FromItem::Path(path) => resolve_roots(state, path)?.into_iter().map(Root::Directory).collect(),
+            FromItem::Archive(file) => {
+                let file = std::fs::canonicalize(state.path.join(expand_home(file)))
+                    .map_err(|e| format!("Cannot open archive '{}': {}", file, e))?;
+                vec![Root::Archive(file)]
+            }
+            FromItem::Stdin => vec![Root::Listed(state.path.clone(), stdin_paths()?)],
+        };
+        for root in resolved {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+    Ok(roots)
+}
+
+// the directories among `roots`, for statements that change or read files on disk
+fn directories(roots: Option<Vec<Root>>, statement: &str) -> Result<Option<Vec<Root>>, Box<dyn Error>> {
+    if let Some(Root::Archive(file)) = roots.iter().flatten().find(|root| matches!(root, Root::Archive(_))) {
+        return Err(format!("{} cannot read inside archives like '{}'", statement, file.display()).into());
+    }
+    Ok(roots)
+}
+
+// filter settings for the first root, or the session's directory
+pub fn settings_for(state: &State, roots: Option<&[Root]>) -> FilterSettings {
+    filter_settings(&state.options, roots.and_then(|roots| roots.first()).map_or(&state.path, Root::path))
+}
+
+// whether a statement can use the session's current listing as it is
+pub fn reads_listing(options: &Options, roots: Option<&[Root]>, depth: Option<usize>, exclude: &[String]) -> bool {
+    let walks = options.max_depth.is_some_and(|depth| depth > 1) || !options.exclude.is_empty();
+    roots.is_none() && depth.is_none() && exclude.is_empty() && !walks
+}
+
+// what a statement walks: the entries below `roots` (the session's directory
+// without a FROM), or the session's current listing when FROM, DEPTH and
+// EXCLUDE ask for nothing else. With `indexed`, directories that have an
+// index are read from it instead
+pub fn found<'a>(
+    state: &'a State,
+    roots: Option<&[Root]>,
+    depth: Option<usize>,
+    exclude: &[String],
+    indexed: bool,
+) -> Result<FoundEntries<'a>, Box<dyn Error>> {
+    if reads_listing(&state.options, roots, depth, exclude) {
+        return Ok(Box::new(listed(state).map(Ok).inspect(|_| stats::scanned())));
+    }
+    let walk = Walk::new(&state.options, depth, exclude)?;
+    let roots = roots.map_or_else(|| vec![Root::Directory(state.path.clone())], <[Root]>::to_vec);
+    let indexed = indexed && !state.options.no_index;
+    let dedupes = dedupes(&state.options, &roots);
+    let verbose = state.options.verbose;
+    let mut seen = HashSet::new();
+    let found = roots.into_iter().flat_map(move |root| {
+        // members of different archives may share a path
+        let archive = matches!(root, Root::Archive(_));
+        root.found(walk.clone(), indexed, verbose).map(move |found| (archive, found))
+    });
+    let found = found.filter_map(move |(archive, found)| match &found {
+        Ok(entry) if dedupes && !archive && !seen.insert(entry.path()) => None,
+        _ => Some(found),
+    });
+    Ok(Box::new(found.inspect(|_| stats::scanned()).map(|found| {
+        crate::cancel::check()?;
+        found
+    })))
+}
+
+// whether the same entry could be found under more than one of `roots`, as
+// when one FROM directory lies inside another or a glob matched both, so
+// found() must drop repeats; --no-dedupe keeps them
+fn dedupes(options: &Options, roots: &[Root]) -> bool {
+    let inside = |dir: &Path| roots.iter().any(|other| matches!(other, Root::Directory(o) if o != dir && o.starts_with(dir)));
+    !options.no_dedupe
+        && roots.iter().any(|root| match root {
+            Root::Directory(dir) => inside(dir),
+            Root::Listed(..) => roots.len() > 1,
+            Root::Archive(_) => false,
+        })
+}
+
+// the entries a statement reads, with their metadata, one at a time
+fn source<'a>(
+    state: &'a State,
+    roots: Option<&[Root]>,
+    depth: Option<usize>,
+    exclude: &[String],
+    indexed: bool,
+    detail: Detail,
+) -> Result<Entries<'a>, Box<dyn Error>> {
+    let options = &state.options;
+    Ok(Box::new(found(state, roots, depth, exclude, indexed)?.map(move |found| found?.stat(options, detail))))
+}
+
+pub fn execute_select(state: &State, command: &Command) -> Result<FileQuerySet, Box<dyn Error>> {
+    crate::pipeline::select(state, command).map(FileQuerySet::new)
+}
+
+#[cfg(feature = "hash")]
+type DuplicateGroups = (Vec<Vec<FileInfo>>, crate::hash::HashStats);
+
+// DUPLICATES: groups of matching files whose contents are identical
+#[cfg(feature = "hash")]
+pub fn execute_duplicates(state: &State, command: &Command) -> Result<DuplicateGroups, Box<dyn Error>> {
+    let Command::Duplicates { from_paths, depth, exclude, where_clause } = command else {
+        return Err("not a duplicates statement".into());
+    };
+    let roots = from_paths.as_deref().map(|paths| resolve_all_roots(state, paths)).transpose()?;
+    let roots = directories(roots, "DUPLICATES")?;
+    let settings = settings_for(state, roots.as_deref());
+    let mut files = Vec::new();
+    for file in source(state, roots.as_deref(), *depth, exclude, false, Detail::Owner)? {
+        let file = file?;
+        if where_clause.as_ref().map_or(Ok(true), |condition| filter::keep(&file, condition, &settings))? {
+            files.push(file);
+        }
+    }
+    let (groups, stats) = crate::hash::duplicate_groups(&files, crate::hash::default_workers());
+    let groups = groups.into_iter().map(|group| group.into_iter().cloned().collect()).collect();
+    Ok((groups, stats))
+}
+
+// EXISTS: whether any entry matches, stopping the walk at the first one
+pub fn execute_exists(state: &State, command: &Command) -> Result<bool, Box<dyn Error>> {
+    let Command::Exists { from_paths, depth, exclude, where_clause } = command else {
+        return Err("not an exists statement".into());
+    };
+    let select = Command::Select {
+        selection: SelectionType::Fields(Vec::new()),
+        distinct: false,
+        where_clause: where_clause.clone(),
+        order_by: None,
+        limit: Some(1),
+        offset: None,
+        from_paths: from_paths.clone(),
+        depth: *depth,
+        exclude: exclude.clone(),
+        ordering: None,
+        natural: false,
+    };
+    Ok(!crate::pipeline::select(state, &select)?.is_empty())
+}
+
+// select count(*): count matching entries without keeping any of them
+pub fn execute_count(state: &State, command: &Command) -> Result<usize, Box<dyn Error>> {
+    let Command::Select { where_clause, from_paths, depth, exclude, .. } = command else {
+        return Err("not a select statement".into());
+    };
+    let roots = from_paths.as_deref().map(|paths| resolve_all_roots(state, paths)).transpose()?;
+    // with overlapping roots, entries found twice are only dropped the slow way
+    let dedupes = roots.as_deref().is_some_and(|roots| dedupes(&state.options, roots));
+    if where_clause.is_none() && !dedupes {
+        if reads_listing(&state.options, roots.as_deref(), *depth, exclude) {
+            return Ok(state.listing.len());
+        }
+        // nothing to filter on, so there is no need to read any metadata
+        let walk = Walk::new(&state.options, *depth, exclude)?;
+        let mut progress = Progress::new();
+        let mut count = 0;
+        for root in roots.unwrap_or_else(|| vec![Root::Directory(state.path.clone())]) {
+            count += match root {
+                Root::Directory(dir) => match (!state.options.no_index).then(|| indexed_entries(&dir, &walk, state.options.verbose)).flatten() {
+                    Some(files) => files?.len(),
+                    None => {
+                        let mut found = 0;
+                        for entry in walk.clone().over(&dir) {
+                            crate::cancel::check()?;
+                            entry?;
+                            found += 1;
+                            progress.update(count + found, count + found);
+                        }
+                        found
+                    }
+                },
+                Root::Archive(file) => archive_members(&file)?.len(),
+                Root::Listed(_, paths) => paths.len(),
+            };
+        }
+        return Ok(count);
+    }
+    let settings = settings_for(state, roots.as_deref());
+    let mut progress = Progress::new();
+    let (mut scanned, mut count) = (0, 0);
+    for file in source(state, roots.as_deref(), *depth, exclude, true, estimate::detail(command, &state.options))? {
+        let file = file?;
+        if where_clause.as_ref().map_or(Ok(true), |condition| filter::keep(&file, condition, &settings))? {
+            count += 1;
+        }
+        scanned += 1;
+        progress.update(scanned, count);
+    }
+    Ok(count)
+}
+
+// fields an UPDATE may assign to
+const UPDATABLE_FIELDS: &[&str] = &["name", "modified"];
+
+fn set_modified(path: &Path, modified: DateTime<Utc>) -> std::io::Result<()> {
+    let file = if path.is_dir() {
+        std::fs::File::open(path)?
+    } else {
+        std::fs::File::options().write(true).open(path)?
+    };
+    file.set_modified(modified.into())
+}
+
+// rename and touch entries; with `dry_run` only describe what would change,
+// and with --emit-script print the changes as shell commands instead
+pub fn execute_update(state: &State, command: &Command, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let Command::Update { path, assignments, where_clause } = command else {
+        return Err("not an update statement".into());
+    };
+    let emit_script = state.options.emit_script;
+    let dry_run = dry_run || emit_script;
+    for (field, _) in assignments {
+        if !UPDATABLE_FIELDS.contains(&field.as_str()) {
+            return Err(format!(
+                "Cannot update field '{}' (updatable fields: {})",
+                field,
+                UPDATABLE_FIELDS.join(", ")
+            )
+            .into());
+        }
+    }
+    let roots: Vec<Root> = resolve_roots(state, path)?.into_iter().map(Root::Directory).collect();
+    let listing = source(state, Some(&roots), None, &[], false, Detail::Owner)?.collect::<Result<Vec<FileInfo>, Box<dyn Error>>>()?;
+    let mut query_set = FileQuerySet::new(listing);
+    if let Some(condition) = where_clause {
+        query_set = query_set.filter(condition, &settings_for(state, Some(&roots)))?;
+    }
+
+    if emit_script {
+        println!("{}", script::HEADER);
+    }
+    let mut claimed: Vec<PathBuf> = Vec::new();
+    let mut changed = 0;
+    let mut failed = 0;
+    let mut denied = Refused::default();
+    for file in query_set.files() {
+        // every new value is worked out before anything changes, so an entry
+        // whose SET fails is skipped whole and the rest still run
+        let values = assignments.iter().map(|(field, value)| {
+            let value = expr::evaluate(value, file)?;
+            match (field.as_str(), value) {
+                ("name", value) | (_, value @ FieldValue::Date(_)) => Ok((field, value)),
+                (_, other) => parse_date(&other.to_text()).map(|date| (field, FieldValue::Date(date))),
+            }
+        });
+        let values = match values.collect::<Result<Vec<_>, String>>() {
+            Ok(values) => values,
+            Err(e) => {
+                eprintln!("skipped {}: {}", file.path, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let mut current = PathBuf::from(&file.path);
+        let mut changes = Vec::new();
+        for (field, value) in values {
+            // the command that would redo a change the OS refused
+            let mut refused: Option<String> = None;
+            let result = match field.as_str() {
+                "name" => {
+                    let new_name = value.to_text();
+                    if new_name == file.name {
+                        continue;
+                    }
+                    if new_name.is_empty() || new_name.contains(['/', '\\']) || new_name == "." || new_name == ".." {
+                        Err(format!("'{}' is not a valid file name", new_name))
+                    } else {
+                        let target = current.with_file_name(&new_name);
+                        // on a case-insensitive filesystem a case-only rename finds
+                        // the entry itself under the new name
+                        let taken = target.exists() && !same_entry(&current, &target);
+                        if taken || claimed.contains(&target) {
+                            Err(format!("'{}' already exists", new_name))
+                        } else {
+                            let renamed = if dry_run { Ok(()) } else { std::fs::rename(&current, &target) };
+                            renamed.map_err(|e| refusal(&mut refused, e, script::mv(&current, &target))).map(|()| {
+                                let detail = if dry_run {
+                                    display::rename_diff(&file.name, &new_name)
+                                } else {
+                                    format!("{} -> {}", file.name, new_name)
+                                };
+                                changes.push(("would rename", "renamed", detail, script::mv(&current, &target)));
+                                claimed.push(target.clone());
+                                current = target;
+                            })
+                        }
+                    }
+                }
+                _ => match value {
+                    FieldValue::Date(modified) => {
+                        let touched = if dry_run { Ok(()) } else { set_modified(&current, modified) };
+                        touched.map_err(|e| refusal(&mut refused, e, script::touch(&current, modified))).map(|()| {
+                            let detail = format!("{} ({})", file.name, modified.format("%Y-%m-%d %H:%M:%S"));
+                            changes.push(("would touch", "touched", detail, script::touch(&current, modified)));
+                        })
+                    }
+                    other => Err(format!("'{}' is not a date", other.to_text())),
+                },
+            };
+            if let Err(e) = result {
+                match refused.take() {
+                    Some(command) => denied.push(&file.path, command, file.name.clone()),
+                    None => eprintln!("skipped {}: {}", file.path, e),
+                }
+                failed += 1;
+                break;
+            }
+        }
+        if !changes.is_empty() {
+            changed += 1;
+            for (planned, done, detail, command) in changes {
+                if emit_script {
+                    println!("{}", command);
+                } else {
+                    println!("{} {}", if dry_run { planned } else { done }, detail);
+                }
+            }
+        }
+    }
+    for name in denied.settle(state.options.sudo) {
+        println!("updated {} with sudo", name);
+        changed += 1;
+        failed -= 1;
+    }
+    let summary = if dry_run {
+        format!("{} entries would be updated (dry run)", changed)
+    } else {
+        format!("{} entries updated", changed)
+    };
+    let summary = if failed > 0 { format!("{}, {} skipped", summary, failed) } else { summary };
+    if emit_script {
+        println!("{}", script::comment(&summary));
+    } else {
+        println!("{}", summary);
+    }
+    Ok(())
+}
+
+// an error message for a failed rename or touch; permission failures also
+// keep the command that would redo the change, for `elevate`
+fn refusal(refused: &mut Option<String>, error: std::io::Error, command: String) -> String {
+    if elevate::is_denied(&error) {
+        *refused = Some(command);
+    }
+    error.to_string()
+}
+
+// space taken by each extension among `files`, largest first; directories
+// count with everything below them
+pub fn size_by_extension(files: &[FileInfo]) -> Vec<(String, usize, u64)> {
+    let mut totals: Vec<(String, usize, u64)> = Vec::new();
+    for file in files {
+        let ext = match file.file_type {
+            FileType::Directory => "(dir)".to_string(),
+            _ if file.extension().is_empty() => "(none)".to_string(),
+            _ => format!(".{}", file.extension().to_lowercase()),
+        };
+        let bytes = file.dir_size();
+        match totals.iter_mut().find(|(name, _, _)| *name == ext) {
+            Some((_, count, total)) => {
+                *count += 1;
+                *total += bytes;
+            }
+            None => totals.push((ext, 1, bytes)),
+        }
+    }
+    totals.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+// entries listed before DELETE asks for confirmation
+const CONFIRM_SHOWN: usize = 20;
+
+#[cfg(feature = "trash")]
+fn move_to_trash(path: &Path) -> Result<(), Box<dyn Error>> {
+    trash::delete(path).map_err(Into::into)
+}
+
+#[cfg(not(feature = "trash"))]
+fn move_to_trash(_path: &Path) -> Result<(), Box<dyn Error>> {
+    Err(crate::features::require("trash").unwrap_err().into())
+}
+
+// what a DELETE removed, or with --dry-run would remove
+#[derive(Debug)]
+pub struct Deleted {
+    pub to_trash: bool,
+    // extension, entries and bytes, as size_by_extension() gives them
+    pub totals: Vec<(String, usize, u64)>,
+}
+
+// remove matching entries; with `dry_run` only list what would go. Either
+// way the summary breaks the space freed down by extension
+pub fn execute_delete(state: &State, command: &Command, dry_run: bool) -> Result<Deleted, Box<dyn Error>> {
+    let Command::DeleteFiles { permanent, from_paths, depth, exclude, where_clause } = command else {
+        return Err("not a delete statement".into());
+    };
+    let Some(condition) = where_clause else {
+        return Err("DELETE needs a WHERE clause; without one it would remove every entry".into());
+    };
+    let emit_script = state.options.emit_script;
+    let dry_run = dry_run || emit_script;
+    let to_trash = state.options.trash && !permanent;
+    if to_trash && !dry_run {
+        crate::features::require("trash")?;
+    }
+    let roots = from_paths.as_deref().map(|paths| resolve_all_roots(state, paths)).transpose()?;
+    let roots = directories(roots, "DELETE")?;
+    let settings = settings_for(state, roots.as_deref());
+    let mut doomed: Vec<FileInfo> = Vec::new();
+    let mut doomed_dirs: HashSet<PathBuf> = HashSet::new();
+    for file in source(state, roots.as_deref(), *depth, exclude, false, Detail::Owner)? {
+        let file = file?;
+        // anything inside a directory that is already going goes with it
+        let covered = Path::new(&file.path).ancestors().any(|dir| doomed_dirs.contains(dir));
+        if !covered && filter::keep(&file, condition, &settings)? {
+            if matches!(file.file_type, FileType::Directory) {
+                doomed_dirs.insert(PathBuf::from(&file.path));
+            }
+            doomed.push(file);
+        }
+    }
+
+    let is_full_dir = |file: &FileInfo| {
+        matches!(file.file_type, FileType::Directory)
+            && std::fs::read_dir(&file.path).is_ok_and(|mut entries| entries.next().is_some())
+    };
+    let full_dirs = doomed.iter().filter(|file| is_full_dir(file)).count();
+    if !dry_run && !state.options.yes && !doomed.is_empty() {
+        if !std::io::stdin().is_terminal() {
+            return Err("refusing to delete without a terminal to confirm on; pass --yes to skip the confirmation".into());
+        }
+        println!("{}", display::doomed(&doomed, CONFIRM_SHOWN)?);
+        let action = if to_trash { "move to the trash" } else { "delete" };
+        let question = match full_dirs {
+            0 => format!("{} these {} entries?", action, doomed.len()),
+            _ => format!("{} these {} entries, including {} non-empty directories and everything in them?", action, doomed.len(), full_dirs),
+        };
+        match crate::shell::ask(&question) {
+            Some(true) => {}
+            Some(false) => return Err("cancelled".into()),
+            None => return Err("cancelled".into()),
+        }
+    }
+
+    if emit_script {
+        println!("{}", script::HEADER);
+        if to_trash {
+            println!("{}", script::comment("--trash does not carry over to scripts: these commands delete for good"));
+        }
+    }
+    let mut removed = Vec::new();
+    let mut failed = 0;
+    let mut refused = Refused::default();
+    for file in doomed {
+        let path = Path::new(&file.path);
+        let is_dir = matches!(file.file_type, FileType::Directory);
+        if emit_script {
+            println!("{}", script::rm(path, is_dir));
+        } else if dry_run {
+            println!("{} {}", if to_trash { "would move to the trash" } else { "would delete" }, file.path);
+        } else if to_trash {
+            file.dir_size();
+            if let Err(e) = move_to_trash(path) {
+                eprintln!("skipped {}: {}", file.path, e);
+                failed += 1;
+                continue;
+            }
+        } else {
+            // measured first: afterwards there is nothing left to measure
+            file.dir_size();
+            let result = if is_dir { std::fs::remove_dir_all(path) } else { std::fs::remove_file(path) };
+            match result {
+                Err(e) if elevate::is_denied(&e) => {
+                    let command = script::rm(path, is_dir);
+                    refused.push(&file.path.clone(), command, file);
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("skipped {}: {}", file.path, e);
+                    failed += 1;
+                    continue;
+                }
+                Ok(()) => {}
+            }
+        }
+        removed.push(file);
+    }
+    let denied = refused.len();
+    let elevated = refused.settle(state.options.sudo);
+    failed += denied - elevated.len();
+    removed.extend(elevated);
+
+    let totals = size_by_extension(&removed);
+    let freed: u64 = totals.iter().map(|(_, _, bytes)| bytes).sum();
+    let size = files::human_readable_size(freed);
+    let mut summary = match (dry_run, to_trash) {
+        (true, true) => format!("{} entries ({}) would be moved to the trash (dry run)", removed.len(), size),
+        (true, false) => format!("{} entries would be deleted, freeing {} (dry run)", removed.len(), size),
+        (false, true) => format!("{} entries ({}) moved to the trash", removed.len(), size),
+        (false, false) => format!("{} entries deleted, {} freed", removed.len(), size),
+    };
+    if failed > 0 {
+        summary.push_str(&format!(", {} skipped", failed));
+    }
+    if emit_script {
+        println!("{}", script::comment(&summary));
+    } else {
+        if !totals.is_empty() {
+            println!("{}", display::size_breakdown(&totals));
+        }
+        println!("{}", summary);
+    }
+    Ok(Deleted { to_trash, totals })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_roots() {
+        let dir = crate::testing::TempDir::new("glob");
+        for sub in ["a/src", "b/src", "b/docs", "c"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join("a/src/main.rs"), "").unwrap();
+        let state = State { listing: Vec::new(), path: dir.to_path_buf(), previous: Vec::new(), options: Options::default() };
+
+        let mut roots = resolve_roots(&state, "*/src").unwrap();
+        roots.sort();
+        assert_eq!(roots, [dir.join("a/src"), dir.join("b/src")]);
+        // `**` searches every level below the session's directory
+        let roots = resolve_roots(&state, "**/src").unwrap();
+        assert_eq!(roots.len(), 2);
+        assert!(resolve_roots(&state, "*/nothing").is_err());
+        assert_eq!(resolve_roots(&state, "c").unwrap(), [dir.join("c")]);
+        // archives are roots only with --archives
+        std::fs::write(dir.join("c/old.zip"), "").unwrap();
+        assert!(resolve_roots(&state, "c/*").is_err());
+        let state = State { options: Options { archives: true, ..Default::default() }, ..state };
+        let roots = resolve_all_roots(&state, &[FromItem::Path("c/*".to_string()), FromItem::Path("a".to_string())]).unwrap();
+        assert_eq!(roots, [Root::Archive(dir.join("c/old.zip")), Root::Directory(dir.join("a"))]);
+    }
+
+    #[test]
+    fn test_overlapping_roots() {
+        let dir = crate::testing::TempDir::new("overlap");
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::write(dir.join("a/b/x.rs"), "").unwrap();
+        let mut state = State { listing: Vec::new(), path: dir.to_path_buf(), previous: Vec::new(), options: Options::default() };
+        let roots = resolve_all_roots(&state, &[FromItem::Path("a".to_string()), FromItem::Path("a/b".to_string())]).unwrap();
+        let paths = |state: &State| -> Vec<PathBuf> {
+            found(state, Some(&roots), Some(2), &[], false).unwrap().map(|found| found.unwrap().path()).collect()
+        };
+        // a/b/x.rs is two levels below a and one below a/b, but listed once
+        assert_eq!(paths(&state), [dir.join("a/b"), dir.join("a/b/x.rs")]);
+        state.options.no_dedupe = true;
+        assert_eq!(paths(&state).len(), 3);
+    }
+
+    #[test]
+    fn test_exclude_prunes_subtrees() {
+        let dir = crate::testing::TempDir::new("exclude");
+        for sub in ["node_modules/pkg", "src/vendor"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for file in ["node_modules/pkg/index.js", "src/app.js", "src/app.min.js", "src/vendor/lib.js"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let options = Options { exclude: vec!["node_modules".to_string()], ..Options::default() };
+        let walk = Walk::new(&options, Some(5), &["*.min.js".to_string(), "src/vendor".to_string()]).unwrap();
+        let mut names: Vec<String> =
+            walk.over(&dir).map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        assert_eq!(names, ["app.js", "src"]);
+        assert!(Walk::new(&options, Some(0), &[]).is_err());
+        // the session's listing stays one level deep and excludes nothing
+        let options = Options { max_depth: Some(3), ..options };
+        let mut names: Vec<String> = list_dir_contents(&dir, &options).unwrap().into_iter().map(|file| file.name).collect();
+        names.sort();
+        assert_eq!(names, ["node_modules", "src"]);
+        assert!(!reads_listing(&options, None, None, &[]));
+    }
+
+    #[test]
+    fn test_size_by_extension() {
+        let dir = crate::testing::TempDir::new("delete");
+        std::fs::create_dir_all(dir.join("cache")).unwrap();
+        for (name, contents) in [("a.log", "12345"), ("b.LOG", "123"), ("notes", "1"), ("cache/x", "1234567890")] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        let files = list_dir_contents(&dir, &Options::default()).unwrap();
+        assert_eq!(
+            size_by_extension(&files),
+            [
+                ("(dir)".to_string(), 1, 10),
+                (".log".to_string(), 2, 8),
+                ("(none)".to_string(), 1, 1),
+            ]
+        );
+    }
+
+    // the names in `dir`, sorted
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> =
+            std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    fn update(dir: &Path, query: &str, dry_run: bool) {
+        let state = State::at(dir, Options::default()).unwrap();
+        execute_update(&state, &crate::parser::parse(query).unwrap().1[0], dry_run).unwrap();
+    }
+
+    #[test]
+    fn test_same_entry() {
+        let dir = crate::testing::TempDir::new("same-entry");
+        // alike in size and time, but two files
+        for name in ["a.txt", "b.txt"] {
+            std::fs::write(dir.join(name), "same").unwrap();
+            set_modified(&dir.join(name), parse_date("2020-01-02 03:04:05").unwrap()).unwrap();
+        }
+        assert!(!same_entry(&dir.join("a.txt"), &dir.join("b.txt")));
+        assert!(same_entry(&dir.join("a.txt"), &dir.join(".").join("a.txt")));
+        assert!(!same_entry(&dir.join("a.txt"), &dir.join("missing.txt")));
+    }
+
+    #[test]
+    fn test_update_renames() {
+        let dir = crate::testing::TempDir::new("update-rename");
+        for (name, contents) in [("a.txt", "a"), ("b.txt", "b"), ("x.md", "x"), ("y.md", "y"), ("c.txt", "c")] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        // a rename onto an existing file skips that entry and overwrites nothing
+        update(&dir, "update . set name = 'b.txt' where name = 'a.txt'", false);
+        assert_eq!(std::fs::read_to_string(dir.join("a.txt")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "b");
+        // of two entries renamed to the same name, only the first gets it
+        update(&dir, "update . set name = 'same.md' where ext = 'md'", false);
+        let left = names(&dir);
+        assert!(left.contains(&"same.md".to_string()));
+        assert_eq!(left.iter().filter(|name| ["x.md", "y.md"].contains(&name.as_str())).count(), 1, "{:?}", left);
+        // a change of case alone is a rename like any other
+        update(&dir, "update . set name = upper(name) where name = 'c.txt'", false);
+        assert!(names(&dir).contains(&"C.TXT".to_string()));
+        assert!(!names(&dir).contains(&"c.txt".to_string()));
+        assert_eq!(std::fs::read_to_string(dir.join("C.TXT")).unwrap(), "c");
+    }
+
+    #[test]
+    fn test_update_touch_and_dry_run() {
+        let dir = crate::testing::TempDir::new("update-touch");
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        let modified = || DateTime::<Utc>::from(std::fs::metadata(dir.join("notes.txt")).unwrap().modified().unwrap());
+        update(&dir, "update . set modified = '2020-01-02 03:04:05' where name = 'notes.txt'", false);
+        assert_eq!(modified().to_rfc3339(), "2020-01-02T03:04:05+00:00");
+
+        // --dry-run changes neither names nor times
+        let before = (names(&dir), modified());
+        update(&dir, "update . set name = 'renamed.txt', modified = '2021-05-06' where name = 'notes.txt'", true);
+        assert_eq!((names(&dir), modified()), before);
+    }
+
+    #[test]
+    fn test_update_skips_entries_whose_values_fail() {
+        let dir = crate::testing::TempDir::new("update-fails");
+        for name in ["2020-01-02", "notes"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        // `notes` is no date: it is skipped, neither renamed nor touched,
+        // and the other entry is still updated
+        update(&dir, "update . set name = upper(name), modified = name", false);
+        assert_eq!(names(&dir), ["2020-01-02", "notes"]);
+        let modified = DateTime::<Utc>::from(std::fs::metadata(dir.join("2020-01-02")).unwrap().modified().unwrap());
+        assert_eq!(modified.to_rfc3339(), "2020-01-02T00:00:00+00:00");
+    }
+
+    fn delete(dir: &Path, query: &str, options: Options, dry_run: bool) -> Result<Deleted, Box<dyn Error>> {
+        let state = State::at(dir, options).unwrap();
+        execute_delete(&state, &crate::parser::parse(query).unwrap().1[0], dry_run)
+    }
+
+    #[test]
+    fn test_delete_removes_matches_once() {
+        let dir = crate::testing::TempDir::new("delete-matches");
+        std::fs::create_dir_all(dir.join("cache")).unwrap();
+        for (name, contents) in [("a.log", "12345"), ("b.log", "123"), ("keep.txt", "1"), ("cache/x.log", "1234567890"), ("cache/y.txt", "12")] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        let yes = Options { yes: true, ..Options::default() };
+        // cache/x.log matches too, but goes with cache rather than on its own
+        let deleted = delete(&dir, "delete from . depth 2 where ext = 'log' or name = 'cache'", yes, false).unwrap();
+        assert_eq!(names(&dir), ["keep.txt"]);
+        assert!(!deleted.to_trash);
+        assert_eq!(deleted.totals, [("(dir)".to_string(), 1, 12), (".log".to_string(), 2, 8)]);
+    }
+
+    #[test]
+    fn test_delete_trash_and_confirmation() {
+        let dir = crate::testing::TempDir::new("delete-trash");
+        std::fs::write(dir.join("a.tmp"), "").unwrap();
+        let trash = Options { trash: true, yes: true, ..Options::default() };
+        let deleted = delete(&dir, "delete from . where ext = 'tmp'", trash.clone(), true).unwrap();
+        assert!(deleted.to_trash);
+        assert_eq!(names(&dir), ["a.tmp"]);
+        // PERMANENT deletes for good even with --trash
+        let deleted = delete(&dir, "delete permanent from . where ext = 'tmp'", trash, false).unwrap();
+        assert!(!deleted.to_trash);
+        assert!(names(&dir).is_empty());
+
+        // without --yes there must be a terminal to ask on; under a test
+        // runner attached to one this would prompt, so it is only checked without
+        std::fs::write(dir.join("b.tmp"), "").unwrap();
+        if !std::io::stdin().is_terminal() {
+            let error = delete(&dir, "delete from . where ext = 'tmp'", Options::default(), false).unwrap_err();
+            assert!(error.to_string().contains("--yes"), "{}", error);
+        }
+        assert_eq!(names(&dir), ["b.tmp"]);
+    }
+
+    #[test]
+    fn test_split_paths() {
+        assert_eq!(split_paths("./a.log\nsrc/b c.rs\r\n\n"), [PathBuf::from("./a.log"), PathBuf::from("src/b c.rs")]);
+        // with NULs, newlines belong to the names
+        assert_eq!(split_paths("a\nb\0c\0"), [PathBuf::from("a\nb"), PathBuf::from("c")]);
+        assert!(split_paths("").is_empty());
+    }
+}
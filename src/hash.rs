@@ -0,0 +1,188 @@
+// Content hashing for duplicate detection.
+//
+// Reading every byte is by far the slowest thing lsql does, so candidates go
+// through stages: entries are grouped by size first (files of different sizes
+// cannot have the same contents), and only files that share a size with
+// another one are read, spread over a pool of worker threads.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::files::{human_readable_size, FileInfo, FileType};
+
+// what a hashing run did, for reporting throughput
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HashStats {
+    pub hashed: usize,
+    // files never read because no other file had the same size
+    pub skipped: usize,
+    pub failed: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for HashStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        let rate = if seconds > 0.0 { (self.bytes as f64 / seconds) as u64 } else { self.bytes };
+        write!(
+            f,
+            "hashed {} files ({}) in {:.2}s, {}/s; {} skipped by size",
+            self.hashed,
+            human_readable_size(self.bytes),
+            seconds,
+            human_readable_size(rate),
+            self.skipped
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} unreadable", self.failed)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn default_workers() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// hex encoded sha256 of a file's contents
+pub fn sha256(path: &Path) -> std::io::Result<String> {
+    digest::<Sha256>(path)
+}
+
+// hex encoded md5 of a file's contents, for comparing with older tools
+pub fn md5(path: &Path) -> std::io::Result<String> {
+    digest::<Md5>(path)
+}
+
+fn digest<D: Digest>(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// hash every path on `workers` threads; results line up with `paths`
+pub fn hash_all(paths: &[PathBuf], workers: usize) -> Vec<Option<String>> {
+    let next = AtomicUsize::new(0);
+    let mut results = vec![None; paths.len()];
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.clamp(1, paths.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else { break };
+                        crate::nice::pace();
+                        done.push((index, sha256(path).ok()));
+                    }
+                    done
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (index, hash) in handle.join().expect("hash worker panicked") {
+                results[index] = hash;
+            }
+        }
+    });
+    results
+}
+
+// groups of two or more regular files with identical contents, each group
+// in the order the files were given
+pub fn duplicate_groups(files: &[FileInfo], workers: usize) -> (Vec<Vec<&FileInfo>>, HashStats) {
+    let started = Instant::now();
+    let regular: Vec<&FileInfo> = files
+        .iter()
+        .filter(|file| matches!(file.file_type, FileType::File))
+        .collect();
+
+    let mut by_size: HashMap<u64, usize> = HashMap::new();
+    for file in &regular {
+        *by_size.entry(file.size).or_default() += 1;
+    }
+    let (candidates, unique): (Vec<&FileInfo>, Vec<&FileInfo>) =
+        regular.into_iter().partition(|file| by_size[&file.size] > 1);
+
+    // only files the metadata cache has no hash for are read
+    let mut hashes: Vec<Option<String>> = candidates.iter().map(|file| crate::cache::cached(file, "hash")).collect();
+    let unknown: Vec<usize> = (0..candidates.len()).filter(|&index| hashes[index].is_none()).collect();
+    let paths: Vec<PathBuf> = unknown.iter().map(|&index| PathBuf::from(&candidates[index].path)).collect();
+    for (index, hash) in unknown.into_iter().zip(hash_all(&paths, workers)) {
+        if let Some(hash) = &hash {
+            crate::cache::store(candidates[index], "hash", hash);
+        }
+        hashes[index] = hash;
+    }
+
+    let mut stats = HashStats { skipped: unique.len(), ..Default::default() };
+    let mut groups: Vec<Vec<&FileInfo>> = Vec::new();
+    let mut group_of: HashMap<(u64, String), usize> = HashMap::new();
+    for (file, hash) in candidates.into_iter().zip(hashes) {
+        let Some(hash) = hash else {
+            stats.failed += 1;
+            continue;
+        };
+        stats.hashed += 1;
+        stats.bytes += file.size;
+        match group_of.get(&(file.size, hash.clone())) {
+            Some(&index) => groups[index].push(file),
+            None => {
+                group_of.insert((file.size, hash), groups.len());
+                groups.push(vec![file]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    stats.elapsed = started.elapsed();
+    (groups, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_groups_hash_only_same_size_files() {
+        let dir = crate::testing::TempDir::new("hash");
+        for (name, contents) in [("a", "same"), ("b", "same"), ("c", "diff"), ("d", "unique size")] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        let files = crate::fs::list_dir_contents(&dir, &Default::default()).unwrap();
+
+        let (groups, stats) = duplicate_groups(&files, 4);
+        let mut names: Vec<&str> = groups[0].iter().map(|file| file.name.as_str()).collect();
+        names.sort();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!((stats.hashed, stats.skipped, stats.bytes), (3, 1, 12));
+    }
+
+    #[test]
+    fn test_digests() {
+        let dir = crate::testing::TempDir::new("sha");
+        let path = dir.join("abc");
+        std::fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            sha256(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(md5(&path).unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+    }
+}
@@ -0,0 +1,123 @@
+// `help` topics, generated from the same registries the parser, the
+// evaluator and the field lookup use, so they list exactly what works
+
+use crate::expr::FUNCTIONS;
+use crate::files::{describe_field, FIELDS};
+use crate::parser::{OPERATORS, STATEMENTS};
+use crate::plugins;
+
+const TOPICS: &[&str] = &["fields", "operators", "functions"];
+
+fn table(rows: &[(String, String)]) -> String {
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(name, description)| format!("  {:<width$}  {}\n", name, description, width = width))
+        .collect()
+}
+
+fn overview() -> String {
+    let statements: Vec<(String, String)> = STATEMENTS
+        .iter()
+        .map(|s| (s.name.to_uppercase(), s.description.to_string()))
+        .collect();
+    let topics: Vec<&str> = TOPICS.iter().copied().chain(STATEMENTS.iter().map(|s| s.name)).collect();
+    format!("statements:\n{}\nhelp topics: {}\n", table(&statements), topics.join(", "))
+}
+
+fn fields() -> String {
+    let builtin: Vec<(String, String)> = FIELDS
+        .iter()
+        .map(|name| (name.to_string(), describe_field(name).unwrap_or_default().to_string()))
+        .collect();
+    let mut out = format!("fields:\n{}", table(&builtin));
+    let plugins: Vec<(String, String)> = plugins::providers()
+        .iter()
+        .map(|p| (p.name().to_string(), p.description().to_string()))
+        .collect();
+    if !plugins.is_empty() {
+        out.push_str(&format!("\nplugin fields:\n{}", table(&plugins)));
+    }
+    out
+}
+
+pub fn render(topic: Option<&str>) -> Result<String, String> {
+    let Some(topic) = topic else {
+        return Ok(overview());
+    };
+    let pairs = |list: &[(&str, &str)]| -> Vec<(String, String)> {
+        list.iter().map(|(name, text)| (name.to_string(), text.to_string())).collect()
+    };
+    match topic.to_lowercase().as_str() {
+        "fields" => Ok(fields()),
+        "operators" => Ok(format!(
+            "operators:\n{}\ncombine conditions with AND, OR, NOT and parentheses;\ncolumns may use + - * / for arithmetic\n",
+            table(&pairs(OPERATORS))
+        )),
+        "functions" => Ok(format!("functions:\n{}", table(&pairs(FUNCTIONS)))),
+        name => match STATEMENTS.iter().find(|s| s.name == name) {
+            Some(s) => Ok(format!("{}\n\n  {}\n\nexample: {}\n", s.syntax, s.description, s.example)),
+            None => {
+                let topics: Vec<&str> = TOPICS.iter().copied().chain(STATEMENTS.iter().map(|s| s.name)).collect();
+                Err(format!("No help for '{}' (topics: {})", name, topics.join(", ")))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the registries must describe what actually works
+
+    #[test]
+    fn test_every_field_is_described() {
+        for field in FIELDS {
+            assert!(describe_field(field).is_some(), "no description for {}", field);
+        }
+    }
+
+    #[test]
+    fn test_statement_examples_parse() {
+        for statement in STATEMENTS {
+            match crate::parser::parse(statement.example) {
+                Ok(("", commands)) => assert_eq!(commands.len(), 1),
+                other => panic!("example for {} does not parse: {:?}", statement.name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_operators_parse() {
+        for (operator, _) in OPERATORS {
+            let query = format!("select * where name {} 'a'", operator);
+            assert!(matches!(crate::parser::parse(&query), Ok(("", _))), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_functions_evaluate() {
+        let file = crate::fs::list_dir_contents(std::path::Path::new(env!("CARGO_MANIFEST_DIR")), &Default::default())
+            .unwrap()
+            .remove(0);
+        for (signature, _) in FUNCTIONS {
+            let call = signature.replace("text", "name").replace("from", "'a'").replace("to", "'b'").replace(", ...", "");
+            let Ok((_, mut commands)) = crate::parser::parse(&format!("select {}", call)) else {
+                panic!("{} does not parse", call);
+            };
+            let crate::parser::Command::Select { selection: crate::parser::SelectionType::Fields(items), .. } = commands.remove(0) else {
+                panic!("{} is not a column", call);
+            };
+            assert!(crate::expr::evaluate(&items[0].expression, &file).is_ok(), "{}", call);
+        }
+    }
+
+    #[test]
+    fn test_topics() {
+        assert!(render(None).unwrap().contains("SELECT"));
+        assert!(render(Some("fields")).unwrap().contains("dir_size"));
+        assert!(render(Some("select")).unwrap().contains("example:"));
+        assert!(render(Some("delete")).unwrap().contains("DELETE"));
+        assert!(render(Some("truncate")).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+// `lsql index build DIR`: the metadata of a whole tree saved under
+// `cache_dir()/index`, so SELECT over that tree, or any directory inside it,
+// reads one file instead of walking. Nothing keeps an index current; it shows
+// the tree as it was when built (EXPLAIN and --verbose say when), `--no-index`
+// walks instead, and building again refreshes it. An index is passed over,
+// with a warning, once a directory between it and the one queried has
+// changed since it was built, since it would list entries that moved or are
+// gone.
+
+use std::error::Error;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use crate::cache;
+use crate::cli::Options;
+use crate::files::{self, FileInfo};
+use crate::fs::{self, Walk};
+use crate::spill;
+
+// bumped whenever the entry encoding changes, so older indexes are ignored
+const HEADER: &str = "lsql-index 4";
+
+fn index_dir() -> Option<PathBuf> {
+    cache::cache_dir().map(|dir| dir.join("index"))
+}
+
+// one file per indexed directory, named after a hash of its path (FNV-1a,
+// which unlike std's hasher stays the same from one build to the next)
+fn index_file(root: &Path) -> Option<PathBuf> {
+    let hash = root
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3));
+    index_dir().map(|dir| dir.join(format!("{:016x}", hash)))
+}
+
+// what an index file says about itself: the directory and when it was built
+fn header(line: &str) -> Option<(PathBuf, DateTime<Utc>)> {
+    let mut fields = line.split('\t');
+    if fields.next()? != HEADER {
+        return None;
+    }
+    let root = PathBuf::from(spill::unescape(fields.next()?));
+    let built = DateTime::from_timestamp_nanos(fields.next()?.parse().ok()?);
+    Some((root, built))
+}
+
+pub fn build(dir: &Path, options: &Options) -> Result<String, Box<dyn Error>> {
+    let root = std::fs::canonicalize(dir).map_err(|e| format!("Cannot index '{}': {}", dir.display(), e))?;
+    let path = index_file(&root).ok_or("no cache directory to keep the index in")?;
+    std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+    let started = std::time::Instant::now();
+    let built = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    // written aside and renamed, so queries never see half an index
+    let partial = path.with_extension("partial");
+    let mut out = BufWriter::new(std::fs::File::create(&partial)?);
+    writeln!(out, "{}\t{}\t{}", HEADER, spill::escape(&root.display().to_string()), built)?;
+    let (mut indexed, mut skipped) = (0, 0);
+    // the whole tree, whatever --max-depth or --exclude say; queries apply their own
+    let walk = Walk::new(&Options::default(), Some(usize::MAX), &[])?;
+    for file in fs::entries(&root, walk, options) {
+        match file {
+            Ok(file) => {
+                writeln!(out, "{}", spill::encode(&file))?;
+                indexed += 1;
+            }
+            Err(_) => skipped += 1,
+        }
+    }
+    out.flush()?;
+    drop(out);
+    std::fs::rename(partial, &path)?;
+    let mut summary = format!("indexed {} entries under {} in {:.1}s", indexed, root.display(), started.elapsed().as_secs_f64());
+    if skipped > 0 {
+        summary.push_str(&format!(" ({} could not be read)", skipped));
+    }
+    Ok(summary)
+}
+
+// the index covering `dir`, its own or one of a directory above it: the
+// index file, the directory indexed and when
+pub fn covering(dir: &Path) -> Option<(PathBuf, PathBuf, DateTime<Utc>)> {
+    dir.ancestors().find_map(|ancestor| {
+        let path = index_file(ancestor)?;
+        let line = BufReader::new(std::fs::File::open(&path).ok()?).lines().next()?.ok()?;
+        let (root, built) = header(&line)?;
+        (root == ancestor).then_some((path, root, built))
+    })
+}
+
+// whether `dir`, or a directory above it up to the indexed `root`, had
+// entries added, removed or renamed after `built`; one that cannot be read
+// counts as changed
+pub fn changed_since(dir: &Path, root: &Path, built: DateTime<Utc>) -> bool {
+    dir.ancestors().take_while(|ancestor| ancestor.starts_with(root)).any(|ancestor| {
+        let modified = std::fs::metadata(ancestor).and_then(|metadata| metadata.modified());
+        modified.map_or(true, |modified| DateTime::<Utc>::from(modified) > built)
+    })
+}
+
+// the entries `walk` would find below `dir`, from the index covering it;
+// None when there is no index to read, or it is out of date
+pub fn entries(dir: &Path, walk: &Walk, verbose: bool) -> Option<Result<Vec<FileInfo>, Box<dyn Error>>> {
+    let (path, root, built) = covering(dir)?;
+    if changed_since(dir, &root, built) {
+        eprintln!(
+            "warning: {} changed after its index was built {}; walking instead (`lsql index build {}` refreshes it)",
+            dir.display(),
+            files::relative_time(built),
+            root.display()
+        );
+        return None;
+    }
+    if verbose {
+        eprintln!("reading {} from the index built {}", dir.display(), files::relative_time(built));
+    }
+    let read = || -> Result<Vec<FileInfo>, Box<dyn Error>> {
+        let root = dir.display().to_string();
+        let mut found = Vec::new();
+        for line in BufReader::new(std::fs::File::open(&path)?).lines().skip(1) {
+            crate::cancel::check()?;
+            let mut file = spill::decode(&line?)?;
+            if walk.reaches(Path::new(&file.path), dir) {
+                file.root = root.clone();
+                found.push(file);
+            }
+        }
+        Ok(found)
+    };
+    Some(read())
+}
+
+// `lsql index list`: each indexed directory, its size and age
+pub fn list() -> Result<String, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    let entries = index_dir().and_then(|dir| std::fs::read_dir(dir).ok());
+    for entry in entries.into_iter().flatten() {
+        let path = entry?.path();
+        let Ok(file) = std::fs::File::open(&path) else { continue };
+        let mut lines = BufReader::new(file).lines();
+        let Some((root, built)) = lines.next().and_then(Result::ok).as_deref().and_then(header) else {
+            continue;
+        };
+        rows.push(format!("{}  {} entries, built {}", root.display(), lines.count(), files::relative_time(built)));
+    }
+    if rows.is_empty() {
+        return Ok("no directories are indexed\n".to_string());
+    }
+    rows.sort();
+    Ok(rows.iter().map(|row| format!("{}\n", row)).collect())
+}
+
+// `lsql index drop DIR`
+pub fn remove(dir: &Path) -> Result<String, Box<dyn Error>> {
+    let root = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    match index_file(&root).filter(|path| path.exists()) {
+        Some(path) => {
+            std::fs::remove_file(path)?;
+            Ok(format!("dropped the index of {}", root.display()))
+        }
+        None => Err(format!("{} is not indexed", root.display()).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let line = format!("{}\t/home/me/my\\tprojects\t1700000000000000000", HEADER);
+        let (root, built) = header(&line).unwrap();
+        assert_eq!(root, PathBuf::from("/home/me/my\tprojects"));
+        assert_eq!(built.timestamp(), 1_700_000_000);
+        assert!(header("lsql-index 0\t/\t0").is_none());
+        assert_ne!(index_file(Path::new("/a")), index_file(Path::new("/b")));
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let dir = crate::testing::TempDir::new("index-stale");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let built = Utc::now() + chrono::Duration::seconds(60);
+        assert!(!changed_since(&dir.join("src"), &dir, built));
+        let before = Utc::now() - chrono::Duration::seconds(60);
+        assert!(changed_since(&dir.join("src"), &dir, before));
+        assert!(changed_since(&dir.join("gone"), &dir, built));
+    }
+}
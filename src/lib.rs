@@ -0,0 +1,628 @@
+// lsql-core: the parser, the filesystem walker and the statements behind the
+// lsql binary, usable from other programs. `parse_query` turns text into
+// statements and `execute_query` runs a SELECT against a `State`, returning
+// the matching entries instead of printing them; `execute_into` streams them
+// into an `OutputSink` instead.
+#[cfg(feature = "archive")]
+pub mod archive;
+pub mod cache;
+pub mod cancel;
+pub mod cli;
+pub mod config;
+pub mod display;
+pub mod elevate;
+pub mod error;
+pub mod estimate;
+pub mod export;
+pub mod explain;
+pub mod expr;
+pub mod features;
+pub mod files;
+pub mod filter;
+pub mod fixture;
+pub mod fs;
+#[cfg(feature = "hash")]
+pub mod hash;
+pub mod help;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod nice;
+pub mod normalize;
+pub mod pager;
+pub mod parser;
+pub mod pipeline;
+pub mod plugins;
+pub mod progress;
+pub mod script;
+pub mod shell;
+pub mod spill;
+pub mod stats;
+pub mod wizard;
+#[cfg(test)]
+mod testing;
+use std::{error::Error, path::{Path, PathBuf}};
+use std::sync::atomic::{AtomicBool, Ordering};
+use cli::{Options, OutputFormat};
+use files::FileInfo;
+use colored::Colorize;
+
+pub use display::{OutputSink, Summary};
+pub use error::LsqlError;
+pub use normalize::{canonical, fingerprint};
+pub use parser::{Command, ConditionNode, Expression, SelectionType, WhereClause};
+
+// an entry found on disk or in an archive, with the fields queries read
+pub type FSEntry = FileInfo;
+
+
+pub struct State {
+    listing: Vec<walkdir::DirEntry>,
+    path: PathBuf,
+    // the directories CD left, most recent last, for BACK
+    previous: Vec<PathBuf>,
+    options: Options,
+}
+
+impl State {
+    pub fn new(options: Options) -> Result<Self, Box<dyn Error>> {
+        Self::at(&std::env::current_dir()?, options)
+    }
+
+    // a session whose current directory is `path`, for embedding
+    pub fn at(path: &Path, options: Options) -> Result<Self, Box<dyn Error>> {
+        let abs_path = std::fs::canonicalize(path)?;
+        let listing = fs::listing(&abs_path)?;
+        Ok(State {
+            listing,
+            path: abs_path,
+            previous: Vec::new(),
+            options,
+        })
+    }
+
+    pub fn set_path(&self, path: &Path) -> Result<Self, Box<dyn Error>> {
+        let abs_path = std::fs::canonicalize(path)?;
+        let listing = fs::listing(&abs_path)?;
+        Ok(State {
+            listing,
+            path: abs_path,
+            previous: self.previous.clone(),
+            options: self.options.clone(),
+        })
+    }
+
+    pub fn cd_back(&mut self) -> Result<Self, Box<dyn Error>> {
+        let parent_path = self.path.parent().ok_or("No parent directory")?;
+        self.set_path(parent_path)
+    }
+
+    pub fn get_abs_path(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+// set when an EXISTS in the last input found nothing, so the process exits 1
+static NOTHING_FOUND: AtomicBool = AtomicBool::new(false);
+
+pub fn nothing_found() -> bool {
+    NOTHING_FOUND.load(Ordering::Relaxed)
+}
+
+// print an error on stderr: as a line of JSON when the results are JSON, so a
+// program reading them can parse failures too
+pub fn report_error(format: OutputFormat, error: &LsqlError) {
+    match format {
+        OutputFormat::Json | OutputFormat::Ndjson => eprintln!("{}", error.to_json()),
+        _ => eprintln!("Error: {}", error),
+    }
+}
+
+// where to collect rows headed for the terminal, when the shell pages them
+fn paged(state: &State) -> Option<pager::Buffer> {
+    let to_terminal = state.options.output.is_none() && state.options.format != OutputFormat::Parquet;
+    (pager::is_enabled() && to_terminal).then(pager::Buffer::default)
+}
+
+// the sink `--format` and `--output` ask for
+fn sink_for(state: &State) -> Result<Box<dyn OutputSink>, Box<dyn Error>> {
+    let output = state.options.output.as_ref().map(|output| state.path.join(output));
+    if let OutputFormat::Parquet = state.options.format {
+        let path = output.ok_or("--format parquet needs an output file, e.g. -o results.parquet")?;
+        if state.options.append {
+            return Err("a parquet file cannot be appended to".into());
+        }
+        return Ok(Box::new(export::ParquetSink::new(path)));
+    }
+    let out: Box<dyn std::io::Write> = match output {
+        Some(path) if state.options.append => {
+            let file = std::fs::File::options().create(true).append(true).open(path)?;
+            Box::new(std::io::BufWriter::new(file))
+        }
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    Ok(display::sink(state.options.format, out))
+}
+
+#[cfg(feature = "hash")]
+fn print_duplicates(state: &State, command: &Command) -> Result<(), Box<dyn Error>> {
+    let (groups, stats) = fs::execute_duplicates(state, command)?;
+    if groups.is_empty() {
+        println!("no duplicate files");
+    } else {
+        // every copy beyond the first in a group is space that could be reclaimed
+        let wasted: u64 = groups.iter().map(|group| group[0].size * (group.len() as u64 - 1)).sum();
+        println!("{}", display::duplicates(&groups));
+        println!("{} groups, {} in redundant copies", groups.len(), files::human_readable_size(wasted));
+    }
+    if state.options.verbose {
+        println!("{}", stats);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "hash"))]
+fn print_duplicates(_state: &State, _command: &Command) -> Result<(), Box<dyn Error>> {
+    Err(features::require("hash").unwrap_err().into())
+}
+
+fn execute(state: &mut State, command: &Command) -> Result<(), Box<dyn Error>> {
+    filter::reset_trace();
+    match command {
+        Command::Select { selection: SelectionType::Count, .. } => {
+            if let QueryResult::Count(count) = execute_query(state, command)? {
+                println!("{}", count);
+            }
+        }
+        Command::Select { selection, from_paths, .. } => {
+            // unknown columns are reported before the size probe asks anything
+            display::columns(selection)?;
+            if !state.options.no_estimate && estimate::is_recursive(command, &state.options) {
+                // archives are read whole and are not worth probing
+                let roots = match from_paths {
+                    Some(paths) => fs::resolve_all_roots(state, paths)?
+                        .into_iter()
+                        .filter_map(|root| match root {
+                            fs::Root::Directory(dir) => Some(dir),
+                            fs::Root::Archive(_) | fs::Root::Listed(..) => None,
+                        })
+                        .collect(),
+                    None => vec![state.path.clone()],
+                };
+                if !estimate::confirm(&roots) {
+                    return Err("cancelled".into());
+                }
+            }
+            match paged(state) {
+                Some(buffer) => {
+                    execute_into(state, command, display::sink(state.options.format, Box::new(buffer.clone())).as_mut())?;
+                    buffer.show()?;
+                }
+                None => execute_into(state, command, sink_for(state)?.as_mut())?,
+            }
+        }
+        Command::Show => {
+            let columns = display::columns(&SelectionType::All)?;
+            let buffer = paged(state);
+            let out: Box<dyn std::io::Write> = match &buffer {
+                Some(buffer) => Box::new(buffer.clone()),
+                None => Box::new(std::io::stdout()),
+            };
+            let mut sink = display::sink(OutputFormat::Table, out);
+            sink.begin(&columns)?;
+            for found in fs::listed(state) {
+                sink.entry(&found.stat(&state.options, fs::Detail::Owner)?)?;
+            }
+            sink.finish(&Summary { rows: state.listing.len(), elapsed: std::time::Duration::ZERO })?;
+            if let Some(buffer) = buffer {
+                buffer.show()?;
+            }
+        }
+        Command::Duplicates { .. } => print_duplicates(state, command)?,
+        Command::Exists { .. } => {
+            let found = fs::execute_exists(state, command)?;
+            if !found {
+                NOTHING_FOUND.store(true, Ordering::Relaxed);
+            }
+            println!("{}", found);
+        }
+        Command::ShowDrives => {
+            let drives = fs::drives();
+            if drives.is_empty() {
+                return Err("SHOW DRIVES is only available on Windows".into());
+            }
+            for drive in drives {
+                println!("{}", drive.display());
+            }
+        }
+        Command::ShowFields => print!("{}", help::render(Some("fields"))?),
+        Command::ShowFunctions => print!("{}", help::render(Some("functions"))?),
+        Command::ShowConfig => {
+            let file = config::config_dir().map(|dir| dir.join("config"));
+            match file {
+                Some(file) if file.exists() => println!("# {}", file.display()),
+                Some(file) => println!("# {} (not found)", file.display()),
+                None => println!("# no config directory"),
+            }
+            let options = &state.options;
+            // flags and the file both count, as main combines them
+            let settings = config::Settings {
+                trash: options.trash,
+                relative_times: options.relative_times,
+                icons: options.icons,
+                summary: options.summary,
+                natural_sort: options.natural_sort,
+                max_width: options.max_width,
+            };
+            print!("{}", config::describe(&settings));
+        }
+        Command::DeleteFiles { .. } => {
+            fs::execute_delete(state, command, state.options.dry_run)?;
+            *state = state.set_path(&state.path)?;
+        }
+        Command::Update { .. } => {
+            fs::execute_update(state, command, state.options.dry_run)?;
+            // the listing shown by SHOW may have changed
+            *state = state.set_path(&state.path)?;
+        }
+        Command::Explain(inner) => {
+            print!("{}", explain::plan(state, inner)?);
+        }
+        Command::Help { topic } => {
+            print!("{}", help::render(topic.as_deref())?);
+        }
+        Command::PrintDir => {
+            println!("{}", state.get_abs_path());
+        }
+        Command::ChangeDir { path } => {
+            let left = state.path.clone();
+            *state = if path == ".." {
+                state.cd_back()?
+            } else {
+                state.set_path(&state.path.join(path))?
+            };
+            state.previous.push(left);
+        }
+        Command::Back => {
+            let path = state.previous.last().ok_or("BACK: no earlier directory to return to")?;
+            let mut back = state.set_path(path)?;
+            back.previous.pop();
+            *state = back;
+        }
+    }
+    Ok(())
+}
+
+// what a SELECT produced: the matching entries, or just their number for COUNT(*)
+#[derive(Debug)]
+pub enum QueryResult {
+    Entries(Vec<FSEntry>),
+    Count(usize),
+}
+
+// parse a line of input into its statements; input the grammar stops short
+// of is an error rather than silently dropped
+pub fn parse_query(input: &str) -> Result<Vec<Command>, LsqlError> {
+    Ok(parse_input(input)?.into_iter().map(|(_source, command)| command).collect())
+}
+
+fn parse_input(input: &str) -> Result<Vec<(&str, Command)>, LsqlError> {
+    match parser::parse_statements(input) {
+        Ok((remaining, _)) if !remaining.trim().is_empty() => {
+            // a LIMIT that parses but is no row count says what is wrong with it
+            let stopped = input.len() - remaining.trim_start().len();
+            if let Some((start, width, problem)) = parser::row_count_error(&input[stopped..]) {
+                let position = stopped + start;
+                let message = format!("{}\n{}", problem, parser::underline(input, position, width));
+                return Err(LsqlError::Parse { message, position });
+            }
+            let diagnostic = parser::diagnose(input, input.len() - remaining.trim_start().len());
+            let mut message = match input[diagnostic.position..].trim() {
+                "" => "could not parse input: it ends too early".to_string(),
+                rest => format!("could not parse input starting at '{}'", rest),
+            };
+            message.push_str(&format!("\n{}", diagnostic.render(input)));
+            if let Some(hint) = parser::hint_for(remaining) {
+                message.push_str(&format!("\nhint: {}", hint));
+            }
+            Err(LsqlError::Parse { message, position: diagnostic.position })
+        }
+        Ok((_remaining, statements)) => Ok(statements),
+        Err(e) => Err(LsqlError::Parse { message: e.to_string(), position: 0 }),
+    }
+}
+
+// run a SELECT against `state` and hand back what it matched, without printing
+// anything; other statements change the session or the disk and go through
+// `run_input` instead
+pub fn execute_query(state: &State, command: &Command) -> Result<QueryResult, LsqlError> {
+    match command {
+        Command::Select { selection: SelectionType::Count, .. } => Ok(QueryResult::Count(fs::execute_count(state, command)?)),
+        Command::Select { .. } => Ok(QueryResult::Entries(fs::execute_select(state, command)?.into_files())),
+        _ => Err("execute_query runs SELECT statements".into()),
+    }
+}
+
+// run a SELECT and hand its rows to `sink` one at a time as the pipeline
+// produces them, for output in a format of the caller's own
+pub fn execute_into(state: &State, command: &Command, sink: &mut dyn OutputSink) -> Result<(), LsqlError> {
+    let Command::Select { selection, .. } = command else {
+        return Err("execute_into runs SELECT statements".into());
+    };
+    let started = std::time::Instant::now();
+    let columns = display::columns(selection)?;
+    // begun with the first row, so a statement that fails before it writes
+    // no header or opening bracket
+    let (mut begun, mut sinking) = (false, std::time::Duration::ZERO);
+    let rows = pipeline::stream(state, command, &mut |file| {
+        let started = std::time::Instant::now();
+        if !begun {
+            sink.begin(&columns)?;
+            begun = true;
+        }
+        sink.entry(&file)?;
+        sinking += started.elapsed();
+        Ok(())
+    })?;
+    let finishing = std::time::Instant::now();
+    if !begun {
+        sink.begin(&columns)?;
+    }
+    sink.finish(&Summary { rows, elapsed: started.elapsed() })?;
+    stats::stage("sink", rows, rows, sinking + finishing.elapsed());
+    Ok(())
+}
+
+// parse a line of input and run its statements in order, labelling each
+// statement's output when there is more than one and stopping at the first error
+pub fn run_input(state: &mut State, input: &str) -> Result<(), LsqlError> {
+    run_statements(state, input, &mut false)
+}
+
+// `written` says whether an earlier result set already went to the -o file,
+// carried over from statement to statement of a script
+fn run_statements(state: &mut State, input: &str, written: &mut bool) -> Result<(), LsqlError> {
+    let statements = parse_input(input)?;
+    NOTHING_FOUND.store(false, Ordering::Relaxed);
+    let total = statements.len();
+    // labels would break JSON or CSV on stdout, so those get them on stderr
+    let labels_on_stderr = !matches!(state.options.format, OutputFormat::Table | OutputFormat::Grid);
+    for (index, (source, command)) in statements.iter().enumerate() {
+        if total > 1 {
+            let label = format!("-- [{}/{}] {}", index + 1, total, source).cyan();
+            if labels_on_stderr {
+                eprintln!("{}", label);
+            } else {
+                println!("{}", label);
+            }
+        }
+        stats::reset();
+        cancel::start(state.options.timeout);
+        let started = std::time::Instant::now();
+        // every result set after the first is added to the -o file rather than replacing it
+        let append = state.options.append;
+        state.options.append |= *written;
+        let result = execute(state, command);
+        state.options.append = append;
+        *written |= matches!(command, Command::Select { selection, .. } if *selection != SelectionType::Count);
+        // what was learned before a failure is still worth keeping
+        if let Err(e) = cache::save() {
+            eprintln!("warning: could not write the metadata cache: {}", e);
+        }
+        // EXPLAIN reads nothing worth reporting
+        if state.options.stats && !matches!(command, Command::Explain(_)) {
+            eprintln!("{}", stats::report(started.elapsed()).dimmed());
+        }
+        result.map_err(|e| {
+            let error = LsqlError::from(e);
+            if total > 1 {
+                LsqlError::Statement { index: index + 1, source: Box::new(error) }
+            } else {
+                error
+            }
+        })?;
+    }
+    Ok(())
+}
+
+// run the statements in a script file, for `--file` and the shell's `source`
+pub fn run_file(state: &mut State, path: &Path) -> Result<(), LsqlError> {
+    let text = std::fs::read_to_string(state.path.join(path))
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    run_script(state, &text, &path.display().to_string())
+}
+
+// `lsql -`: run the statements piped to stdin, as a script, so they share
+// one -o file as a script's do
+pub fn run_stdin(state: &mut State) -> Result<(), LsqlError> {
+    let text = fs::read_query_from_stdin()?;
+    run_script(state, &text, "stdin")
+}
+
+// a script's statements in order, stopping at the first that fails unless
+// --keep-going is given. Lines starting with `--` are comments
+fn run_script(state: &mut State, text: &str, origin: &str) -> Result<(), LsqlError> {
+    let statements = script_statements(text);
+    let (mut failed, mut written) = (0, false);
+    for (index, statement) in statements.iter().enumerate() {
+        if let Err(e) = run_statements(state, statement, &mut written) {
+            let error = LsqlError::Statement { index: index + 1, source: Box::new(e) };
+            if !state.options.keep_going {
+                return Err(error);
+            }
+            report_error(state.options.format, &error);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} statements in {} failed", failed, statements.len(), origin).into()),
+    }
+}
+
+// a script's statements, split at semicolons outside quotes, with comments
+// and empty statements left out
+fn script_statements(text: &str) -> Vec<String> {
+    let text: Vec<&str> = text.lines().map(|line| if line.trim_start().starts_with("--") { "" } else { line }).collect();
+    let text = text.join("\n");
+    let mut statements = Vec::new();
+    let (mut quote, mut start, mut escaped, mut raw) = (None, 0, false, false);
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            // `\'` inside a quote is a quote character, not its end, except
+            // in a raw string such as r"C:\"
+            (Some(_), '\\') if !raw => escaped = true,
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => {
+                // only a standalone r makes a raw string, not the end of a word
+                raw = text[..i].strip_suffix(['r', 'R']).is_some_and(|before| !before.ends_with(|c: char| c.is_alphanumeric() || c == '_'));
+                quote = Some(c);
+            }
+            (None, ';') => {
+                statements.push(text[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(text[start..].trim().to_string());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_api() {
+        let dir = crate::testing::TempDir::new("core");
+        std::fs::write(dir.join("a.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        let state = State::at(&dir, Options::default()).unwrap();
+
+        let commands = parse_query("select name from . where ext = 'rs'; select count(*)").unwrap();
+        match execute_query(&state, &commands[0]).unwrap() {
+            QueryResult::Entries(entries) => {
+                assert_eq!(entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["a.rs"]);
+            }
+            other => panic!("expected entries, got {:?}", other),
+        }
+        assert!(matches!(execute_query(&state, &commands[1]).unwrap(), QueryResult::Count(2)));
+        assert!(execute_query(&state, &Command::PrintDir).is_err());
+        assert!(matches!(parse_query("pwd; selec * from ."), Err(LsqlError::Parse { position: 5, .. })));
+        for (query, problem) in [("select * limit 1 - 5", "LIMIT cannot be negative"), ("select * limit 10/0", "division by zero in LIMIT")] {
+            match parse_query(query) {
+                Err(LsqlError::Parse { message, position: 15 }) => assert!(message.starts_with(problem), "{}", message),
+                other => panic!("{}: {:?}", query, other),
+            }
+        }
+    }
+
+    // what a sink was told, in order
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl OutputSink for Recorder {
+        fn begin(&mut self, columns: &[display::Column]) -> Result<(), Box<dyn Error>> {
+            self.0.push(format!("begin {}", columns.len()));
+            Ok(())
+        }
+
+        fn entry(&mut self, file: &FileInfo) -> Result<(), Box<dyn Error>> {
+            if file.name == "stop" {
+                return Err("sink refused".into());
+            }
+            self.0.push(file.name.clone());
+            Ok(())
+        }
+
+        fn finish(&mut self, summary: &Summary) -> Result<(), Box<dyn Error>> {
+            self.0.push(format!("finish {}", summary.rows));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_execute_into_streams_to_the_sink() {
+        let dir = crate::testing::TempDir::new("sink");
+        for name in ["a", "b", "stop"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let state = State::at(&dir, Options::default()).unwrap();
+        let run = |query: &str| {
+            let mut sink = Recorder::default();
+            let result = execute_into(&state, &parse_query(query).unwrap()[0], &mut sink);
+            (result, sink.0)
+        };
+        let (result, told) = run("select name from . where name <> 'stop' order by name desc");
+        assert!(result.is_ok());
+        assert_eq!(told, ["begin 1", "b", "a", "finish 2"]);
+        // a failing sink ends the statement with its error
+        let (result, told) = run("select name from . order by name desc");
+        assert!(result.unwrap_err().to_string().contains("sink refused"));
+        assert_eq!(told, ["begin 1"]);
+        // nothing reaches the sink when the statement fails before any row
+        let (result, told) = run("select name from ./missing");
+        assert!(result.is_err());
+        assert!(told.is_empty());
+        let (_, told) = run("select name from . where name = 'none'");
+        assert_eq!(told, ["begin 1", "finish 0"]);
+    }
+
+    #[test]
+    fn test_unknown_field_exit_code() {
+        let dir = crate::testing::TempDir::new("unknown");
+        std::fs::write(dir.join("a.rs"), "").unwrap();
+        let mut state = State::at(&dir, Options::default()).unwrap();
+        for query in ["select nosuch from .", "select name from . order by nosuch", "select name from . where nosuch = 1"] {
+            let error = run_input(&mut state, query).unwrap_err();
+            assert_eq!((error.kind(), error.exit_code()), ("invalid_field", 3), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_script_statements() {
+        let script = "-- tidy up\nselect name\n  -- only sources\n  from . where ext = 'rs';\n\nselect name where name = 'a;b';;\npwd";
+        assert_eq!(script_statements(script), ["select name\n\n  from . where ext = 'rs'", "select name where name = 'a;b'", "pwd"]);
+        let script = "select name where name = 'it\\'s;x'; pwd";
+        assert_eq!(script_statements(script), ["select name where name = 'it\\'s;x'", "pwd"]);
+        assert_eq!(script_statements(r#"cd r"C:\"; pwd"#), [r#"cd r"C:\""#, "pwd"]);
+        assert_eq!(script_statements(r"select name where name = tar'a\';b'; pwd"), [r"select name where name = tar'a\';b'", "pwd"]);
+    }
+
+    #[test]
+    fn test_script_writes_every_result_set() {
+        let dir = crate::testing::TempDir::new("script-output");
+        std::fs::write(dir.join("a.rs"), "").unwrap();
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        let options = Options { format: OutputFormat::Csv, output: Some("out.csv".to_string()), ..Default::default() };
+        let mut state = State::at(&dir, options).unwrap();
+        let script = "select name from . where ext = 'rs';\nselect name from . where ext = 'txt';";
+        run_script(&mut state, script, "test").unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("out.csv")).unwrap(), "name\na.rs\nname\nb.txt\n");
+        // a new script starts the file over
+        run_script(&mut state, "select name from . where ext = 'rs'", "test").unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("out.csv")).unwrap(), "name\na.rs\n");
+    }
+
+    #[test]
+    fn test_cd_and_back() {
+        let dir = crate::testing::TempDir::new("cd");
+        std::fs::create_dir_all(dir.join("a").join("b")).unwrap();
+        let mut state = State::at(&dir, Options::default()).unwrap();
+
+        run_input(&mut state, "cd a; cd b; cd ..").unwrap();
+        assert_eq!(state.path, dir.join("a"));
+        run_input(&mut state, "back").unwrap();
+        assert_eq!(state.path, dir.join("a").join("b"));
+        run_input(&mut state, "back; back").unwrap();
+        assert_eq!(state.path, *dir);
+        assert!(run_input(&mut state, "back").is_err());
+    }
+}
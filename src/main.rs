@@ -1,128 +1,168 @@
 // lsql - A simple SQL-like language interpreter to query the files
 // like ls but supercharged with SQL-like queries
-pub mod files;
-pub mod parser;
-use std::{error::Error, fs, io::Write, path::{Path, PathBuf}};
-use chrono::{DateTime, Utc};
-use files::{FileInfo, FileType};
-use parser::parse;
-use walkdir::WalkDir;
-use colored::Colorize;
+use lsql_core::cli::{self, Subcommand};
+#[cfg(feature = "index")]
+use lsql_core::index;
+use lsql_core::{cache, config, display, features, filter, fixture, nice, plugins, report_error, run_file, run_input, run_stdin, shell, wizard, LsqlError, State};
 
-
-fn list_dir_contents(path: &Path) -> Result<Vec<FileInfo>, Box<dyn Error>> {
-    let mut files = Vec::new();
-    for entry in WalkDir::new(path).min_depth(1).max_depth(1) {
-        let entry = entry?;
-        let metadata = entry.metadata()?;
-        let file_type = if metadata.is_dir() {
-            FileType::Directory
-        } else if metadata.is_file() {
-            FileType::File
-        } else {
-            FileType::Other
-        };
-        let last_modified = DateTime::<Utc>::from(metadata.modified()?);
-        let file_info = FileInfo {
-            size: metadata.len(),
-            modified: last_modified,
-            name: entry.file_name().to_string_lossy().to_string(),
-            path: entry.path().display().to_string(),
-            file_type,
-        };
-        files.push(file_info);
+fn main() -> ! {
+    
+    if cfg!(debug_assertions) {
+        std::env::set_var("RUST_BACKTRACE", "1");
+        std::env::set_var("RUST_LIB_BACKTRACE", "1");
     }
-    Ok(files)
-}
-struct State {
-    files: Vec<FileInfo>,
-    path: PathBuf,
-}
 
-impl State {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        let current_dir = std::env::current_dir()?;
-        let files = list_dir_contents(&current_dir)?;
-        Ok(State {
-            files,
-            path: current_dir,
-        })
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+    if options.subcommand == Some(Subcommand::Version) {
+        println!("{}", features::version(options.verbose));
+        std::process::exit(0);
     }
 
-    pub fn set_path(&self, path: &Path) -> Result<Self, Box<dyn Error>> {
-        let abs_path = fs::canonicalize(path)?;
-        let files = list_dir_contents(&abs_path)?;
-        Ok(State {
-            files,
-            path: abs_path,
-        })
+    if let Some(Subcommand::GenFixture(spec)) = &options.subcommand {
+        match fixture::generate(spec) {
+            Ok(summary) => println!("{}", summary),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
     }
 
-
-   pub fn cd_back(&mut self) -> Result<Self, Box<dyn Error>>{
-    let parent_path = self.path.parent().ok_or("No parent directory")?;
-    self.set_path(parent_path)
+    if options.subcommand == Some(Subcommand::CacheClear) {
+        match cache::clear() {
+            Ok(summary) => println!("{}", summary),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
     }
 
-    pub fn get_abs_path(&self) -> String {
-        self.path.display().to_string()
+    #[cfg(feature = "index")]
+    let indexing = match &options.subcommand {
+        Some(Subcommand::IndexBuild(dir)) => Some(index::build(std::path::Path::new(dir), &options)),
+        Some(Subcommand::IndexList) => Some(index::list()),
+        Some(Subcommand::IndexDrop(dir)) => Some(index::remove(std::path::Path::new(dir))),
+        _ => None,
+    };
+    #[cfg(not(feature = "index"))]
+    let indexing: Option<Result<String, Box<dyn std::error::Error>>> = match &options.subcommand {
+        Some(Subcommand::IndexBuild(_) | Subcommand::IndexList | Subcommand::IndexDrop(_)) => Some(Err(features::require("index").unwrap_err().into())),
+        _ => None,
+    };
+    match indexing {
+        Some(Ok(summary)) => {
+            println!("{}", summary.trim_end());
+            std::process::exit(0);
+        }
+        Some(Err(e)) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        None => {}
     }
 
-}
+    let settings = config::load();
+    options.trash |= settings.trash;
+    options.relative_times |= settings.relative_times;
+    options.icons |= settings.icons;
+    options.summary |= settings.summary;
+    options.natural_sort |= settings.natural_sort;
+    options.max_width = options.max_width.or(settings.max_width);
 
-fn main() -> ! {
-    
-    if cfg!(debug_assertions) {
-        std::env::set_var("RUST_BACKTRACE", "1");
-        std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    if options.nice {
+        nice::enable();
+    }
+    if !options.no_cache {
+        cache::enable();
+    }
+    if options.trace_filter {
+        filter::enable_trace();
+    }
+    if options.relative_times {
+        display::use_relative_times();
+    }
+    if options.long {
+        display::use_long_listing();
+    }
+    if options.icons {
+        display::use_icons();
+    }
+    if options.summary {
+        display::use_summary();
+    }
+    if options.bytes {
+        display::use_bytes();
+    }
+    if let Some(width) = options.max_width {
+        display::use_max_width(width);
     }
 
-    let mut state = State::new().expect("Failed to initialize state");
-    let args: Vec<String> = std::env::args().skip(1).collect();
+    plugins::load();
+    if options.subcommand == Some(Subcommand::PluginsList) {
+        print!("{}", plugins::list());
+        std::process::exit(0);
+    }
 
+    let query = options.query.join(" ");
+    let file = options.file.clone();
+    let subcommand = options.subcommand.clone();
+    let format = options.format;
+    let mut state = match State::new(options) {
+        Ok(state) => state,
+        Err(e) => {
+            let e = LsqlError::from(e);
+            report_error(format, &e);
+            std::process::exit(e.exit_code());
+        }
+    };
 
-    loop {
-        let lsql_prompt = "lsql> ".green();
-        println!("current directory: {}", state.get_abs_path());
-        print!("{} ", lsql_prompt);
-        std::io::stdout().flush().unwrap();
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).expect("Failed to read input");
-        let input = input.trim();
-        match parse(input) {
-            Ok((_remaining, commands)) => {
-                if let Some(first_command) = commands.first() {
-                    match first_command {
-                        parser::Command::Show => {
-                            let query_set = files::FileQuerySet::new(state.files.clone());
-                            let table = query_set.table_them();
-                            println!("{}", table);
-                        }
-                        parser::Command::ChangeDir { path } => {
-                            let result = if path == ".." {
-                                state.cd_back()
-                            } else {
-                                state.set_path(Path::new(path))
-                            };
+    if subcommand == Some(Subcommand::Wizard) {
+        let code = match wizard::run(&mut state) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        };
+        std::process::exit(code);
+    }
 
-                            match result {
-                                Ok(new_state) => {
-                                    state = new_state;
-                                    // Reflect the change immediately
-                                    let current_abs_path = state.get_abs_path();
-                                },
-                                Err(e) => eprintln!("Error: {}", e),
-                            }
-                        }
-                        _ => {
-                            println!("Command not implemented yet");
-                        }
-                    }
-                }
+    if let Some(file) = file {
+        let code = match run_file(&mut state, std::path::Path::new(&file)) {
+            Ok(()) if lsql_core::nothing_found() => 1,
+            Ok(()) => 0,
+            Err(e) => {
+                report_error(format, &e);
+                e.exit_code()
             }
+        };
+        std::process::exit(code);
+    }
+
+    // a query on the command line runs once instead of starting the shell,
+    // and `-` reads the statements from stdin
+    if !query.is_empty() {
+        let result = if query == "-" { run_stdin(&mut state) } else { run_input(&mut state, &query) };
+        let code = match result {
+            Ok(()) if lsql_core::nothing_found() => 1,
+            Ok(()) => 0,
             Err(e) => {
-                eprintln!("Error: {}", e);
+                report_error(format, &e);
+                e.exit_code()
             }
-        }
+        };
+        std::process::exit(code);
     }
+
+    shell::run(&mut state)
 }
@@ -0,0 +1,56 @@
+// `--nice`: scan gently so a large background query does not starve
+// interactive work on a shared machine. The process drops to a low CPU and
+// idle I/O priority where the OS allows it, and walks pause briefly after
+// every batch of entries.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+// entries read between pauses
+const BATCH: usize = 256;
+const PAUSE: Duration = Duration::from_millis(25);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+    lower_priority();
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// called once per entry read from disk; sleeps after every BATCH of them
+pub fn pace() {
+    if is_enabled() && (SEEN.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(BATCH) {
+        std::thread::sleep(PAUSE);
+    }
+}
+
+// best effort: failing to lower the priority just leaves the throttling
+#[cfg(target_os = "linux")]
+fn lower_priority() {
+    use nix::libc;
+
+    // IOPRIO_WHO_PROCESS, with the idle class (3) in the top bits of the value
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_IDLE: libc::c_int = 3 << 13;
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+        libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_IDLE);
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn lower_priority() {
+    use nix::libc;
+
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 10);
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_priority() {}
@@ -0,0 +1,258 @@
+// A canonical spelling of a parsed statement and a fingerprint of it, so
+// queries written differently but meaning the same thing (keyword case,
+// quoting, the order of AND/OR operands or EXCLUDE patterns, an ASC that is
+// the default anyway) are recognized as one.
+
+use crate::parser::{Command, ConditionNode, Expression, FromItem, Ordering, SelectItem, SelectionType, WhereClause, UNLIMITED};
+
+// the statement as lsql would write it; parsing the result gives the same
+// canonical form back
+pub fn canonical(command: &Command) -> String {
+    match command {
+        Command::Select { selection, distinct, where_clause, order_by, limit, offset, from_paths, depth, exclude, ordering, natural } => {
+            let mut out = String::from("SELECT ");
+            let depth = match depth {
+                Some(UNLIMITED) => {
+                    out.push_str("RECURSIVE ");
+                    &None
+                }
+                depth => depth,
+            };
+            if *distinct {
+                out.push_str("DISTINCT ");
+            }
+            out.push_str(&selection_text(selection));
+            out.push_str(&scope(from_paths, depth, exclude, where_clause));
+            if let Some(fields) = order_by {
+                let fields: Vec<String> = fields.iter().map(|field| field.to_lowercase()).collect();
+                out.push_str(&format!(" ORDER BY {}", fields.join(", ")));
+                if *natural {
+                    out.push_str(" NATURAL");
+                }
+                if *ordering == Some(Ordering::Descending) {
+                    out.push_str(" DESC");
+                }
+            }
+            if let Some(limit) = limit {
+                out.push_str(&format!(" LIMIT {}", limit));
+            }
+            if let Some(offset) = offset {
+                out.push_str(&format!(" OFFSET {}", offset));
+            }
+            out
+        }
+        Command::ChangeDir { path } => format!("CD {}", quote(path)),
+        Command::Back => "BACK".to_string(),
+        Command::DeleteFiles { permanent, from_paths, depth, exclude, where_clause } => {
+            let permanent = if *permanent { " PERMANENT" } else { "" };
+            format!("DELETE{}{}", permanent, scope(from_paths, depth, exclude, where_clause))
+        }
+        Command::Exists { from_paths, depth, exclude, where_clause } => {
+            format!("EXISTS{}", scope(from_paths, depth, exclude, where_clause))
+        }
+        Command::Duplicates { from_paths, depth, exclude, where_clause } => {
+            format!("DUPLICATES{}", scope(from_paths, depth, exclude, where_clause))
+        }
+        Command::Show => "SHOW".to_string(),
+        Command::ShowDrives => "SHOW DRIVES".to_string(),
+        Command::ShowFields => "SHOW FIELDS".to_string(),
+        Command::ShowFunctions => "SHOW FUNCTIONS".to_string(),
+        Command::ShowConfig => "SHOW CONFIG".to_string(),
+        Command::PrintDir => "PWD".to_string(),
+        Command::Update { path, assignments, where_clause } => {
+            let assignments: Vec<String> = assignments
+                .iter()
+                .map(|(field, value)| format!("{} = {}", field.to_lowercase(), expression(value)))
+                .collect();
+            format!("UPDATE {} SET {}{}", quote(path), assignments.join(", "), scope(&None, &None, &[], where_clause))
+        }
+        Command::Help { topic: Some(topic) } => format!("HELP {}", topic.to_lowercase()),
+        Command::Help { topic: None } => "HELP".to_string(),
+        Command::Explain(inner) => format!("EXPLAIN {}", canonical(inner)),
+    }
+}
+
+// a short stable hash of the canonical form, the same across runs, builds
+// and platforms
+pub fn fingerprint(command: &Command) -> String {
+    // 64-bit FNV-1a
+    let hash = canonical(command)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3));
+    format!("{:016x}", hash)
+}
+
+fn quote(text: &str) -> String {
+    let escaped = text.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n").replace('\t', r"\t");
+    format!("\"{}\"", escaped)
+}
+
+fn selection_text(selection: &SelectionType) -> String {
+    let items = |items: &[SelectItem]| {
+        items
+            .iter()
+            .map(|item| match &item.alias {
+                Some(alias) if alias.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                    format!("{} AS {}", expression(&item.expression), alias)
+                }
+                Some(alias) => format!("{} AS {}", expression(&item.expression), quote(alias)),
+                None => expression(&item.expression),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    match selection {
+        SelectionType::All => "*".to_string(),
+        SelectionType::Every => "**".to_string(),
+        SelectionType::Count => "COUNT(*)".to_string(),
+        SelectionType::Files => "FILES".to_string(),
+        SelectionType::Directories => "DIRS".to_string(),
+        SelectionType::Fields(list) => items(list),
+        SelectionType::AllWith(list) => format!("*, {}", items(list)),
+    }
+}
+
+// FROM, DEPTH, EXCLUDE and WHERE, each with a leading space when present
+fn scope(from_paths: &Option<Vec<FromItem>>, depth: &Option<usize>, exclude: &[String], where_clause: &Option<ConditionNode>) -> String {
+    let mut out = String::new();
+    if let Some(items) = from_paths {
+        let items: Vec<String> = items
+            .iter()
+            .map(|item| match item {
+                FromItem::Path(path) => quote(path),
+                FromItem::Archive(file) => format!("ARCHIVE({})", quote(file)),
+                FromItem::Stdin => "STDIN".to_string(),
+            })
+            .collect();
+        out.push_str(&format!(" FROM {}", items.join(", ")));
+    }
+    if let Some(depth) = depth {
+        out.push_str(&format!(" DEPTH {}", depth));
+    }
+    if !exclude.is_empty() {
+        let mut patterns: Vec<String> = exclude.iter().map(|pattern| quote(pattern)).collect();
+        patterns.sort();
+        patterns.dedup();
+        out.push_str(&format!(" EXCLUDE {}", patterns.join(", ")));
+    }
+    if let Some(condition) = where_clause {
+        out.push_str(&format!(" WHERE {}", condition_text(condition)));
+    }
+    out
+}
+
+// field and function names in lower case, everything else as parsed
+fn lowercase(expression: &Expression) -> Expression {
+    match expression {
+        Expression::Field(name) => Expression::Field(name.to_lowercase()),
+        Expression::Function(name, args) => Expression::Function(name.to_lowercase(), args.iter().map(lowercase).collect()),
+        Expression::Arithmetic(left, operator, right) => {
+            Expression::Arithmetic(Box::new(lowercase(left)), *operator, Box::new(lowercase(right)))
+        }
+        literal => literal.clone(),
+    }
+}
+
+fn expression(expression: &Expression) -> String {
+    lowercase(expression).to_string()
+}
+
+// the terms of a chain of one operator, e.g. a, b and c of a AND b AND c
+fn operands<'a>(node: &'a ConditionNode, is_and: bool, out: &mut Vec<&'a ConditionNode>) {
+    match node {
+        ConditionNode::And(left, right) if is_and => {
+            operands(left, is_and, out);
+            operands(right, is_and, out);
+        }
+        ConditionNode::Or(left, right) if !is_and => {
+            operands(left, is_and, out);
+            operands(right, is_and, out);
+        }
+        other => out.push(other),
+    }
+}
+
+// AND and OR take their operands sorted and without repeats; NOT NOT cancels
+fn condition_text(condition: &ConditionNode) -> String {
+    match condition {
+        ConditionNode::Condition(clause) => clause_text(clause),
+        ConditionNode::Not(inner) => match &**inner {
+            ConditionNode::Not(twice) => condition_text(twice),
+            ConditionNode::Condition(clause) => format!("NOT {}", clause_text(clause)),
+            other => format!("NOT ({})", condition_text(other)),
+        },
+        ConditionNode::And(..) | ConditionNode::Or(..) => {
+            let is_and = matches!(condition, ConditionNode::And(..));
+            let mut nodes = Vec::new();
+            operands(condition, is_and, &mut nodes);
+            let mut terms: Vec<String> = nodes
+                .into_iter()
+                .map(|node| match node {
+                    // OR binds looser than AND, so it needs parentheses inside one
+                    ConditionNode::Or(..) if is_and => format!("({})", condition_text(node)),
+                    _ => condition_text(node),
+                })
+                .collect();
+            terms.sort();
+            terms.dedup();
+            terms.join(if is_and { " AND " } else { " OR " })
+        }
+    }
+}
+
+fn clause_text(clause: &WhereClause) -> String {
+    let (field, operator, value) = match clause {
+        WhereClause::Equal(field, value) => (field, "=", value),
+        WhereClause::NotEqual(field, value) => (field, "!=", value),
+        WhereClause::LessThan(field, value) => (field, "<", value),
+        WhereClause::LessThanOrEqual(field, value) => (field, "<=", value),
+        WhereClause::GreaterThan(field, value) => (field, ">", value),
+        WhereClause::GreaterThanOrEqual(field, value) => (field, ">=", value),
+        WhereClause::Contains(field, value) => (field, "CONTAINS", value),
+        WhereClause::Like(field, value) => (field, "LIKE", value),
+        WhereClause::ILike(field, value) => (field, "ILIKE", value),
+        WhereClause::UnknownOperator(field, value) => (field, "?", value),
+    };
+    format!("{} {} {}", field.to_lowercase(), operator, quote(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(query: &str) -> Command {
+        crate::parser::parse(query).unwrap().1.remove(0)
+    }
+
+    #[test]
+    fn test_equivalent_queries_share_a_fingerprint() {
+        let a = command("select name from . exclude target, .git where ext = 'rs' and size > 1mb order by size asc");
+        let b = command(r#"SELECT NAME FROM "." EXCLUDE .git, target WHERE size > "1mb" AND EXT = "rs" ORDER BY size"#);
+        assert_eq!(canonical(&a), canonical(&b));
+        assert_eq!(fingerprint(&a), fingerprint(&b));
+        assert_eq!(
+            canonical(&a),
+            r#"SELECT name FROM "." EXCLUDE ".git", "target" WHERE ext = "rs" AND size > "1mb" ORDER BY size"#
+        );
+        assert_ne!(fingerprint(&a), fingerprint(&command("select name from . where ext = 'rs'")));
+    }
+
+    #[test]
+    fn test_canonical_form_reparses() {
+        for query in [
+            "select distinct ext, size / 1024 as kb from ./src, archive(\"a.zip\") depth 3 where not (a = 1 or b = 2) and c = 3 order by name desc limit 5 offset 2",
+            "select name from stdin, . where size > 1mb",
+            "delete permanent from ./build where ext = 'o' or ext = 'a' or ext = 'o'",
+            "update . set name = replace(name, \" \", \"_\") where ext = \"mp3\"",
+            "explain select count(*) from .",
+            "select name from . order by name desc natural",
+            "select r distinct ext from ./src",
+            "select ** from . limit 2",
+        ] {
+            let once = canonical(&command(query));
+            assert_eq!(canonical(&command(&once)), once, "{}", query);
+        }
+        assert_eq!(canonical(&command("delete where ext = 'o' or ext = 'a' or ext = 'o'")), r#"DELETE WHERE ext = "a" OR ext = "o""#);
+        assert_eq!(canonical(&command("select name order by name desc natural")), "SELECT name ORDER BY name NATURAL DESC");
+    }
+}
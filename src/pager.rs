@@ -0,0 +1,92 @@
+// Paging long results in the shell. Output taller than the terminal goes
+// through `$PAGER` (`less` by default), which scrolls with the arrow keys,
+// searches with `/` and quits with `q`, instead of scrolling the start of
+// the results off the screen. Shorter output, or any when the pager cannot
+// be started, is printed as usual. `--no-pager` turns this off.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// a writer that keeps what a sink writes, to page once the statement is done
+#[derive(Clone, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    // show what was written, through the pager when it does not fit
+    pub fn show(&self) -> io::Result<()> {
+        let text = self.0.take();
+        let rows = crossterm::terminal::size().map(|(_, rows)| usize::from(rows)).unwrap_or(usize::MAX);
+        // the prompt takes a line as well
+        if fits(&text, rows.saturating_sub(1)) || page(&text).is_err() {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&text)?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn fits(text: &[u8], rows: usize) -> bool {
+    text.iter().filter(|&&byte| byte == b'\n').count() <= rows
+}
+
+fn page(text: &[u8]) -> io::Result<()> {
+    let pager = std::env::var("PAGER").ok().filter(|pager| !pager.trim().is_empty());
+    let (program, args) = match &pager {
+        Some(pager) => {
+            let mut words = pager.split_whitespace();
+            (words.next().unwrap_or("less"), words.collect())
+        }
+        // -R keeps colours, -S chops rows wider than the screen rather than wrapping a table
+        None => ("less", vec!["-R", "-S"]),
+    };
+    let mut child = Command::new(program).args(args).stdin(Stdio::piped()).spawn()?;
+    let mut stdin = child.stdin.take().ok_or_else(|| io::Error::other("could not write to the pager"))?;
+    match stdin.write_all(text) {
+        // quitting the pager early closes its input
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+        _ => {}
+    }
+    drop(stdin);
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits() {
+        assert!(fits(b"a\nb\nc\n", 3));
+        assert!(!fits(b"a\nb\nc\nd\n", 3));
+        assert!(fits(b"", 0));
+        let mut buffer = Buffer::default();
+        write!(buffer, "one\ntwo\n").unwrap();
+        assert!(fits(&buffer.0.borrow(), 2));
+    }
+}
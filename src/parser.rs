@@ -1,8 +1,8 @@
 use nom::{
-    branch::alt, bytes::complete::{tag, tag_no_case, take_while, take_while1}, character::complete::{char, multispace0}, combinator::{map, opt}, multi::separated_list0, sequence::{delimited, preceded, tuple}, IResult, Parser
+    branch::alt, bytes::complete::{tag, tag_no_case, take_while, take_while1}, character::complete::{alpha1, char, digit1, multispace0, one_of, satisfy, space0}, combinator::{consumed, map, map_opt, map_res, not, opt, peek, recognize}, multi::{fold_many0, many0, many1, separated_list0, separated_list1}, sequence::{delimited, preceded, terminated, tuple}, IResult, Parser
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum WhereClause {
     Equal(String, String),
     NotEqual(String, String),
@@ -10,47 +10,341 @@ pub enum WhereClause {
     LessThanOrEqual(String, String),
     GreaterThan(String, String),
     GreaterThanOrEqual(String, String),
+    Contains(String, String),
+    // wildcard match: `*` for any run of characters, `?` for one
+    Like(String, String),
+    // LIKE ignoring case
+    ILike(String, String),
     UnknownOperator(String, String),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionNode {
+    Condition(WhereClause),
+    And(Box<ConditionNode>, Box<ConditionNode>),
+    Or(Box<ConditionNode>, Box<ConditionNode>),
+    Not(Box<ConditionNode>),
+}
+
+// values computed per entry, e.g. `replace(name, " ", "_")` or `now()`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(String),
+    Field(String),
+    Function(String, Vec<Expression>),
+    // size / 1024
+    Arithmetic(Box<Expression>, Operator, Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    pub fn symbol(&self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Subtract => '-',
+            Operator::Multiply => '*',
+            Operator::Divide => '/',
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Add | Operator::Subtract => 1,
+            Operator::Multiply | Operator::Divide => 2,
+        }
+    }
+}
+
+// a condition written back out, for explaining why an entry was rejected
+impl std::fmt::Display for WhereClause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (field, operator, value) = match self {
+            WhereClause::Equal(field, value) => (field, "=", value),
+            WhereClause::NotEqual(field, value) => (field, "!=", value),
+            WhereClause::LessThan(field, value) => (field, "<", value),
+            WhereClause::LessThanOrEqual(field, value) => (field, "<=", value),
+            WhereClause::GreaterThan(field, value) => (field, ">", value),
+            WhereClause::GreaterThanOrEqual(field, value) => (field, ">=", value),
+            WhereClause::Contains(field, value) => (field, "CONTAINS", value),
+            WhereClause::Like(field, value) => (field, "LIKE", value),
+            WhereClause::ILike(field, value) => (field, "ILIKE", value),
+            WhereClause::UnknownOperator(field, value) => (field, "?", value),
+        };
+        write!(f, "{} {} {:?}", field, operator, value)
+    }
+}
+
+impl std::fmt::Display for ConditionNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // OR binds looser than AND, so it needs parentheses inside one
+        let operand = |node: &ConditionNode| match node {
+            ConditionNode::Or(..) => format!("({})", node),
+            _ => node.to_string(),
+        };
+        match self {
+            ConditionNode::Condition(clause) => write!(f, "{}", clause),
+            ConditionNode::And(left, right) => write!(f, "{} AND {}", operand(left), operand(right)),
+            ConditionNode::Or(left, right) => write!(f, "{} OR {}", left, right),
+            ConditionNode::Not(inner) => match **inner {
+                ConditionNode::Condition(_) => write!(f, "NOT {}", inner),
+                _ => write!(f, "NOT ({})", inner),
+            },
+        }
+    }
+}
+
+// the expression written back out, used to name computed columns
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Literal(text) if text.parse::<f64>().is_ok() => write!(f, "{}", text),
+            Expression::Literal(text) => write!(f, "{:?}", text),
+            Expression::Field(name) => write!(f, "{}", name),
+            Expression::Function(name, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Expression::Arithmetic(left, operator, right) => {
+                // parenthesize operands that would otherwise regroup when read back
+                let operand = |expression: &Expression, right_side: bool| match expression {
+                    Expression::Arithmetic(_, inner, _)
+                        if inner.precedence() < operator.precedence()
+                            || (right_side && inner.precedence() == operator.precedence()) =>
+                    {
+                        format!("({})", expression)
+                    }
+                    _ => expression.to_string(),
+                };
+                write!(f, "{} {} {}", operand(left, false), operator.symbol(), operand(right, true))
+            }
+        }
+    }
+}
+
+// one entry of the select list, e.g. `size / 1024 as size_kb`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectItem {
+    pub expression: Expression,
+    pub alias: Option<String>,
+}
+
+impl SelectItem {
+    pub fn field(name: &str) -> Self {
+        SelectItem { expression: Expression::Field(name.to_lowercase()), alias: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionType {
+    // select *
+    All,
+    // select name, size / 1024 as size_kb
+    Fields(Vec<SelectItem>),
+    // select *, dir_size -> the default columns followed by extra columns
+    AllWith(Vec<SelectItem>),
+    // select count(*) -> only the number of matching entries
+    Count,
+    // select ** -> every field that reads no more than metadata
+    Every,
+    // select files, select dirs -> the default columns, for entries of that type only
+    Files,
+    Directories,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Ordering {
     Ascending,
     Descending,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum WhereType<'a> {
-    Conditions(Vec<(&'a str, &'a str, &'a str)>),
-}
-
 #[derive(Debug, PartialEq)]
 pub enum Command {
     Select {
-        props: Vec<String>,
-        where_clause: Option<Vec<WhereClause>>,
+        selection: SelectionType,
+        // SELECT DISTINCT: drop rows whose selected columns repeat an earlier row
+        distinct: bool,
+        where_clause: Option<ConditionNode>,
         order_by: Option<Vec<String>>,
         limit: Option<usize>,
-        from_path: Option<String>,
+        // rows skipped after sorting, for paging with LIMIT
+        offset: Option<usize>,
+        // FROM ./src, ./tests reads every listed directory
+        from_paths: Option<Vec<FromItem>>,
+        // DEPTH n: how many levels below each root to read
+        depth: Option<usize>,
+        // EXCLUDE node_modules, "*.min.js": subtrees the walk skips
+        exclude: Vec<String>,
         ordering: Option<Ordering>,
+        // ORDER BY ... NATURAL: runs of digits compare as numbers, so file2 < file10
+        natural: bool,
     },
     
     ChangeDir {
         path: String,
     },
+    // BACK: return to the directory the last CD left
+    Back,
     
+    // DELETE FROM . WHERE ...: remove matching entries
     DeleteFiles {
-        first: bool,
-        where_clause: Vec<WhereClause>,
+        // DELETE PERMANENT: remove even when deleting to the trash is the default
+        permanent: bool,
+        from_paths: Option<Vec<FromItem>>,
+        depth: Option<usize>,
+        exclude: Vec<String>,
+        where_clause: Option<ConditionNode>,
     },
 
+    // EXISTS FROM . WHERE ...: whether any entry matches, as the exit code too
     Exists {
-        where_clause: Vec<WhereClause>,
+        from_paths: Option<Vec<FromItem>>,
+        depth: Option<usize>,
+        exclude: Vec<String>,
+        where_clause: Option<ConditionNode>,
+    },
+
+    // DUPLICATES FROM . WHERE ...: groups of files with identical contents
+    Duplicates {
+        from_paths: Option<Vec<FromItem>>,
+        depth: Option<usize>,
+        exclude: Vec<String>,
+        where_clause: Option<ConditionNode>,
     },
 
     Show,
+    // SHOW DRIVES: the drive letters available on Windows
+    ShowDrives,
+    // SHOW FIELDS, SHOW FUNCTIONS: what queries can use, as `help` lists it
+    ShowFields,
+    ShowFunctions,
+    // SHOW CONFIG: the settings in effect and the file they are read from
+    ShowConfig,
+
+    PrintDir,
+
+    Update {
+        path: String,
+        assignments: Vec<(String, Expression)>,
+        where_clause: Option<ConditionNode>,
+    },
+
+    Help {
+        topic: Option<String>,
+    },
+
+    // EXPLAIN SELECT ...: describe how a statement would run instead of running it
+    Explain(Box<Command>),
+}
+
+// one entry of a FROM list
+#[derive(Debug, Clone, PartialEq)]
+pub enum FromItem {
+    // a directory, possibly `~/...` or a glob naming several
+    Path(String),
+    // archive("logs.tar.gz"): the members of a zip or tar file
+    Archive(String),
+    // STDIN: the paths piped in, one per line or NUL separated
+    Stdin,
+}
+
+// a statement the grammar accepts, documented for `help`
+pub struct Statement {
+    pub name: &'static str,
+    pub syntax: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
 }
 
+pub const STATEMENTS: &[Statement] = &[
+    Statement {
+        name: "select",
+        syntax: "SELECT [RECURSIVE] [DISTINCT] *|**|FILES|DIRS|COUNT(*)|columns [FROM path|archive(file)|stdin, ...] [DEPTH n] [EXCLUDE pattern, ...] [WHERE condition] [ORDER BY fields] [ASC|DESC] [LIMIT n] [OFFSET n]",
+        description: "list entries; columns may be fields or expressions such as `size / 1024 AS kb`",
+        example: "select name, size from . where ext = 'rs' order by size desc limit 5",
+    },
+    Statement {
+        name: "update",
+        syntax: "UPDATE path SET field = value, ... [WHERE condition]",
+        description: "rename (name) or touch (modified) matching entries; --dry-run shows the changes first",
+        example: "update . set name = lower(name) where ext = 'JPG'",
+    },
+    Statement {
+        name: "delete",
+        syntax: "DELETE [PERMANENT] [FROM path, ...] [DEPTH n] [EXCLUDE pattern, ...] WHERE condition",
+        description: "list matching entries and, once confirmed (or with --yes), remove them or move them to the trash with --trash; --dry-run shows what would go",
+        example: "delete from ./build where ext = 'o'",
+    },
+    Statement {
+        name: "duplicates",
+        syntax: "DUPLICATES [FROM path, ...] [DEPTH n] [EXCLUDE pattern, ...] [WHERE condition]",
+        description: "group matching files with identical contents; only files sharing a size are read",
+        example: "duplicates from ~/Pictures depth 10 where size > 100kb",
+    },
+    Statement {
+        name: "exists",
+        syntax: "EXISTS [FROM path, ...] [DEPTH n] [EXCLUDE pattern, ...] [WHERE condition]",
+        description: "print true if any entry matches and false otherwise, exiting with 0 or 1 for use in scripts",
+        example: "exists from . where name = 'Cargo.lock'",
+    },
+    Statement {
+        name: "cd",
+        syntax: "CD path",
+        description: "change the session's directory; `cd ..` goes up",
+        example: "cd ./src",
+    },
+    Statement {
+        name: "back",
+        syntax: "BACK",
+        description: "return to the directory the last CD left, as popd does; repeat to go further back",
+        example: "back",
+    },
+    Statement {
+        name: "pwd",
+        syntax: "PWD",
+        description: "print the session's directory",
+        example: "pwd",
+    },
+    Statement {
+        name: "show",
+        syntax: "SHOW [DRIVES|FIELDS|FUNCTIONS|CONFIG]",
+        description: "list the session's directory with the default columns; or the available drives on Windows, the fields and functions queries can use, or the settings in effect",
+        example: "show",
+    },
+    Statement {
+        name: "explain",
+        syntax: "EXPLAIN statement",
+        description: "describe what a statement would read and filter, without running it",
+        example: "explain select * from . depth 5 exclude target where ext = 'rs'",
+    },
+    Statement {
+        name: "help",
+        syntax: "HELP [topic]",
+        description: "show help on a statement, `fields`, `operators` or `functions`",
+        example: "help fields",
+    },
+];
+
+// WHERE operators, in the form `operator` accepts them, for `help`
+pub const OPERATORS: &[(&str, &str)] = &[
+    ("=", "equal"),
+    ("!=", "not equal (also <>)"),
+    ("<", "less than; sizes, dates, modes and text"),
+    ("<=", "less than or equal"),
+    (">", "greater than"),
+    (">=", "greater than or equal"),
+    ("CONTAINS", "text contains the value"),
+    ("LIKE", "text matches a pattern where % (or *) stands for any characters, _ (or ?) for one and \\ escapes"),
+    ("ILIKE", "LIKE ignoring case, e.g. name ilike \"%.jpg\""),
+    ("WITHIN", "a date no older than a duration, e.g. modified within \"2 weeks\""),
+];
+
 
 fn identifier(input: &str) -> IResult<&str, &str> {
     // example => "name" or "file_name"
@@ -58,49 +352,320 @@ fn identifier(input: &str) -> IResult<&str, &str> {
 }
 
 fn limit_statement(input: &str) -> IResult<&str, usize> {
-    preceded(ws(tag_no_case("LIMIT")), ws(take_while1(|c: char| c.is_numeric())))(input).map(|(remaining, limit)| {
-        (remaining, limit.parse().unwrap())
+    preceded(ws(tag_no_case("LIMIT")), ws(row_count))(input)
+}
+
+// a number of rows for LIMIT and OFFSET: a whole number or arithmetic on
+// whole numbers such as `10 * 2`. `5mb` is not a number of rows, and neither
+// is a result below zero or too large to count
+fn row_count(input: &str) -> IResult<&str, usize> {
+    map_opt(row_sum, Result::ok)(input)
+}
+
+// why arithmetic that parses is still no number of rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowCountError {
+    Negative,
+    DivisionByZero,
+    TooLarge,
+}
+
+type RowCount = Result<usize, RowCountError>;
+
+fn row_sum(input: &str) -> IResult<&str, RowCount> {
+    let (input, first) = row_product(input)?;
+    fold_many0(
+        tuple((ws(one_of("+-")), row_product)),
+        move || first,
+        |left, (operator, right)| match operator {
+            '+' => left?.checked_add(right?).ok_or(RowCountError::TooLarge),
+            _ => left?.checked_sub(right?).ok_or(RowCountError::Negative),
+        },
+    )(input)
+}
+
+fn row_product(input: &str) -> IResult<&str, RowCount> {
+    let (input, first) = row_factor(input)?;
+    fold_many0(
+        tuple((ws(one_of("*/")), row_factor)),
+        move || first,
+        |left, (operator, right)| match operator {
+            '*' => left?.checked_mul(right?).ok_or(RowCountError::TooLarge),
+            _ => left?.checked_div(right?).ok_or(RowCountError::DivisionByZero),
+        },
+    )(input)
+}
+
+fn row_factor(input: &str) -> IResult<&str, RowCount> {
+    let whole = terminated(digit1, not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_' || c == '.'))));
+    alt((
+        map(whole, |digits: &str| digits.parse().map_err(|_| RowCountError::TooLarge)),
+        delimited(ws(char('(')), row_sum, ws(char(')'))),
+    ))(input)
+}
+
+// when `remaining` is a LIMIT or OFFSET whose arithmetic parses but is no
+// number of rows, what is wrong with it, with where the expression starts
+// and how long it is
+pub fn row_count_error(remaining: &str) -> Option<(usize, usize, String)> {
+    let (after, clause) = alt((keyword("LIMIT"), keyword("OFFSET")))(remaining).ok()?;
+    let (rest, value) = ws(row_sum)(after).ok()?;
+    let clause = clause.to_uppercase();
+    let message = match value.err()? {
+        RowCountError::Negative => format!("{} cannot be negative", clause),
+        RowCountError::DivisionByZero => format!("division by zero in {}", clause),
+        RowCountError::TooLarge => format!("{} is too large", clause),
+    };
+    let expression = after[..after.len() - rest.len()].trim();
+    Some((remaining.len() - after.trim_start().len(), expression.len(), message))
+}
+
+fn depth_clause(input: &str) -> IResult<&str, usize> {
+    preceded(ws(keyword("DEPTH")), ws(take_while1(|c: char| c.is_ascii_digit())))(input).map(|(remaining, depth)| {
+        (remaining, depth.parse().unwrap_or(usize::MAX))
     })
 }
 
-fn ws<'a, F: 'a, O>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O>
+fn exclude_clause(input: &str) -> IResult<&str, Vec<String>> {
+    preceded(ws(keyword("EXCLUDE")), separated_list1(ws(char(',')), ws(listed_directory_path)))(input)
+}
+
+fn offset_clause(input: &str) -> IResult<&str, usize> {
+    preceded(ws(keyword("OFFSET")), ws(row_count))(input)
+}
+
+fn ws<'a, F, O>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O>
 where
-    F: Fn(&'a str) -> IResult<&'a str, O>,
+    F: Fn(&'a str) -> IResult<&'a str, O> + 'a,
 {
     delimited(multispace0, inner, multispace0)
 }
 
-fn literal(input: &str) -> IResult<&str, &str> {
-    // literals like -> 'file_name.txt'
-    delimited(char('\''), take_while1(|c| c != '\''), char('\''))(input)
+fn keyword<'a>(word: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    // a keyword must not run into the identifier that follows it,
+    // so `notes` is not read as `NOT es`
+    move |input| {
+        terminated(
+            tag_no_case(word),
+            not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+        )(input)
+    }
+}
+
+fn quoted(input: &str) -> IResult<&str, String> {
+    // quoted strings like -> 'file_name.txt', "file_name.txt" or "tab\there"
+    // raw strings like    -> r"C:\Users\me" keep backslashes as written
+    alt((
+        map(
+            preceded(
+                alt((char('r'), char('R'))),
+                alt((
+                    delimited(char('\''), take_while(|c| c != '\''), char('\'')),
+                    delimited(char('"'), take_while(|c| c != '"'), char('"')),
+                )),
+            ),
+            |raw: &str| raw.to_string(),
+        ),
+        escaped_string('\''),
+        escaped_string('"'),
+    ))(input)
+}
+
+fn escaped_string(quote: char) -> impl Fn(&str) -> IResult<&str, String> {
+    move |input| {
+        let (input, _) = char(quote)(input)?;
+        let mut out = String::new();
+        let mut chars = input.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == quote {
+                return Ok((&input[i + c.len_utf8()..], out));
+            }
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some((_, 'n')) => out.push('\n'),
+                Some((_, 't')) => out.push('\t'),
+                Some((_, '\\')) => out.push('\\'),
+                Some((_, escaped)) if escaped == quote => out.push(escaped),
+                // unknown escapes are kept as written
+                Some((_, other)) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => break,
+            }
+        }
+        Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Char)))
+    }
+}
+
+fn literal(input: &str) -> IResult<&str, String> {
+    // literals like -> 'file_name.txt', "file_name.txt" or 1024
+    alt((
+        quoted,
+        map(
+            take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '.' || c == '-'),
+            |bare: &str| bare.to_string(),
+        ),
+    ))(input)
 }
 
 fn asterisk(input: &str) -> IResult<&str, &str> {
     tag_no_case("*")(input)
 }
 
-fn column_identifier(input: &str) -> IResult<&str, &str> {
-    alt((asterisk, identifier))(input)
+// an ORDER BY column: a field, or an alias, quoted when it was declared so
+fn order_column(input: &str) -> IResult<&str, String> {
+    alt((quoted, map(identifier, str::to_string)))(input)
+}
+
+fn column_list(input: &str) -> IResult<&str, Vec<String>> {
+    separated_list1(ws(char(',')), ws(order_column))(input)
+}
+
+fn select_item(input: &str) -> IResult<&str, SelectItem> {
+    map(
+        tuple((ws(expression), opt(preceded(ws(keyword("AS")), ws(alias))))),
+        |(expression, alias)| SelectItem { expression, alias },
+    )(input)
+}
+
+// `AS file_name` is folded to lower case like a field; `AS "File Name"` is kept as written
+fn alias(input: &str) -> IResult<&str, String> {
+    alt((quoted, map(identifier, str::to_lowercase)))(input)
+}
+
+// ORDER BY may name a column by its alias when that column is a plain field
+fn unalias(selection: &SelectionType, name: &str) -> String {
+    let items = match selection {
+        SelectionType::Fields(items) | SelectionType::AllWith(items) => items.as_slice(),
+        _ => &[],
+    };
+    let field = items.iter().find_map(|item| match (&item.alias, &item.expression) {
+        (Some(alias), Expression::Field(field)) if alias.eq_ignore_ascii_case(name) => Some(field.clone()),
+        _ => None,
+    });
+    field.unwrap_or_else(|| name.to_string())
+}
+
+fn select_list(input: &str) -> IResult<&str, Vec<SelectItem>> {
+    separated_list1(ws(char(',')), select_item)(input)
+}
+
+fn count_all(input: &str) -> IResult<&str, &str> {
+    terminated(keyword("COUNT"), tuple((ws(char('(')), char('*'), ws(char(')')))))(input)
+}
+
+// FILES or DIRS standing alone, so a field list is still read as one
+fn type_selection<'a>(word: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |input| terminated(keyword(word), not(peek(preceded(multispace0, char(',')))))(input)
+}
+
+fn selection(input: &str) -> IResult<&str, SelectionType> {
+    alt((
+        map(ws(count_all), |_| SelectionType::Count),
+        map(ws(tag("**")), |_| SelectionType::Every),
+        map(ws(type_selection("FILES")), |_| SelectionType::Files),
+        map(ws(type_selection("DIRS")), |_| SelectionType::Directories),
+        map(
+            preceded(ws(asterisk), opt(preceded(ws(char(',')), select_list))),
+            |extra| match extra {
+                Some(items) => SelectionType::AllWith(items),
+                None => SelectionType::All,
+            },
+        ),
+        map(select_list, SelectionType::Fields),
+    ))(input)
+}
+
+type RawCondition<'a> = (&'a str, &'a str, String);
+
+// condition grammar, loosest binding first:
+//   or_condition  = and_condition (OR and_condition)*
+//   and_condition = not_condition (AND not_condition)*
+//   not_condition = NOT not_condition | '(' or_condition ')' | comparison
+fn where_clause(input: &str) -> IResult<&str, ConditionNode> {
+    or_condition(input)
 }
 
-fn column_list(input: &str) -> IResult<&str, Vec<&str>> {
-    separated_list0(ws(char(',')), ws(column_identifier))(input)
+fn or_condition(input: &str) -> IResult<&str, ConditionNode> {
+    let (input, first) = and_condition(input)?;
+    fold_many0(
+        preceded(ws(keyword("OR")), and_condition),
+        move || first.clone(),
+        |left, right| ConditionNode::Or(Box::new(left), Box::new(right)),
+    )(input)
 }
 
-fn where_clause(input: &str) -> IResult<&str, Vec<(&str, &str, &str)>> {
-    separated_list0(ws(tag_no_case("AND")), ws(comparison))(input)
+fn and_condition(input: &str) -> IResult<&str, ConditionNode> {
+    let (input, first) = not_condition(input)?;
+    fold_many0(
+        preceded(ws(keyword("AND")), not_condition),
+        move || first.clone(),
+        |left, right| ConditionNode::And(Box::new(left), Box::new(right)),
+    )(input)
 }
 
-fn exists_statement(input: &str) -> IResult<&str, (&str, Vec<(&str, &str, &str)>)> {
+fn not_condition(input: &str) -> IResult<&str, ConditionNode> {
+    alt((
+        map(preceded(ws(keyword("NOT")), not_condition), |node| {
+            ConditionNode::Not(Box::new(node))
+        }),
+        delimited(ws(char('(')), or_condition, ws(char(')'))),
+        map(ws(comparison), comparison_to_enum),
+    ))(input)
+}
+
+
+// FROM, DEPTH, EXCLUDE and WHERE: which entries a statement acts on
+type RawScope = (Option<Vec<FromItem>>, Option<usize>, Option<Vec<String>>, Option<ConditionNode>);
+
+fn scope(input: &str) -> IResult<&str, RawScope> {
     tuple((
-        ws(tag_no_case("EXISTS")),
-        where_clause,
+        opt(from_clause),
+        opt(depth_clause),
+        opt(exclude_clause),
+        opt(preceded(ws(keyword("WHERE")), where_clause)),
     ))(input)
 }
 
+fn duplicates_statement(input: &str) -> IResult<&str, RawScope> {
+    preceded(ws(keyword("DUPLICATES")), scope)(input)
+}
+
+fn exists_statement(input: &str) -> IResult<&str, RawScope> {
+    preceded(ws(keyword("EXISTS")), scope)(input)
+}
 
-fn show_statement(input: &str) -> IResult<&str, &str> {
-    ws(tag_no_case("SHOW"))(input)
+fn delete_statement(input: &str) -> IResult<&str, (Option<&str>, RawScope)> {
+    preceded(ws(keyword("DELETE")), tuple((opt(ws(keyword("PERMANENT"))), scope)))(input)
+}
+
+fn help_keyword(input: &str) -> IResult<&str, &str> {
+    // `\help` is accepted too, as in other database shells
+    preceded(opt(char('\\')), keyword("HELP"))(input)
+}
+
+fn help_statement(input: &str) -> IResult<&str, Option<&str>> {
+    preceded(ws(help_keyword), opt(ws(identifier)))(input)
+}
+
+fn show_target(input: &str) -> IResult<&str, &str> {
+    alt((keyword("DRIVES"), keyword("FIELDS"), keyword("FUNCTIONS"), keyword("CONFIG")))(input)
+}
+
+fn show_statement(input: &str) -> IResult<&str, Option<&str>> {
+    preceded(ws(keyword("SHOW")), opt(ws(show_target)))(input)
+}
+
+fn explain_statement(input: &str) -> IResult<&str, Command> {
+    preceded(ws(keyword("EXPLAIN")), command)(input)
+}
+
+fn pwd_statement(input: &str) -> IResult<&str, &str> {
+    ws(keyword("PWD"))(input)
 }
 
 
@@ -109,21 +674,69 @@ fn operator(input: &str) -> IResult<&str, &str> {
         tag("="),
         tag("<>"),
         tag("!="),
-        tag("<"),
         tag("<="),
-        tag(">"),
+        tag("<"),
         tag(">="),
+        tag(">"),
+        keyword("CONTAINS"),
+        keyword("WITHIN"),
+        keyword("LIKE"),
+        keyword("ILIKE"),
     ))(input)
 }
 
 
-fn comparison(input: &str) -> IResult<&str, (&str, &str, &str)> {
-    tuple((ws(identifier), ws(operator), ws(literal)))(input)
+fn comparison(input: &str) -> IResult<&str, RawCondition<'_>> {
+    tuple((ws(identifier), ws(operator), ws(comparison_value)))(input)
+}
+
+// `now()` or `now() - 7d`: a date relative to when entries are compared,
+// kept as text for `filter::parse_date` to work out
+fn relative_date(input: &str) -> IResult<&str, String> {
+    let amount = recognize(tuple((digit1, space0, alpha1)));
+    map_res(
+        tuple((keyword("NOW"), ws(char('(')), char(')'), opt(tuple((ws(one_of("+-")), amount))))),
+        |(_, _, _, offset)| match offset {
+            Some((sign, amount)) => crate::filter::parse_duration(amount).map(|_| format!("now() {} {}", sign, amount)),
+            None => Ok("now()".to_string()),
+        },
+    )(input)
+}
+
+fn comparison_value(input: &str) -> IResult<&str, String> {
+    // `me()` is looked up once, when the statement is parsed; dates relative
+    // to `now()` are worked out as each entry is compared
+    alt((
+        relative_date,
+        map_opt(
+            terminated(identifier, tuple((ws(char('(')), char(')')))),
+            |name| crate::expr::constant(name).map(|value| value.to_text()),
+        ),
+        literal,
+    ))(input)
 }
 
 
-fn from_path_clause(input: &str) -> IResult<&str, &str> {
-    preceded(ws(tag_no_case("FROM")), ws(directory_path))(input)
+fn archive_call(input: &str) -> IResult<&str, String> {
+    let bare = map(take_while1(|c: char| c != ')'), |path: &str| path.trim().to_string());
+    preceded(ws(keyword("ARCHIVE")), delimited(ws(char('(')), alt((ws(quoted), bare)), char(')')))(input)
+}
+
+// STDIN on its own, so `stdin/logs` is still a path
+fn stdin_source(input: &str) -> IResult<&str, &str> {
+    terminated(keyword("STDIN"), not(peek(satisfy(|c: char| !c.is_whitespace() && c != ',' && c != ';'))))(input)
+}
+
+fn from_item(input: &str) -> IResult<&str, FromItem> {
+    alt((
+        map(archive_call, FromItem::Archive),
+        map(stdin_source, |_| FromItem::Stdin),
+        map(listed_directory_path, FromItem::Path),
+    ))(input)
+}
+
+fn from_clause(input: &str) -> IResult<&str, Vec<FromItem>> {
+    preceded(ws(tag_no_case("FROM")), separated_list1(ws(char(',')), ws(from_item)))(input)
 }
 
 
@@ -135,25 +748,98 @@ fn ordering_clause(input: &str) -> IResult<&str, Ordering> {
 }
 
 
-fn select_statement(input: &str) -> IResult<&str, (&str, Vec<&str>, Option<Vec<(&str, &str, &str)>>, Option<Vec<&str>>, Option<usize>, Option<&str>, Option<Ordering>)> {
+type RawSelect<'a> = (
+    (Option<&'a str>, Option<&'a str>),
+    SelectionType,
+    Option<Vec<FromItem>>,
+    Option<usize>,
+    Option<Vec<String>>,
+    Option<ConditionNode>,
+    Option<(Vec<String>, Option<&'a str>)>,
+    Option<Ordering>,
+    Option<&'a str>,
+    Option<usize>,
+    Option<usize>,
+);
+
+// SELECT RECURSIVE (or SELECT R) reads every level below the FROM
+// directories; `select r from .` is still a column named r
+fn recursive(input: &str) -> IResult<&str, &str> {
+    let column_follows = preceded(multispace0, alt((keyword("FROM"), tag(","))));
+    terminated(alt((keyword("RECURSIVE"), keyword("R"))), not(peek(column_follows)))(input)
+}
+
+fn select_statement(input: &str) -> IResult<&str, RawSelect<'_>> {
     tuple((
-        ws(tag_no_case("SELECT")),
-        column_list,
-        opt(preceded(ws(tag_no_case("WHERE")), where_clause)),
-        opt(preceded(ws(tag_no_case("ORDER")), preceded(ws(tag_no_case("BY")), column_list))),
+        preceded(ws(tag_no_case("SELECT")), tuple((opt(ws(recursive)), opt(ws(keyword("DISTINCT")))))),
+        selection,
+        opt(from_clause),
+        opt(depth_clause),
+        opt(exclude_clause),
+        opt(preceded(ws(keyword("WHERE")), where_clause)),
+        opt(preceded(
+            ws(tag_no_case("ORDER")),
+            preceded(ws(tag_no_case("BY")), tuple((column_list, opt(ws(keyword("NATURAL")))))),
+        )),
+        opt(ordering_clause),
+        // `order by name desc natural` reads as well as `order by name natural desc`
+        opt(ws(keyword("NATURAL"))),
         opt(limit_statement),
-        opt(from_path_clause),
-        opt(ordering_clause)
+        opt(offset_clause),
     ))(input)
 }
 
 
-fn directory_path(input: &str) -> IResult<&str, &str> {
-    take_while(|c: char| c.is_alphanumeric() || c == '/' || c == '.' || c == '_')(input)
+// the DEPTH SELECT RECURSIVE stands for: no limit at all
+pub const UNLIMITED: usize = usize::MAX;
+
+// every keyword the grammar understands, for completion and help
+pub const KEYWORDS: &[&str] = &[
+    "SELECT", "RECURSIVE", "DISTINCT", "COUNT", "FILES", "DIRS", "FROM", "ARCHIVE", "STDIN", "DEPTH", "EXCLUDE", "WHERE", "ORDER BY", "NATURAL", "ASC", "DESC", "LIMIT", "OFFSET", "AND", "OR", "NOT",
+    "CONTAINS", "WITHIN", "LIKE", "ILIKE", "CD", "BACK", "PWD", "SHOW", "DRIVES", "FIELDS", "FUNCTIONS", "CONFIG", "DUPLICATES", "DELETE", "PERMANENT", "EXISTS", "UPDATE", "SET", "AS", "HELP", "EXPLAIN",
+];
+
+// keywords that may follow a path and therefore end an unquoted one
+const PATH_TERMINATORS: &[&str] = &["DEPTH", "EXCLUDE", "WHERE", "ORDER", "NATURAL", "LIMIT", "OFFSET", "ASC", "DESC", "SET"];
+
+fn directory_path(input: &str) -> IResult<&str, String> {
+    alt((quoted, map(unquoted_path(&[';']), |path: &str| path.to_string())))(input)
+}
+
+// one path of a comma separated FROM list
+fn listed_directory_path(input: &str) -> IResult<&str, String> {
+    alt((quoted, map(unquoted_path(&[';', ',']), |path: &str| path.to_string())))(input)
+}
+
+fn unquoted_path<'a>(separators: &'static [char]) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |input| unquoted_path_until(input, separators)
+}
+
+fn unquoted_path_until<'a>(input: &'a str, separators: &[char]) -> IResult<&'a str, &'a str> {
+    // an unquoted path may contain spaces (`from ./My Documents where ...`),
+    // so consume whole words until the next clause keyword, a separator or
+    // the end of input
+    let mut end = 0;
+    loop {
+        let rest = &input[end..];
+        let word_start = end + (rest.len() - rest.trim_start().len());
+        let word_len = input[word_start..]
+            .find(|c: char| c.is_whitespace() || separators.contains(&c))
+            .unwrap_or(input.len() - word_start);
+        let word = &input[word_start..word_start + word_len];
+        if word.is_empty() || word.starts_with(['\'', '"']) || PATH_TERMINATORS.iter().any(|k| k.eq_ignore_ascii_case(word)) {
+            break;
+        }
+        end = word_start + word_len;
+    }
+    if end == 0 {
+        return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::TakeWhile1)));
+    }
+    Ok((&input[end..], &input[..end]))
 }
 
 
-fn cd_statement(input: &str) -> IResult<&str, (&str, &str)> {
+fn cd_statement(input: &str) -> IResult<&str, (&str, String)> {
     tuple((
         ws(tag_no_case("CD")).or(ws(tag_no_case("CHANGEDIR"))),
         ws(directory_path),
@@ -161,53 +847,484 @@ fn cd_statement(input: &str) -> IResult<&str, (&str, &str)> {
 }
 
 
-fn where_clause_to_enum(wh: Option<Vec<(&str, &str, &str)>>) -> Option<Vec<WhereClause>> {
-    wh.map(|v| {
-        v.into_iter().map(|(col, op, val)| {
-            match op {
-                "=" => WhereClause::Equal(col.to_string(), val.to_string()),
-                "<>" | "!=" => WhereClause::NotEqual(col.to_string(), val.to_string()),
-                "<" => WhereClause::LessThan(col.to_string(), val.to_string()),
-                "<=" => WhereClause::LessThanOrEqual(col.to_string(), val.to_string()),
-                ">" => WhereClause::GreaterThan(col.to_string(), val.to_string()),
-                ">=" => WhereClause::GreaterThanOrEqual(col.to_string(), val.to_string()),
-                _ => WhereClause::UnknownOperator(col.to_string(), val.to_string()),
-            }
-        }).collect()
-    })
+fn back_statement(input: &str) -> IResult<&str, &str> {
+    ws(keyword("BACK"))(input)
+}
+
+fn additive_operator(input: &str) -> IResult<&str, Operator> {
+    alt((map(char('+'), |_| Operator::Add), map(char('-'), |_| Operator::Subtract)))(input)
+}
+
+fn multiplicative_operator(input: &str) -> IResult<&str, Operator> {
+    alt((map(char('*'), |_| Operator::Multiply), map(char('/'), |_| Operator::Divide)))(input)
+}
+
+// arithmetic binds as usual: `*` and `/` before `+` and `-`, left to right
+fn expression(input: &str) -> IResult<&str, Expression> {
+    let (input, first) = term(input)?;
+    fold_many0(
+        tuple((ws(additive_operator), term)),
+        move || first.clone(),
+        |left, (operator, right)| Expression::Arithmetic(Box::new(left), operator, Box::new(right)),
+    )(input)
+}
+
+fn term(input: &str) -> IResult<&str, Expression> {
+    let (input, first) = factor(input)?;
+    fold_many0(
+        tuple((ws(multiplicative_operator), factor)),
+        move || first.clone(),
+        |left, (operator, right)| Expression::Arithmetic(Box::new(left), operator, Box::new(right)),
+    )(input)
+}
+
+fn factor(input: &str) -> IResult<&str, Expression> {
+    alt((
+        delimited(ws(char('(')), expression, ws(char(')'))),
+        map(
+            tuple((
+                identifier,
+                delimited(
+                    ws(char('(')),
+                    separated_list0(ws(char(',')), ws(expression)),
+                    ws(char(')')),
+                ),
+            )),
+            |(name, args)| Expression::Function(name.to_lowercase(), args),
+        ),
+        map(quoted, Expression::Literal),
+        map(take_while1(|c: char| c.is_ascii_digit() || c == '.'), |number: &str| {
+            Expression::Literal(number.to_string())
+        }),
+        map(identifier, |field: &str| Expression::Field(field.to_lowercase())),
+    ))(input)
+}
+
+fn assignment(input: &str) -> IResult<&str, (String, Expression)> {
+    map(
+        tuple((ws(identifier), ws(char('=')), ws(expression))),
+        |(field, _, value)| (field.to_lowercase(), value),
+    )(input)
+}
+
+type RawUpdate = (String, Vec<(String, Expression)>, Option<ConditionNode>);
+
+fn update_statement(input: &str) -> IResult<&str, RawUpdate> {
+    tuple((
+        preceded(ws(keyword("UPDATE")), ws(directory_path)),
+        preceded(ws(keyword("SET")), separated_list1(ws(char(',')), assignment)),
+        opt(preceded(ws(keyword("WHERE")), where_clause)),
+    ))(input)
+}
+
+fn comparison_to_enum((col, op, val): RawCondition<'_>) -> ConditionNode {
+    let clause = match op.to_uppercase().as_str() {
+        "=" => WhereClause::Equal(col.to_string(), val),
+        "<>" | "!=" => WhereClause::NotEqual(col.to_string(), val),
+        "<" => WhereClause::LessThan(col.to_string(), val),
+        "<=" => WhereClause::LessThanOrEqual(col.to_string(), val),
+        ">" => WhereClause::GreaterThan(col.to_string(), val),
+        ">=" => WhereClause::GreaterThanOrEqual(col.to_string(), val),
+        "CONTAINS" => WhereClause::Contains(col.to_string(), val),
+        "LIKE" => WhereClause::Like(col.to_string(), val),
+        "ILIKE" => WhereClause::ILike(col.to_string(), val),
+        // `modified within "2 weeks"` is no older than two weeks
+        "WITHIN" => WhereClause::GreaterThanOrEqual(col.to_string(), format!("now() - {}", val)),
+        _ => WhereClause::UnknownOperator(col.to_string(), val),
+    };
+    ConditionNode::Condition(clause)
 }
 
 fn command(input: &str) -> IResult<&str, Command> {
     alt((
-        map(select_statement, |(select)| {
-            let (_command, columns, where_clause, order_by, _limit, _from_path, _ordering) = select;
+        map(select_statement, |select| {
+            let ((recursive, distinct), selection, from_paths, depth, exclude, where_clause, order_by, ordering, natural, limit, offset) = select;
+            // an explicit DEPTH is more specific than RECURSIVE
+            let depth = depth.or(recursive.map(|_| UNLIMITED));
+            let natural = natural.is_some() || order_by.as_ref().is_some_and(|(_, natural)| natural.is_some());
             Command::Select {
-                props: columns.iter().map(|&s| s.to_string()).collect(),
-                order_by: order_by.map(|v| v.iter().map(|&s| s.to_string()).collect()),
-                where_clause: where_clause_to_enum(where_clause),
-                limit: _limit,
-                from_path: _from_path.map(|s| s.to_string()),
-                ordering: _ordering,
+                distinct: distinct.is_some(),
+                order_by: order_by.map(|(v, _)| v.iter().map(|name| unalias(&selection, name)).collect()),
+                natural,
+                selection,
+                where_clause,
+                limit,
+                offset,
+                from_paths,
+                depth,
+                exclude: exclude.unwrap_or_default(),
+                ordering,
             }
         }),
         map(cd_statement, |(_command, path)| {
             Command::ChangeDir {
-                path: path.to_string(),
+                path,
             }
         }),
-        map(show_statement, |_command| {
-            Command::Show
+        map(back_statement, |_command| Command::Back),
+        map(show_statement, |what| match what.map(str::to_uppercase).as_deref() {
+            Some("DRIVES") => Command::ShowDrives,
+            Some("FIELDS") => Command::ShowFields,
+            Some("FUNCTIONS") => Command::ShowFunctions,
+            Some(_) => Command::ShowConfig,
+            None => Command::Show,
         }),
-        map(exists_statement, |(_command, where_clause)|{
-            Command::Exists { 
-                where_clause: where_clause_to_enum(Some(where_clause)).unwrap_or_default(),
-             }
-        })
+        map(pwd_statement, |_command| {
+            Command::PrintDir
+        }),
+        map(exists_statement, |(from_paths, depth, exclude, where_clause)| Command::Exists {
+            from_paths,
+            depth,
+            exclude: exclude.unwrap_or_default(),
+            where_clause,
+        }),
+        map(duplicates_statement, |(from_paths, depth, exclude, where_clause)| Command::Duplicates {
+            from_paths,
+            depth,
+            exclude: exclude.unwrap_or_default(),
+            where_clause,
+        }),
+        map(delete_statement, |(permanent, (from_paths, depth, exclude, where_clause))| Command::DeleteFiles {
+            permanent: permanent.is_some(),
+            from_paths,
+            depth,
+            exclude: exclude.unwrap_or_default(),
+            where_clause,
+        }),
+        map(update_statement, |(path, assignments, where_clause)| {
+            Command::Update {
+                path,
+                assignments,
+                where_clause,
+            }
+        }),
+        map(help_statement, |topic| Command::Help {
+            topic: topic.map(|t| t.to_lowercase()),
+        }),
+        map(explain_statement, |inner| Command::Explain(Box::new(inner))),
     ))(input)
 }
 
+// what may follow at the point a statement stopped parsing, each with text
+// that completes it, so `diagnose` can try which of them the grammar takes
+const CONTINUATIONS: &[(&str, &[&str])] = &[
+    ("FROM", &["FROM ."]),
+    ("DEPTH", &["DEPTH 2"]),
+    ("EXCLUDE", &["EXCLUDE x"]),
+    ("WHERE", &["WHERE name = 'x'"]),
+    ("AND", &["AND name = 'x'"]),
+    ("OR", &["OR name = 'x'"]),
+    ("ORDER BY", &["ORDER BY name"]),
+    ("BY", &["BY name"]),
+    ("ASC", &["ASC"]),
+    ("DESC", &["DESC"]),
+    ("LIMIT", &["LIMIT 1"]),
+    ("OFFSET", &["OFFSET 1"]),
+    ("SET", &["SET name = 'x'"]),
+    ("','", &[", name"]),
+    ("';'", &[";"]),
+    ("')'", &[")"]),
+    // a field starts a column, a condition or an assignment
+    ("a field", &["name", "name = 'x'"]),
+    ("a value", &["'x'"]),
+    ("a number", &["1"]),
+    ("an operator", &["= 'x'"]),
+    ("a path", &["."]),
+];
+
+// where and why input failed to parse, for an error that points at it
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    // byte offset of the offending token in the input
+    pub position: usize,
+    // the offending token; empty at the end of the input
+    pub found: String,
+    pub expected: Vec<&'static str>,
+}
+
+impl Diagnostic {
+    // the line of `input` holding the problem, underlined, with what was
+    // expected there
+    pub fn render(&self, input: &str) -> String {
+        let found = if self.found.is_empty() { "end of input".to_string() } else { format!("`{}`", self.found) };
+        let mut out = underline(input, self.position, self.found.chars().count());
+        match self.expected.as_slice() {
+            [] => out.push_str(&format!(" unexpected {}", found)),
+            [only] => out.push_str(&format!(" expected {}, found {}", only, found)),
+            [most @ .., last] => out.push_str(&format!(" expected {} or {}, found {}", most.join(", "), last, found)),
+        }
+        out
+    }
+}
+
+// the line of `input` holding `position`, with `width` characters from
+// there underlined (at least one)
+pub fn underline(input: &str, position: usize, width: usize) -> String {
+    use colored::Colorize;
+    let line_start = input[..position].rfind('\n').map_or(0, |index| index + 1);
+    let line_end = input[position..].find('\n').map_or(input.len(), |index| position + index);
+    let column = input[line_start..position].chars().count();
+    format!("  {}\n  {}{}", &input[line_start..line_end], " ".repeat(column), "^".repeat(width.max(1)).red())
+}
+
+// where tokens start in `text`: after whitespace, at quotes, and wherever
+// words and punctuation meet
+fn token_starts(text: &str) -> Vec<usize> {
+    let class = |c: char| match c {
+        c if c.is_whitespace() => 0,
+        c if c.is_alphanumeric() || "_./~*-\\".contains(c) => 1,
+        _ => 2,
+    };
+    let mut starts = Vec::new();
+    let mut previous = 0;
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        if let Some(open) = quote {
+            if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        let current = class(c);
+        if current != 0 && (current != previous || current == 2) {
+            starts.push(index);
+        }
+        if c == '"' || c == '\'' {
+            quote = Some(c);
+        }
+        previous = current;
+    }
+    starts
+}
+
+// the token starting at `text`
+fn token(text: &str) -> &str {
+    let end = token_starts(text)
+        .into_iter()
+        .find(|&start| start > 0)
+        .unwrap_or(text.len());
+    text[..end].trim_end()
+}
+
+// `parse_statements` stopped at `position`. Optional clauses backtrack, so
+// when the token there starts a clause the grammar knows, the real mistake
+// lies inside that clause: follow it to the furthest token the grammar can
+// still reach, and report what it would accept there instead
+pub fn diagnose(input: &str, position: usize) -> Diagnostic {
+    let accepts = |prefix: &str| -> Vec<&'static str> {
+        let at_start = prefix.trim_end().is_empty() || prefix.trim_end().ends_with(';');
+        let statements = STATEMENTS
+            .iter()
+            .filter(|_| at_start)
+            .map(|statement| (statement.name, std::slice::from_ref(&statement.example)));
+        let parses = |completion: &&str| {
+            let candidate = format!("{} {}", prefix, completion);
+            matches!(parse_statements(&candidate), Ok((rest, _)) if rest.trim().is_empty())
+        };
+        let expected: Vec<&'static str> = CONTINUATIONS
+            .iter()
+            .copied()
+            .chain(statements)
+            .filter(|(_, completions)| completions.iter().any(parses))
+            .map(|(name, _)| name)
+            .collect();
+        // unquoted values and paths take almost anything, which says nothing
+        // useful: keep just the value, or the path and the keywords that end one
+        if expected.contains(&"a value") {
+            return expected.into_iter().filter(|name| ["a value", "a number"].contains(name)).collect();
+        }
+        if expected.contains(&"a path") {
+            let ends_path = |name: &&str| {
+                ["a path", "','", "';'"].contains(name)
+                    || PATH_TERMINATORS.iter().any(|word| name.split(' ').next() == Some(word))
+            };
+            return expected.into_iter().filter(ends_path).collect();
+        }
+        if at_start {
+            return expected.into_iter().filter(|name| *name != "';'").collect();
+        }
+        expected
+    };
+    let rest = &input[position..];
+    let found = token(rest.trim_start()).to_string();
+    let expected = accepts(&input[..position]);
+    let starts_clause = found == "("
+        || expected.iter().any(|name| name.split(' ').next().is_some_and(|word| word.eq_ignore_ascii_case(&found)));
+    let mut diagnostic = Diagnostic { position, found, expected };
+    if !starts_clause {
+        return diagnostic;
+    }
+    // the end of the input counts too, for statements cut short
+    for start in token_starts(rest).into_iter().skip(1).chain([rest.trim_end().len()]) {
+        let expected = accepts(&input[..position + start]);
+        if !expected.is_empty() {
+            let found = token(&rest[start..]).to_string();
+            diagnostic = Diagnostic { position: position + start, found, expected };
+        }
+    }
+    diagnostic
+}
+
+// letters to insert, remove or change to turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1).min(row[j] + 1).min(diagonal + usize::from(x != *y));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+// edits of `input` that might fix what `diagnostic` points at, likeliest
+// first: the offending token respelled as a keyword expected there, missing
+// punctuation, a placeholder where a value or path is missing, an expected
+// keyword put in front of the token, and finally a whole clause
+fn corrections(input: &str, diagnostic: &Diagnostic) -> Vec<String> {
+    let before = input[..diagnostic.position].trim_end();
+    let after = input[diagnostic.position..].trim_start();
+    let rest = after[diagnostic.found.len()..].trim_start();
+    // answer in the case the input was typed in
+    let lowercase = !input.chars().any(|c| c.is_ascii_uppercase());
+    let spell = |text: &str| if lowercase { text.to_lowercase() } else { text.to_uppercase() };
+    let found = diagnostic.found.to_uppercase();
+    let close = |word: &str| {
+        let allowed = if word.len() > 3 { 2 } else { 1 };
+        !found.is_empty() && distance(&found, &word.to_uppercase()) <= allowed
+    };
+    let keywords = || {
+        diagnostic
+            .expected
+            .iter()
+            .filter(|label| label.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .filter(|label| !label.starts_with("a ") && !label.starts_with("an "))
+    };
+    let completions = |placeholders: bool| {
+        CONTINUATIONS
+            .iter()
+            .filter(move |(label, _)| diagnostic.expected.contains(label))
+            .filter(move |(label, _)| label.starts_with('a') == placeholders)
+            .flat_map(|(_, completions)| completions.iter())
+    };
+
+    // (what goes where the offending token was, what follows it)
+    let mut candidates: Vec<(String, &str)> = Vec::new();
+    for label in keywords() {
+        let word = label.split(' ').next().unwrap_or(label);
+        if close(word) {
+            candidates.push((spell(word), rest));
+            candidates.push((spell(label), rest));
+        }
+    }
+    for completion in completions(true) {
+        candidates.push((completion.to_string(), after));
+        candidates.push((completion.to_string(), rest));
+    }
+    for label in ["','", "')'"].iter().filter(|label| diagnostic.expected.contains(label)) {
+        candidates.push((label.trim_matches('\'').to_string(), after));
+    }
+    for label in keywords() {
+        candidates.push((spell(label), after));
+    }
+    for completion in completions(false) {
+        candidates.push((spell(completion), after));
+        candidates.push((spell(completion), rest));
+    }
+    candidates
+        .into_iter()
+        .map(|(middle, tail)| {
+            let mut out = before.to_string();
+            for part in [middle.as_str(), tail] {
+                if !out.is_empty() && !part.is_empty() && !part.starts_with([',', ')']) {
+                    out.push(' ');
+                }
+                out.push_str(part);
+            }
+            out
+        })
+        .collect()
+}
+
+// an unquoted path takes in words up to the next clause keyword, so a
+// keyword left out right after one only shows as an error further on: where
+// each word but the first of the path that runs up to `position` starts
+fn path_words(input: &str, position: usize) -> Vec<usize> {
+    let head = &input[..position];
+    let mut words: Vec<usize> = Vec::new();
+    for start in token_starts(head).into_iter().rev() {
+        let word = token(&head[start..]);
+        if word.eq_ignore_ascii_case("FROM") || word.eq_ignore_ascii_case("CD") {
+            words.pop();
+            words.reverse();
+            return words;
+        }
+        if word.starts_with(['\'', '"', ',', ';']) || KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(word)) {
+            break;
+        }
+        if head[..start].ends_with(char::is_whitespace) {
+            words.push(start);
+        }
+    }
+    Vec::new()
+}
+
+// a corrected version of input the parser stopped short of, for the shell
+// to offer; the first of the `corrections` that parses
+pub fn did_you_mean(input: &str) -> Option<String> {
+    let Ok((remaining, _)) = parse_statements(input) else {
+        return None;
+    };
+    if remaining.trim().is_empty() {
+        return None;
+    }
+    let position = input.len() - remaining.trim_start().len();
+    let diagnostic = diagnose(input, position);
+    path_words(input, diagnostic.position)
+        .into_iter()
+        .map(|start| diagnose(input, start))
+        .chain([diagnostic])
+        .flat_map(|diagnostic| corrections(input, &diagnostic))
+        .find(|candidate| matches!(parse_statements(candidate), Ok((rest, _)) if rest.trim().is_empty()))
+}
+
+// a targeted suggestion for input the parser stopped at, if we have one
+pub fn hint_for(remaining: &str) -> Option<&'static str> {
+    let remaining = remaining.trim_start();
+    let starts_with = |word: &str| {
+        remaining.len() >= word.len() && remaining[..word.len()].eq_ignore_ascii_case(word)
+    };
+    if starts_with("FROM") || starts_with("CD") {
+        Some("quote paths that contain spaces or special characters, e.g. from \"./My Documents\"")
+    } else if starts_with("LIMIT") || starts_with("OFFSET") {
+        Some("LIMIT and OFFSET take a whole number of rows, e.g. limit 20 or limit 10 * 2")
+    } else {
+        None
+    }
+}
+
 pub fn parse(input: &str) -> IResult<&str, Vec<Command>> {
-    separated_list0(ws(char(';')), ws(command))(input)
+    map(parse_statements, |statements| {
+        statements.into_iter().map(|(_source, command)| command).collect()
+    })(input)
+}
+
+fn statement(input: &str) -> IResult<&str, (&str, Command)> {
+    consumed(command)(input)
+}
+
+// like `parse`, but keeps the source text of every statement alongside it
+pub fn parse_statements(input: &str) -> IResult<&str, Vec<(&str, Command)>> {
+    // empty statements, as in `pwd;; show` or a trailing `;`, are skipped
+    delimited(
+        many0(ws(char(';'))),
+        separated_list0(many1(ws(char(';'))), ws(statement)),
+        many0(ws(char(';'))),
+    )(input)
 }
 
 
@@ -219,16 +1336,22 @@ mod tests {
     fn test_select_statement() {
         let input = "SELECT * WHERE name = 'file_name.txt'";
         let expected = Command::Select {
-            props: vec!["*".to_string()],
-            where_clause: Some(vec![WhereClause::Equal("name".to_string(), "file_name.txt".to_string())]),
+            selection: SelectionType::All,
+            distinct: false,
+            where_clause: Some(ConditionNode::Condition(WhereClause::Equal("name".to_string(), "file_name.txt".to_string()))),
             order_by: None,
             limit: None,
-            from_path: None,
+            offset: None,
+            from_paths: None,
+            depth: None,
+            exclude: Vec::new(),
             ordering: None,
+            natural: false,
         };
 
         let result = parse(input);
         assert_eq!(result, Ok(("", vec![expected])));
+        assert_eq!(parse("cd src; back"), Ok(("", vec![Command::ChangeDir { path: "src".to_string() }, Command::Back])));
     }
 
     #[test]
@@ -242,6 +1365,20 @@ mod tests {
         assert_eq!(result, Ok(("", vec![expected])));
     }
 
+    #[test]
+    fn test_help_statement() {
+        assert_eq!(parse("help"), Ok(("", vec![Command::Help { topic: None }])));
+        assert_eq!(
+            parse("\\help Fields"),
+            Ok(("", vec![Command::Help { topic: Some("fields".to_string()) }]))
+        );
+    }
+
+    #[test]
+    fn test_pwd_statement() {
+        assert_eq!(parse("pwd"), Ok(("", vec![Command::PrintDir])));
+    }
+
     #[test]
     fn test_show_statement() {
         let input = "SHOW";
@@ -249,5 +1386,531 @@ mod tests {
 
         let result = parse(input);
         assert_eq!(result, Ok(("", vec![expected])));
+        assert_eq!(parse("show drives"), Ok(("", vec![Command::ShowDrives])));
+        assert_eq!(parse("show Fields; show config"), Ok(("", vec![Command::ShowFields, Command::ShowConfig])));
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let input = "select * where size lots";
+        let diagnostic = diagnose(input, input.find("where").unwrap());
+        assert_eq!(diagnostic.position, input.find("lots").unwrap());
+        assert_eq!(diagnostic.found, "lots");
+        assert!(diagnostic.expected.contains(&"an operator"), "{:?}", diagnostic);
+
+        let diagnostic = diagnose("select * frm .", 9);
+        assert_eq!((diagnostic.position, diagnostic.found.as_str()), (9, "frm"));
+        assert!(diagnostic.expected.contains(&"FROM") && diagnostic.expected.contains(&"WHERE"));
+
+        let diagnostic = diagnose("select * from . where", 16);
+        assert_eq!((diagnostic.position, diagnostic.found.as_str()), (21, ""));
+        assert!(diagnostic.expected.contains(&"a field"));
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        assert_eq!(did_you_mean("select * form ."), Some("select * from .".to_string()));
+        assert_eq!(did_you_mean("SELCT * FROM ."), Some("SELECT * FROM .".to_string()));
+        assert_eq!(did_you_mean("select * from . ext = \"rs\""), Some("select * from . where ext = \"rs\"".to_string()));
+        assert_eq!(did_you_mean("select name size from ."), Some("select name, size from .".to_string()));
+        assert_eq!(did_you_mean("select * from . where ext ="), Some("select * from . where ext = 'x'".to_string()));
+        assert_eq!(did_you_mean("select * from . limit"), Some("select * from . limit 1".to_string()));
+        assert_eq!(did_you_mean("select * from ."), None);
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(parse("explain pwd"), Ok(("", vec![Command::Explain(Box::new(Command::PrintDir))])));
+        match parse("EXPLAIN select * from . where ext = 'rs'") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Explain(inner) => assert!(matches!(**inner, Command::Select { .. })),
+                other => panic!("expected explain, got {:?}", other),
+            },
+            other => panic!("unexpected parse result: {:?}", other),
+        }
+    }
+
+    fn cond(col: &str, val: &str) -> ConditionNode {
+        ConditionNode::Condition(WhereClause::Equal(col.to_string(), val.to_string()))
+    }
+
+    fn where_of(input: &str) -> ConditionNode {
+        match parse(input) {
+            Ok((_, mut commands)) => match commands.remove(0) {
+                Command::Select { where_clause, .. } => where_clause.expect("missing where clause"),
+                other => panic!("unexpected command {:?}", other),
+            },
+            Err(e) => panic!("failed to parse {}: {}", input, e),
+        }
+    }
+
+    #[test]
+    fn test_not_negates_group() {
+        let node = where_of("select * from . where not (ext = \"tmp\" or is_hidden = true)");
+        let expected = ConditionNode::Not(Box::new(ConditionNode::Or(
+            Box::new(cond("ext", "tmp")),
+            Box::new(cond("is_hidden", "true")),
+        )));
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn test_not_binds_tighter_than_and() {
+        let node = where_of("select * where not name = 'a' and ext = 'rs'");
+        let expected = ConditionNode::And(
+            Box::new(ConditionNode::Not(Box::new(cond("name", "a")))),
+            Box::new(cond("ext", "rs")),
+        );
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        let and = |left, right| ConditionNode::And(Box::new(left), Box::new(right));
+        let or = |left, right| ConditionNode::Or(Box::new(left), Box::new(right));
+        let (a, b, c, d) = (cond("a", "1"), cond("b", "2"), cond("c", "3"), cond("d", "4"));
+
+        let expected = or(a.clone(), and(b.clone(), c.clone()));
+        assert_eq!(where_of("select * where a = 1 or b = 2 and c = 3"), expected);
+        let expected = or(and(a.clone(), b.clone()), c.clone());
+        assert_eq!(where_of("select * where a = 1 and b = 2 or c = 3"), expected);
+        // parentheses override precedence, however deeply nested
+        let expected = and(or(a.clone(), b.clone()), c.clone());
+        assert_eq!(where_of("select * where (a = 1 or b = 2) and c = 3"), expected);
+        let expected = and(a.clone(), or(b.clone(), and(c.clone(), d.clone())));
+        assert_eq!(where_of("select * where a = 1 and ((b = 2 or (c = 3 and d = 4)))"), expected);
+        // operators of one precedence group from the left
+        let expected = or(or(a.clone(), b.clone()), c.clone());
+        assert_eq!(where_of("select * where a = 1 or b = 2 or c = 3"), expected);
+        let expected = or(and(a, b), and(c, d));
+        assert_eq!(where_of("select * where a = 1 and b = 2 or c = 3 and d = 4"), expected);
+        assert!(parse("select * where (a = 1 or b = 2").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn test_not_requires_keyword_boundary() {
+        let node = where_of("select * where notes = 'x'");
+        assert_eq!(node, cond("notes", "x"));
+    }
+
+    #[test]
+    fn test_from_path_with_spaces() {
+        for input in [
+            "select * from ./My Documents where name = 'a'",
+            "select * from \"./My Documents\" where name = 'a'",
+        ] {
+            match parse(input) {
+                Ok(("", commands)) => match &commands[0] {
+                    Command::Select { from_paths, .. } => {
+                        assert_eq!(from_paths.as_deref(), Some(&[FromItem::Path("./My Documents".to_string())][..]))
+                    }
+                    other => panic!("unexpected command {:?}", other),
+                },
+                other => panic!("failed to parse {}: {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiple_from_paths() {
+        match parse("select * from ./src, \"./my tests\",./docs where ext = \"rs\"") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Select { from_paths, where_clause, .. } => {
+                    let expected = ["./src", "./my tests", "./docs"].map(|path| FromItem::Path(path.to_string()));
+                    assert_eq!(from_paths.as_deref(), Some(&expected[..]));
+                    assert_eq!(*where_clause, Some(cond("ext", "rs")));
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_depth_clause() {
+        match parse("select * from ./my dir depth 3 where ext = 'rs'") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Select { from_paths, depth, where_clause, .. } => {
+                    assert_eq!(from_paths.as_deref(), Some(&[FromItem::Path("./my dir".to_string())][..]));
+                    assert_eq!(*depth, Some(3));
+                    assert_eq!(*where_clause, Some(cond("ext", "rs")));
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exclude_clause() {
+        match parse("select * from . depth 5 exclude node_modules, \"*.min.js\" where ext = 'js'") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Select { depth, exclude, where_clause, .. } => {
+                    assert_eq!(*depth, Some(5));
+                    assert_eq!(exclude, &["node_modules".to_string(), "*.min.js".to_string()]);
+                    assert_eq!(*where_clause, Some(cond("ext", "js")));
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_duplicates_statement() {
+        assert_eq!(
+            parse("duplicates from ./a, ./b depth 3 where size > 1kb"),
+            Ok((
+                "",
+                vec![Command::Duplicates {
+                    from_paths: Some(vec![FromItem::Path("./a".to_string()), FromItem::Path("./b".to_string())]),
+                    depth: Some(3),
+                    exclude: Vec::new(),
+                    where_clause: Some(ConditionNode::Condition(WhereClause::GreaterThan(
+                        "size".to_string(),
+                        "1kb".to_string()
+                    ))),
+                }]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_delete_statement() {
+        match parse("delete from ./tmp exclude keep where ext = 'log'") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::DeleteFiles { permanent, from_paths, exclude, where_clause, .. } => {
+                    assert!(!permanent);
+                    assert_eq!(from_paths.as_deref(), Some(&[FromItem::Path("./tmp".to_string())][..]));
+                    assert_eq!(exclude, &["keep".to_string()]);
+                    assert_eq!(*where_clause, Some(cond("ext", "log")));
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+        match parse("DELETE PERMANENT WHERE ext = 'log'") {
+            Ok(("", commands)) => assert!(matches!(commands[0], Command::DeleteFiles { permanent: true, .. })),
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_type_selection() {
+        let selection = |query: &str| match parse(query) {
+            Ok(("", mut commands)) => match commands.remove(0) {
+                Command::Select { selection, .. } => selection,
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        };
+        assert_eq!(selection("select files from . where size > 1mb"), SelectionType::Files);
+        assert_eq!(selection("SELECT DIRS"), SelectionType::Directories);
+        assert_eq!(selection("select ** from ."), SelectionType::Every);
+        // a field list that starts with an unknown `files` is still a field list
+        assert!(matches!(selection("select files, name from ."), SelectionType::Fields(_)));
+    }
+
+    #[test]
+    fn test_select_recursive() {
+        let depth = |query: &str| match parse(query) {
+            Ok(("", mut commands)) => match commands.remove(0) {
+                Command::Select { depth, .. } => depth,
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        };
+        assert_eq!(depth("select recursive * from . where ext = 'rs'"), Some(UNLIMITED));
+        assert_eq!(depth("select r distinct ext"), Some(UNLIMITED));
+        assert_eq!(depth("select recursive name from . depth 2"), Some(2));
+        assert_eq!(depth("select r from ."), None);
+    }
+
+    #[test]
+    fn test_natural_ends_a_path() {
+        match parse("select * from ./src natural") {
+            Ok(("", mut commands)) => match commands.remove(0) {
+                Command::Select { from_paths, natural, .. } => {
+                    assert_eq!(from_paths.as_deref(), Some(&[FromItem::Path("./src".to_string())][..]));
+                    assert!(natural);
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exists_statement() {
+        match parse("exists from . depth 3 where name = 'Cargo.lock'") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Exists { from_paths, depth, where_clause, .. } => {
+                    assert_eq!(from_paths.as_deref(), Some(&[FromItem::Path(".".to_string())][..]));
+                    assert_eq!(*depth, Some(3));
+                    assert_eq!(*where_clause, Some(cond("name", "Cargo.lock")));
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_archive_in_from() {
+        match parse("select * from archive(\"logs.tar.gz\"), ./archive where size > 1mb") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Select { from_paths, .. } => assert_eq!(
+                    from_paths.as_deref(),
+                    Some(
+                        &[FromItem::Archive("logs.tar.gz".to_string()), FromItem::Path("./archive".to_string())][..]
+                    )
+                ),
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stdin_in_from() {
+        let from = |query: &str| match parse(query) {
+            Ok((_, mut commands)) => match commands.remove(0) {
+                Command::Select { from_paths, .. } => from_paths.unwrap(),
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        };
+        assert_eq!(from("select * from stdin where size > 1mb"), [FromItem::Stdin]);
+        assert_eq!(from("select name from STDIN, ./src"), [FromItem::Stdin, FromItem::Path("./src".to_string())]);
+        assert_eq!(from("select * from stdin/logs"), [FromItem::Path("stdin/logs".to_string())]);
+    }
+
+    #[test]
+    fn test_cd_path_with_comma() {
+        let expected = Command::ChangeDir { path: "a,b".to_string() };
+        assert_eq!(parse("cd a,b"), Ok(("", vec![expected])));
+    }
+
+    #[test]
+    fn test_cd_path_with_spaces() {
+        let expected = Command::ChangeDir {
+            path: "My Documents".to_string(),
+        };
+        assert_eq!(parse("cd My Documents"), Ok(("", vec![expected])));
+    }
+
+    #[test]
+    fn test_contains_operator() {
+        let node = where_of("select * where permissions contains \"x\"");
+        let expected = ConditionNode::Condition(WhereClause::Contains(
+            "permissions".to_string(),
+            "x".to_string(),
+        ));
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn test_escape_sequences_and_raw_strings() {
+        assert_eq!(
+            where_of(r#"select * where name = "say \"hi\"\tnow""#),
+            cond("name", "say \"hi\"\tnow")
+        );
+        assert_eq!(
+            where_of(r#"select * where path = r"C:\Users\me\new""#),
+            cond("path", r"C:\Users\me\new")
+        );
+        match parse(r"cd r'C:\Users\me\Documents'") {
+            Ok(("", commands)) => assert_eq!(
+                commands,
+                vec![Command::ChangeDir { path: r"C:\Users\me\Documents".to_string() }]
+            ),
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_multiple_statements_keep_their_source() {
+        let (remaining, statements) =
+            parse_statements("cd ./src; select * from . where ext = 'rs'; show;").unwrap();
+        assert_eq!(remaining, "");
+        let sources: Vec<&str> = statements.iter().map(|(source, _)| *source).collect();
+        assert_eq!(sources, vec!["cd ./src", "select * from . where ext = 'rs'", "show"]);
+        let (remaining, statements) = parse_statements("; pwd;; ; show ;;").unwrap();
+        assert_eq!((remaining, statements.len()), ("", 2));
+    }
+
+    fn selection_of(input: &str) -> SelectionType {
+        match parse(input) {
+            Ok(("", mut commands)) => match commands.remove(0) {
+                Command::Select { selection, .. } => selection,
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("failed to parse {}: {:?}", input, other),
+        }
+    }
+
+    #[test]
+    fn test_selection_types() {
+        assert_eq!(selection_of("select * from ."), SelectionType::All);
+        assert_eq!(
+            selection_of("select name, size from ."),
+            SelectionType::Fields(vec![SelectItem::field("name"), SelectItem::field("size")])
+        );
+        assert_eq!(
+            selection_of("select *, dir_size from ."),
+            SelectionType::AllWith(vec![SelectItem::field("dir_size")])
+        );
+    }
+
+    #[test]
+    fn test_constant_functions_in_conditions() {
+        let me = crate::files::current_user();
+        assert_eq!(where_of("select * where owner = me()"), cond("owner", &me));
+        assert_eq!(
+            where_of("select * where owner != ME ( )"),
+            ConditionNode::Condition(WhereClause::NotEqual("owner".to_string(), me))
+        );
+        // quoted, it is just text
+        assert_eq!(where_of("select * where owner = 'me()'"), cond("owner", "me()"));
+        assert!(parse("select * where owner = nobody()").map(|(rest, _)| rest.is_empty()) != Ok(true));
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(selection_of("select count(*) from . where ext = 'rs'"), SelectionType::Count);
+        assert_eq!(selection_of("SELECT COUNT ( * )"), SelectionType::Count);
+        // only count(*) counts; count(name) is an ordinary function call
+        assert_ne!(selection_of("select count(name)"), SelectionType::Count);
+    }
+
+    #[test]
+    fn test_limit_and_offset() {
+        let paging = |input: &str| match parse(input) {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Select { limit, offset, .. } => (*limit, *offset),
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("failed to parse {}: {:?}", input, other),
+        };
+        assert_eq!(paging("select * from . order by size limit 20 offset 40"), (Some(20), Some(40)));
+        assert_eq!(paging("select * from ./my dir offset 5"), (None, Some(5)));
+        assert_eq!(paging("select * limit 3"), (Some(3), None));
+        assert_eq!(paging("select * limit 10*2 offset (1 + 2) * 20"), (Some(20), Some(60)));
+        // units, fractions, negative results and overflow are not row counts
+        for input in ["select * limit 5mb", "select * limit 2.5", "select * limit 1 - 3", "select * limit 99999999999999999999999"] {
+            assert!(parse(input).map_or(true, |(rest, _)| !rest.is_empty()), "{}", input);
+        }
+        assert!(hint_for("limit 5mb").is_some());
+        // arithmetic that parses says what is wrong with its result
+        assert_eq!(row_count_error("limit 1 - 5"), Some((6, 5, "LIMIT cannot be negative".to_string())));
+        assert_eq!(row_count_error("limit 10/0 offset 1"), Some((6, 4, "division by zero in LIMIT".to_string())));
+        assert_eq!(row_count_error("offset (2 - 3) * 4").map(|(.., message)| message).as_deref(), Some("OFFSET cannot be negative"));
+        assert_eq!(row_count_error("limit 99999999999999999999999").map(|(.., message)| message).as_deref(), Some("LIMIT is too large"));
+        assert_eq!(row_count_error("limit 5mb"), None);
+        assert_eq!(row_count_error("limit 5"), None);
+    }
+
+    #[test]
+    fn test_select_distinct() {
+        match parse("select distinct ext from .") {
+            Ok(("", commands)) => match &commands[0] {
+                Command::Select { selection, distinct, .. } => {
+                    assert!(*distinct);
+                    assert_eq!(*selection, SelectionType::Fields(vec![SelectItem::field("ext")]));
+                }
+                other => panic!("unexpected command {:?}", other),
+            },
+            other => panic!("unexpected parse {:?}", other),
+        }
+        // a column that merely starts with the keyword is not DISTINCT
+        match parse("select distinctive from .") {
+            Ok(("", commands)) => assert!(matches!(commands[0], Command::Select { distinct: false, .. })),
+            other => panic!("unexpected parse {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_computed_columns() {
+        let field = |name: &str| Box::new(Expression::Field(name.to_string()));
+        let number = |n: &str| Box::new(Expression::Literal(n.to_string()));
+        assert_eq!(
+            selection_of("select name, size / 1024 as size_kb, upper(name) from ."),
+            SelectionType::Fields(vec![
+                SelectItem::field("name"),
+                SelectItem {
+                    expression: Expression::Arithmetic(field("size"), Operator::Divide, number("1024")),
+                    alias: Some("size_kb".to_string()),
+                },
+                SelectItem {
+                    expression: Expression::Function("upper".to_string(), vec![*field("name")]),
+                    alias: None,
+                },
+            ])
+        );
+        // `*` and `/` bind tighter than `+` and `-`
+        let SelectionType::Fields(items) = selection_of("select size + 1 * 2 - 3") else {
+            panic!("expected a field list");
+        };
+        assert_eq!(items[0].expression.to_string(), "size + 1 * 2 - 3");
+        assert_eq!(
+            items[0].expression,
+            Expression::Arithmetic(
+                Box::new(Expression::Arithmetic(
+                    field("size"),
+                    Operator::Add,
+                    Box::new(Expression::Arithmetic(number("1"), Operator::Multiply, number("2"))),
+                )),
+                Operator::Subtract,
+                number("3"),
+            )
+        );
+        let SelectionType::Fields(items) = selection_of("select (size + 1) * 2, size - (1 - 2)") else {
+            panic!("expected a field list");
+        };
+        assert_eq!(items[0].expression.to_string(), "(size + 1) * 2");
+        assert_eq!(items[1].expression.to_string(), "size - (1 - 2)");
+    }
+
+    #[test]
+    fn test_aliases() {
+        let (_, mut commands) = parse("select name as FileName, size as bytes, size / 2 as 'Half Size' from . order by bytes").unwrap();
+        let Command::Select { selection: SelectionType::Fields(items), order_by, .. } = commands.remove(0) else {
+            panic!("expected a select with fields");
+        };
+        let aliases: Vec<Option<&str>> = items.iter().map(|item| item.alias.as_deref()).collect();
+        assert_eq!(aliases, vec![Some("filename"), Some("bytes"), Some("Half Size")]);
+        // an alias of a plain field sorts by that field
+        assert_eq!(order_by, Some(vec!["size".to_string()]));
+
+        // a quoted alias is quoted in ORDER BY too
+        let (_, mut commands) = parse("select name as \"File Name\", size from . order by \"File Name\" desc").unwrap();
+        let Command::Select { order_by, ordering, .. } = commands.remove(0) else {
+            panic!("expected a select");
+        };
+        assert_eq!((order_by, ordering), (Some(vec!["name".to_string()]), Some(Ordering::Descending)));
+    }
+
+    #[test]
+    fn test_update_statement() {
+        let input = "update . set name = replace(name, \" \", \"_\"), modified = now() where ext = \"mp3\"";
+        let expected = Command::Update {
+            path: ".".to_string(),
+            assignments: vec![
+                (
+                    "name".to_string(),
+                    Expression::Function(
+                        "replace".to_string(),
+                        vec![
+                            Expression::Field("name".to_string()),
+                            Expression::Literal(" ".to_string()),
+                            Expression::Literal("_".to_string()),
+                        ],
+                    ),
+                ),
+                ("modified".to_string(), Expression::Function("now".to_string(), vec![])),
+            ],
+            where_clause: Some(cond("ext", "mp3")),
+        };
+        assert_eq!(parse(input), Ok(("", vec![expected])));
     }
 }
@@ -0,0 +1,362 @@
+// SELECT as a chain of stages: walk → stat → filter → enrich → sort/limit →
+// sink. Entries move through in batches: the walk collects a batch, dropping
+// entries SELECT FILES or SELECT DIRS leave out before anything reads them, then
+// stat (reading metadata), filter (the WHERE clause) and enrich (fields that
+// read file contents or subtrees) each split it across worker threads before
+// sort/limit takes what survives. Every stage reports its counts and time to
+// `stats`; the sink is the caller printing the table or writing parquet,
+// handed each row as it leaves sort/limit.
+//
+// LIMIT without ORDER BY stops the walk once it has enough rows, and with
+// --max-memory ORDER BY sorts through the spilling sorter.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::Instant;
+
+use crate::display::{self, Column};
+use crate::error::LsqlError;
+use crate::estimate;
+use crate::cli::Options;
+use crate::files::{self, FileInfo, FileType, SortKey};
+use crate::filter;
+use crate::fs::{self, Found};
+use crate::nice;
+use crate::parser::{Command, Ordering, SelectionType};
+use crate::progress::Progress;
+use crate::spill::Sorter;
+use crate::stats;
+use crate::State;
+
+// entries taken from the walk at a time
+const BATCH: usize = 1024;
+
+// fewest entries worth handing a thread of their own
+const PER_WORKER: usize = 64;
+
+fn workers() -> usize {
+    if nice::is_enabled() {
+        return 1;
+    }
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+// `f` over every item on up to `workers` threads, each mapping a contiguous
+// share of `items`; results come back in input order
+fn par_map<T, U, F>(mut items: Vec<T>, workers: usize, f: F) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    F: Fn(T) -> U + Sync,
+{
+    let workers = workers.min(items.len() / PER_WORKER).max(1);
+    if workers == 1 {
+        return items.into_iter().map(f).collect();
+    }
+    let share = items.len().div_ceil(workers);
+    let mut shares = Vec::new();
+    while !items.is_empty() {
+        let rest = items.split_off(share.min(items.len()));
+        shares.push(std::mem::replace(&mut items, rest));
+    }
+    let f = &f;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = shares
+            .into_iter()
+            .map(|share| scope.spawn(move || share.into_iter().map(f).collect::<Vec<U>>()))
+            .collect();
+        handles.into_iter().flat_map(|handle| handle.join().expect("pipeline worker panicked")).collect()
+    })
+}
+
+// where rows go as they leave sort/limit
+type Emit<'a> = dyn FnMut(FileInfo) -> Result<(), Box<dyn Error>> + 'a;
+
+// the sort/limit stage: sorts when asked, then applies DISTINCT, OFFSET and
+// LIMIT in that order. Rows are handed on rather than kept, so only the
+// sort's buffer counts against --max-memory
+struct Rows {
+    sorting: Option<Sorting>,
+    distinct: Option<(Vec<Column>, HashSet<Vec<String>>)>,
+    offset: usize,
+    skipped: usize,
+    limit: Option<usize>,
+    emitted: usize,
+}
+
+enum Sorting {
+    Memory(Vec<FileInfo>, Vec<SortKey>),
+    Spill(Sorter),
+}
+
+impl Rows {
+    fn new(command: &Command, options: &Options) -> Result<Self, Box<dyn Error>> {
+        let Command::Select { selection, distinct, limit, offset, .. } = command else {
+            return Err("not a select statement".into());
+        };
+        let sorting = match sort_keys(command, options) {
+            Some(keys) => {
+                if let Some(key) = keys.iter().find(|key| !files::is_field(&key.field)) {
+                    return Err(LsqlError::InvalidField(files::unknown_field(&key.field)).into());
+                }
+                Some(match options.max_memory {
+                    Some(budget) => Sorting::Spill(Sorter::new(&keys, budget)),
+                    None => Sorting::Memory(Vec::new(), keys),
+                })
+            }
+            None => None,
+        };
+        // rows are compared as displayed, so `select distinct ext` lists each extension once
+        let distinct = if *distinct { Some((display::columns(selection)?, HashSet::new())) } else { None };
+        Ok(Rows {
+            sorting,
+            distinct,
+            offset: offset.unwrap_or(0),
+            skipped: 0,
+            limit: *limit,
+            emitted: 0,
+        })
+    }
+
+    // false once LIMIT is met and nothing more is wanted
+    fn push(&mut self, file: FileInfo, emit: &mut Emit) -> Result<bool, Box<dyn Error>> {
+        match &mut self.sorting {
+            Some(Sorting::Memory(files, ..)) => {
+                files.push(file);
+                Ok(true)
+            }
+            Some(Sorting::Spill(sorter)) => {
+                sorter.push(file)?;
+                Ok(true)
+            }
+            None => self.take(file, emit),
+        }
+    }
+
+    fn take(&mut self, file: FileInfo, emit: &mut Emit) -> Result<bool, Box<dyn Error>> {
+        if self.limit.is_some_and(|limit| self.emitted >= limit) {
+            return Ok(false);
+        }
+        if let Some((columns, seen)) = &mut self.distinct {
+            let key = columns.iter().map(|column| column.cell(&file)).collect::<Result<Vec<String>, String>>()?;
+            if !seen.insert(key) {
+                return Ok(true);
+            }
+        }
+        if self.skipped < self.offset {
+            self.skipped += 1;
+            return Ok(true);
+        }
+        emit(file)?;
+        self.emitted += 1;
+        Ok(self.limit.is_none_or(|limit| self.emitted < limit))
+    }
+
+    // hands on what sorting held back; how many rows left the stage in all
+    fn finish(mut self, verbose: bool, emit: &mut Emit) -> Result<usize, Box<dyn Error>> {
+        let sorted: Box<dyn Iterator<Item = Result<FileInfo, Box<dyn Error>>>> = match self.sorting.take() {
+            None => return Ok(self.emitted),
+            Some(Sorting::Memory(mut files, keys)) => {
+                files.sort_by(|a, b| files::compare_keys(a, b, &keys));
+                Box::new(files.into_iter().map(Ok))
+            }
+            Some(Sorting::Spill(sorter)) => {
+                if verbose && sorter.spilled() > 0 {
+                    eprintln!("sorted in {} runs spilled to disk", sorter.spilled());
+                }
+                Box::new(sorter.finish()?.map(|file| file.map_err(Into::into)))
+            }
+        };
+        for file in sorted {
+            if !self.take(file?, emit)? {
+                break;
+            }
+        }
+        Ok(self.emitted)
+    }
+}
+
+// what a SELECT sorts on: its ORDER BY, or failing that `--sort`
+pub fn sort_keys(command: &Command, options: &Options) -> Option<Vec<SortKey>> {
+    let Command::Select { order_by, ordering, natural, .. } = command else {
+        return None;
+    };
+    let natural = *natural || options.natural_sort;
+    let keys: Vec<SortKey> = match order_by {
+        Some(fields) => {
+            let descending = *ordering == Some(Ordering::Descending);
+            fields.iter().map(|field| SortKey { field: field.clone(), descending, natural }).collect()
+        }
+        None if !options.sort.is_empty() => options.sort.iter().map(|key| SortKey { natural, ..key.clone() }).collect(),
+        None => return None,
+    };
+    Some(keys)
+}
+
+// run a SELECT through the stages and return the rows it produced
+pub fn select(state: &State, command: &Command) -> Result<Vec<FileInfo>, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    stream(state, command, &mut |file| {
+        rows.push(file);
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+// run a SELECT through the stages, handing each row to `emit` as soon as it
+// is known: during the walk without a sort, after it with one. Returns how
+// many rows there were
+pub fn stream(state: &State, command: &Command, emit: &mut Emit) -> Result<usize, Box<dyn Error>> {
+    let Command::Select { selection, where_clause, from_paths, depth, exclude, limit, distinct, .. } = command else {
+        return Err("not a select statement".into());
+    };
+    let options = &state.options;
+    let roots = from_paths.as_deref().map(|paths| fs::resolve_all_roots(state, paths)).transpose()?;
+    let settings = fs::settings_for(state, roots.as_deref());
+    let mut walk = fs::found(state, roots.as_deref(), *depth, exclude, true)?;
+    // expensive fields computed ahead on the workers: those the sort reads,
+    // and the selected ones too unless LIMIT will drop most rows unseen
+    let keys = sort_keys(command, options);
+    let sorts_on = |field: &str| keys.iter().flatten().any(|key| key.field.eq_ignore_ascii_case(field));
+    let enrich: Vec<&str> = files::EXPENSIVE_FIELDS
+        .iter()
+        .map(|(field, _)| *field)
+        .filter(|field| sorts_on(field) || ((limit.is_none() || *distinct) && estimate::mentions(command, field)))
+        .collect();
+    let detail = estimate::detail(command, options);
+    let kind = match selection {
+        SelectionType::Files => Some(FileType::File),
+        SelectionType::Directories => Some(FileType::Directory),
+        _ => None,
+    };
+    let workers = workers();
+    let mut rows = Rows::new(command, options)?;
+    let mut progress = Progress::new();
+    let (mut scanned, mut matched) = (0, 0);
+
+    loop {
+        let started = Instant::now();
+        let mut batch: Vec<Found> = walk.by_ref().take(BATCH).collect::<Result<_, _>>()?;
+        let (walked, walk_ended) = (batch.len(), batch.len() < BATCH);
+        // SELECT FILES and SELECT DIRS keep entries of their type only
+        if let Some(kind) = kind {
+            batch.retain(|found| found.file_type() == kind);
+        }
+        stats::stage("walk", walked, batch.len(), started.elapsed());
+        if batch.is_empty() {
+            if walk_ended {
+                break;
+            }
+            continue;
+        }
+
+        let (count, started) = (batch.len(), Instant::now());
+        // boxed errors cannot cross threads, so they come back as LsqlError
+        let stat = par_map(batch, workers, |found| found.stat(options, detail).map_err(LsqlError::from));
+        let batch = stat.into_iter().collect::<Result<Vec<FileInfo>, LsqlError>>()?;
+        stats::stage("stat", count, batch.len(), started.elapsed());
+
+        let batch = match where_clause {
+            Some(condition) => {
+                let (count, started) = (batch.len(), Instant::now());
+                let kept = par_map(batch, workers, |file| {
+                    filter::keep(&file, condition, &settings).map(|keep| keep.then_some(file))
+                });
+                let batch = kept.into_iter().filter_map(Result::transpose).collect::<Result<Vec<FileInfo>, LsqlError>>()?;
+                stats::stage("filter", count, batch.len(), started.elapsed());
+                batch
+            }
+            None => batch,
+        };
+        scanned += walked;
+        matched += batch.len();
+        progress.update(scanned, matched);
+
+        let batch = if enrich.is_empty() {
+            batch
+        } else {
+            let (count, started) = (batch.len(), Instant::now());
+            let batch = par_map(batch, workers, |file| {
+                for field in &enrich {
+                    file.display_field(field);
+                }
+                file
+            });
+            stats::stage("enrich", count, batch.len(), started.elapsed());
+            batch
+        };
+
+        let (count, before, started) = (batch.len(), rows.emitted, Instant::now());
+        let mut wanted = true;
+        for file in batch {
+            if !rows.push(file, emit)? {
+                wanted = false;
+                break;
+            }
+        }
+        // sorted rows leave this stage in `finish`
+        stats::stage("sort/limit", count, rows.emitted - before, started.elapsed());
+        if !wanted {
+            break;
+        }
+    }
+
+    progress.clear();
+    let (before, started) = (rows.emitted, Instant::now());
+    let emitted = rows.finish(options.verbose, emit)?;
+    stats::stage("sort/limit", 0, emitted - before, started.elapsed());
+    Ok(emitted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_map_keeps_order() {
+        let items: Vec<usize> = (0..1000).collect();
+        assert_eq!(par_map(items.clone(), 4, |n| n * 2), items.iter().map(|n| n * 2).collect::<Vec<_>>());
+        assert_eq!(par_map(vec![1, 2], 8, |n| n + 1), [2, 3]);
+    }
+
+    #[test]
+    fn test_max_memory_spills_instead_of_failing() {
+        let dir = crate::testing::TempDir::new("budget");
+        for name in ["c", "a", "d", "b"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let options = Options { max_memory: Some(1), ..Default::default() };
+        let state = State::at(&dir, options).unwrap();
+        let command = crate::parser::parse("select name from . order by name").unwrap().1.remove(0);
+        let names: Vec<String> = select(&state, &command).unwrap().into_iter().map(|file| file.name).collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_listing_reads_only_what_a_statement_uses() {
+        let dir = crate::testing::TempDir::new("listing-detail");
+        std::fs::write(dir.join("a.txt"), "12345").unwrap();
+        let state = State::at(&dir, Options::default()).unwrap();
+        let first = |query: &str| select(&state, &crate::parser::parse(query).unwrap().1.remove(0)).unwrap().remove(0);
+        // names come with the listing: no stat, so no size
+        assert_eq!((first("select name").name, first("select name").size), ("a.txt".to_string(), 0));
+        let sized = first("select name, size");
+        assert_eq!((sized.size, sized.owner.as_str()), (5, ""));
+        #[cfg(unix)]
+        assert!(!first("select owner").owner.is_empty());
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let command = |query: &str| crate::parser::parse(query).unwrap().1.remove(0);
+        let options = Options { sort: SortKey::parse_list("size:desc,name").unwrap(), ..Default::default() };
+        let keys = sort_keys(&command("select * from ."), &options).unwrap();
+        assert_eq!(keys, options.sort);
+        // ORDER BY wins over --sort
+        let keys = sort_keys(&command("select * from . order by ext desc"), &options).unwrap();
+        assert_eq!(keys, [SortKey { field: "ext".to_string(), descending: true, natural: false }]);
+        let keys = sort_keys(&command("select * from . order by name natural"), &options).unwrap();
+        assert!(keys[0].natural);
+        assert_eq!(sort_keys(&command("select * from ."), &Options::default()), None);
+    }
+}
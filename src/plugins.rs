@@ -0,0 +1,235 @@
+// user field providers loaded from `<config dir>/plugins`
+//
+// Every `<field>.field` file in the plugin directory defines one extra field.
+// The file holds `key = value` lines:
+//
+//     description = lines changed since the last commit
+//     command = git diff --numstat HEAD --
+//
+// The command runs without a shell, with the entry's path appended as its
+// last argument, and its trimmed stdout becomes the field's text value.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+use crate::files::{FileInfo, FIELDS};
+
+pub trait FieldProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn value(&self, file: &FileInfo) -> Option<String>;
+}
+
+pub struct CommandField {
+    name: String,
+    description: String,
+    program: String,
+    args: Vec<String>,
+    // keyed by path so sorting does not rerun the command for every comparison
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl FieldProvider for CommandField {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn value(&self, file: &FileInfo) -> Option<String> {
+        if let Some(value) = self.cache.lock().ok()?.get(&file.path) {
+            return value.clone();
+        }
+        // the lock is not held while the command runs, so the pipeline's
+        // workers run it for their entries side by side
+        let value = Command::new(&self.program)
+            .args(&self.args)
+            .arg(&file.path)
+            .output()
+            .ok()
+            .and_then(|output| {
+                output
+                    .status
+                    .success()
+                    .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            });
+        self.cache.lock().ok()?.insert(file.path.clone(), value.clone());
+        value
+    }
+}
+
+pub struct LoadReport {
+    pub directory: Option<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+static PROVIDERS: OnceLock<Vec<Box<dyn FieldProvider>>> = OnceLock::new();
+static REPORT: OnceLock<LoadReport> = OnceLock::new();
+
+pub fn parse_plugin(name: &str, contents: &str) -> Result<CommandField, String> {
+    let mut description = String::new();
+    let mut command = None;
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+        match key.trim() {
+            "description" => description = value.trim().to_string(),
+            "command" => command = Some(value.trim().to_string()),
+            other => return Err(format!("line {}: unknown key '{}'", number + 1, other)),
+        }
+    }
+    let command = command.ok_or("missing `command`")?;
+    let mut words = command.split_whitespace().map(str::to_string);
+    let program = words.next().ok_or("`command` is empty")?;
+    Ok(CommandField {
+        name: name.to_string(),
+        description,
+        program,
+        args: words.collect(),
+        cache: Mutex::new(HashMap::new()),
+    })
+}
+
+fn load_dir(dir: &Path) -> (Vec<Box<dyn FieldProvider>>, Vec<String>) {
+    let mut providers: Vec<Box<dyn FieldProvider>> = Vec::new();
+    let mut errors = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (providers, errors);
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "field"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if FIELDS.contains(&name.as_str()) || providers.iter().any(|p| p.name() == name) {
+            errors.push(format!("{}: field '{}' already exists", path.display(), name));
+            continue;
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| parse_plugin(&name, &contents))
+        {
+            Ok(provider) => providers.push(Box::new(provider)),
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (providers, errors)
+}
+
+// load the plugin directory once; later calls are no-ops
+pub fn load() {
+    let directory = crate::config::config_dir().map(|dir| dir.join("plugins"));
+    let (providers, errors) = match &directory {
+        Some(dir) => load_dir(dir),
+        None => (Vec::new(), Vec::new()),
+    };
+    let _ = PROVIDERS.set(providers);
+    let _ = REPORT.set(LoadReport { directory, errors });
+}
+
+pub fn providers() -> &'static [Box<dyn FieldProvider>] {
+    PROVIDERS.get().map(Vec::as_slice).unwrap_or(&[])
+}
+
+pub fn find(name: &str) -> Option<&'static dyn FieldProvider> {
+    providers()
+        .iter()
+        .find(|p| p.name().eq_ignore_ascii_case(name))
+        .map(|p| p.as_ref())
+}
+
+// what `lsql plugins list` prints
+pub fn list() -> String {
+    let mut out = String::new();
+    match REPORT.get().and_then(|report| report.directory.as_ref()) {
+        Some(dir) => out.push_str(&format!("plugin directory: {}\n", dir.display())),
+        None => out.push_str("plugin directory: unknown (no home directory)\n"),
+    }
+    if providers().is_empty() {
+        out.push_str("no plugins loaded\n");
+    }
+    for provider in providers() {
+        out.push_str(&format!("  {:<16} {}\n", provider.name(), provider.description()));
+    }
+    for error in REPORT.get().map(|r| r.errors.as_slice()).unwrap_or(&[]) {
+        out.push_str(&format!("error: {}\n", error));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plugin() {
+        let plugin = parse_plugin("first_line", "# comment\ndescription = first line\ncommand = head -n 1\n").unwrap();
+        assert_eq!(plugin.name(), "first_line");
+        assert_eq!(plugin.description(), "first line");
+        assert_eq!(plugin.program, "head");
+        assert_eq!(plugin.args, vec!["-n", "1"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commands_run_side_by_side() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = crate::testing::TempDir::new("plugin");
+        let script = dir.join("slow");
+        std::fs::write(&script, "#!/bin/sh\nsleep 0.3\necho \"$1\"\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let plugin = parse_plugin("slow", &format!("command = {}", script.display())).unwrap();
+        let file = |name: &str| FileInfo {
+            size: 0,
+            modified: chrono::Utc::now(),
+            accessed: chrono::Utc::now(),
+            name: name.to_string(),
+            file_type: crate::files::FileType::File,
+            path: name.to_string(),
+            owner: String::new(),
+            group: String::new(),
+            mode: 0o644,
+            root: ".".to_string(),
+            compressed_size: None,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        };
+        let started = std::time::Instant::now();
+        std::thread::scope(|scope| {
+            for name in ["a", "b", "c", "d"] {
+                let plugin = &plugin;
+                scope.spawn(move || assert_eq!(plugin.value(&file(name)).as_deref(), Some(name)));
+            }
+        });
+        // four runs one after another would take 1.2s
+        assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+        assert_eq!(plugin.cache.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_parse_plugin_errors() {
+        assert!(parse_plugin("x", "description = nothing to run").is_err());
+        assert!(parse_plugin("x", "command").is_err());
+        assert!(parse_plugin("x", "colour = red\ncommand = true").is_err());
+    }
+}
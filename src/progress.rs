@@ -0,0 +1,75 @@
+// A live `scanned 120k entries, 8k matched` line on stderr while a long walk
+// runs. It only appears once a statement has taken a second, only on a
+// terminal, and is wiped before results are printed (or an error is).
+
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+// how long a walk runs before the line appears, and how often it is redrawn
+const DELAY: Duration = Duration::from_secs(1);
+const REDRAW: Duration = Duration::from_millis(100);
+
+pub struct Progress {
+    started: Instant,
+    drawn: Option<Instant>,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Progress { started: Instant::now(), drawn: None, enabled: std::io::stderr().is_terminal() }
+    }
+
+    pub fn update(&mut self, scanned: usize, matched: usize) {
+        let now = Instant::now();
+        if !self.enabled || now - self.started < DELAY || self.drawn.is_some_and(|drawn| now - drawn < REDRAW) {
+            return;
+        }
+        eprint!("\r\x1b[2Kscanned {} entries, {} matched", abbreviate(scanned), abbreviate(matched));
+        let _ = std::io::stderr().flush();
+        self.drawn = Some(now);
+    }
+
+    pub fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// 950, 12.3k, 120k, 4.5M
+fn abbreviate(count: usize) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=99_999 => format!("{:.1}k", count as f64 / 1e3).replace(".0k", "k"),
+        100_000..=999_999 => format!("{}k", count / 1_000),
+        _ => format!("{:.1}M", count as f64 / 1e6).replace(".0M", "M"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abbreviate() {
+        assert_eq!(abbreviate(950), "950");
+        assert_eq!(abbreviate(12_345), "12.3k");
+        assert_eq!(abbreviate(8_000), "8k");
+        assert_eq!(abbreviate(120_400), "120k");
+        assert_eq!(abbreviate(4_500_000), "4.5M");
+    }
+}
@@ -0,0 +1,53 @@
+// `--emit-script`: the shell commands equivalent to a mutating statement, so
+// cautious users can review them and run them with their own tooling instead
+// of letting lsql change anything.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+pub const HEADER: &str = "#!/bin/sh\nset -e";
+
+// single quoted for a POSIX shell; a quote inside becomes '\''
+pub fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn quote_path(path: &Path) -> String {
+    quote(&path.to_string_lossy())
+}
+
+pub fn mv(from: &Path, to: &Path) -> String {
+    format!("mv -n -- {} {}", quote_path(from), quote_path(to))
+}
+
+pub fn rm(path: &Path, is_dir: bool) -> String {
+    let flags = if is_dir { "-rf" } else { "-f" };
+    format!("rm {} -- {}", flags, quote_path(path))
+}
+
+// `touch -t` is the portable form; TZ pins the stamp to UTC
+pub fn touch(path: &Path, modified: DateTime<Utc>) -> String {
+    format!("TZ=UTC touch -m -t {} -- {}", modified.format("%Y%m%d%H%M.%S"), quote_path(path))
+}
+
+// lines that are not commands, such as the summary
+pub fn comment(text: &str) -> String {
+    text.lines().map(|line| format!("# {}", line)).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_commands_quote_paths() {
+        assert_eq!(quote("it's here"), r"'it'\''s here'");
+        assert_eq!(mv(Path::new("./a b"), Path::new("./a_b")), "mv -n -- './a b' './a_b'");
+        let date = Utc.with_ymd_and_hms(2024, 1, 2, 15, 30, 5).unwrap();
+        assert_eq!(touch(Path::new("x"), date), "TZ=UTC touch -m -t 202401021530.05 -- 'x'");
+        assert_eq!(rm(Path::new("build"), true), "rm -rf -- 'build'");
+        assert_eq!(comment("2 entries\nok"), "# 2 entries\n# ok");
+    }
+}
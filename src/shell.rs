@@ -0,0 +1,415 @@
+// the interactive shell: line editing, history and tab completion
+
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+#[cfg(feature = "shell")]
+use std::path::PathBuf;
+
+use colored::Colorize;
+#[cfg(feature = "shell")]
+use rustyline::completion::{Completer, Pair};
+#[cfg(feature = "shell")]
+use rustyline::error::ReadlineError;
+#[cfg(feature = "shell")]
+use rustyline::highlight::Highlighter;
+#[cfg(feature = "shell")]
+use rustyline::hint::Hinter;
+#[cfg(feature = "shell")]
+use rustyline::history::DefaultHistory;
+#[cfg(feature = "shell")]
+use rustyline::validate::Validator;
+#[cfg(feature = "shell")]
+use rustyline::{Context, Editor, Helper};
+
+use crate::cli::OutputFormat;
+use crate::error::LsqlError;
+#[cfg(feature = "shell")]
+use crate::files::FIELDS;
+use crate::parser::{self, Command, SelectionType};
+#[cfg(feature = "shell")]
+use crate::parser::KEYWORDS;
+use crate::{display, report_error, run_input, State};
+
+// ask a yes/no question on the terminal; None when there is no terminal to ask on
+pub fn ask(question: &str) -> Option<bool> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    eprint!("{} [y/N] ", question);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    // Ctrl+C while waiting is a no
+    if std::io::stdin().lock().read_line(&mut answer).is_err() || crate::cancel::interrupted() {
+        return Some(false);
+    }
+    Some(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(feature = "shell")]
+pub struct LsqlHelper {
+    // directory that relative paths are completed against
+    cwd: PathBuf,
+}
+
+#[cfg(feature = "shell")]
+impl LsqlHelper {
+    fn complete_word(&self, word: &str) -> Vec<Pair> {
+        let lowercase = word.chars().all(|c| !c.is_uppercase()) && !word.is_empty();
+        let keywords = KEYWORDS.iter().map(|k| {
+            if lowercase {
+                k.to_lowercase()
+            } else {
+                k.to_string()
+            }
+        });
+        keywords
+            .chain(FIELDS.iter().map(|f| f.to_string()))
+            .filter(|candidate| candidate.to_lowercase().starts_with(&word.to_lowercase()))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect()
+    }
+
+    fn complete_path(&self, word: &str) -> Vec<Pair> {
+        let (dir, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => ("", word),
+        };
+        let base = if dir.is_empty() {
+            self.cwd.clone()
+        } else {
+            self.cwd.join(Path::new(dir))
+        };
+        let Ok(entries) = std::fs::read_dir(base) else {
+            return Vec::new();
+        };
+        let mut candidates: Vec<Pair> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                    return None;
+                }
+                let suffix = if entry.path().is_dir() { "/" } else { "" };
+                Some(Pair {
+                    display: format!("{}{}", name, suffix),
+                    replacement: format!("{}{}{}", dir, name, suffix),
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates
+    }
+}
+
+#[cfg(feature = "shell")]
+impl Completer for LsqlHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let path_start = before.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let previous = before[..path_start].split_whitespace().last().unwrap_or("");
+        if previous.eq_ignore_ascii_case("FROM") || previous.eq_ignore_ascii_case("CD") {
+            return Ok((path_start, self.complete_path(&before[path_start..])));
+        }
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || c == ',' || c == '(')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        Ok((start, self.complete_word(&before[start..])))
+    }
+}
+
+#[cfg(feature = "shell")]
+impl Hinter for LsqlHelper {
+    type Hint = String;
+}
+
+#[cfg(feature = "shell")]
+impl Highlighter for LsqlHelper {}
+
+#[cfg(feature = "shell")]
+impl Validator for LsqlHelper {}
+
+#[cfg(feature = "shell")]
+impl Helper for LsqlHelper {}
+
+// `> file` or `>> file` at the end of a shell line
+#[derive(Debug, PartialEq)]
+struct Redirect<'a> {
+    path: &'a str,
+    append: bool,
+}
+
+// `source queries.lsql`: the script to run, quoted or not
+fn source(input: &str) -> Option<&str> {
+    let (command, path) = input.split_once(char::is_whitespace)?;
+    if !command.eq_ignore_ascii_case("source") {
+        return None;
+    }
+    let path = path.trim();
+    let path = ['"', '\''].iter().find_map(|&q| path.strip_prefix(q).and_then(|rest| rest.strip_suffix(q))).unwrap_or(path);
+    (!path.is_empty()).then_some(path)
+}
+
+// split `query > file` into the query and where its rows go. The last `>`
+// outside quotes only redirects when the text before it is a whole query,
+// so `where size > 1gb` stays a comparison
+fn redirection(input: &str) -> Option<(&str, Redirect<'_>)> {
+    let bytes = input.as_bytes();
+    let mut quote = None;
+    let mut last = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') if !matches!(bytes.get(i + 1), Some(b'=' | b'>')) => last = Some(i),
+            _ => {}
+        }
+    }
+    let at = last?;
+    let append = at > 0 && bytes[at - 1] == b'>';
+    let query = input[..if append { at - 1 } else { at }].trim();
+    let target = input[at + 1..].trim();
+    let path = ['"', '\'']
+        .iter()
+        .find_map(|&q| target.strip_prefix(q).and_then(|rest| rest.strip_suffix(q)))
+        .unwrap_or(target);
+    if path.is_empty() || (path == target && path.contains(char::is_whitespace)) {
+        return None;
+    }
+    crate::parse_query(query).ok()?;
+    Some((query, Redirect { path, append }))
+}
+
+// run `query` with the rows of each SELECT written to the redirect's file
+fn run_redirected(state: &mut State, query: &str, redirect: &Redirect) -> Result<(), LsqlError> {
+    selects_rows(query, "a file")?;
+    // `>` empties the file once, so every statement on the line ends up in it
+    if !redirect.append {
+        std::fs::File::create(state.path.join(redirect.path))?;
+    }
+    let saved = (state.options.output.replace(redirect.path.to_string()), state.options.append);
+    state.options.append = true;
+    let result = run_input(state, query);
+    (state.options.output, state.options.append) = saved;
+    result
+}
+
+// split `query | command` at the first `|` outside quotes that follows a
+// whole query; the command is everything after it, pipes and all
+fn pipe(input: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '|') => {
+                let (query, command) = (input[..i].trim(), input[i + 1..].trim());
+                if !command.is_empty() && crate::parse_query(query).is_ok() {
+                    return Some((query, command));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// only statements that produce rows can have them sent somewhere else
+fn selects_rows(query: &str, target: &str) -> Result<Vec<Command>, LsqlError> {
+    let statements = crate::parse_query(query)?;
+    let rows = |command: &Command| matches!(command, Command::Select { selection, .. } if *selection != SelectionType::Count);
+    if !statements.iter().all(rows) {
+        return Err(LsqlError::Usage(format!("only SELECT results can be sent to {}", target)));
+    }
+    Ok(statements)
+}
+
+// run `query`, streaming its rows one per line (cells separated by tabs)
+// into the standard input of `command`, which runs under the system shell
+fn run_piped(state: &mut State, query: &str, command: &str) -> Result<(), LsqlError> {
+    let statements = selects_rows(query, "a command")?;
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut child = std::process::Command::new(shell)
+        .args([flag, command])
+        .current_dir(&state.path)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+    let mut sink = display::LinesSink::new(child.stdin.take().ok_or("could not write to the command")?);
+    let mut result = Ok(());
+    for statement in &statements {
+        crate::cancel::start(state.options.timeout);
+        result = crate::execute_into(state, statement, &mut sink);
+        if result.is_err() {
+            break;
+        }
+    }
+    drop(sink);
+    let status = child.wait()?;
+    if let Err(e) = crate::cache::save() {
+        eprintln!("warning: could not write the metadata cache: {}", e);
+    }
+    match result {
+        // the command stopped reading, as `head` does; that is its call
+        Err(LsqlError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        result => result?,
+    }
+    // the command may have changed what SHOW lists
+    *state = state.set_path(&state.path)?;
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(code) => Err(LsqlError::Failed(format!("`{}` exited with status {}", command, code))),
+        None => Err(LsqlError::Failed(format!("`{}` was stopped by a signal", command))),
+    }
+}
+
+fn start(state: &State) {
+    crate::cancel::install();
+    if !state.options.no_pager && std::io::stdout().is_terminal() {
+        crate::pager::enable();
+    }
+    println!("type `help` for statements and help topics");
+}
+
+fn prompt(state: &State) -> String {
+    println!("current directory: {}", state.get_abs_path());
+    format!("{} ", "lsql> ".green())
+}
+
+// one line typed at the prompt: a script to source, a statement whose rows go
+// to a file or a command, or statements to run
+fn handle(state: &mut State, input: &str) {
+    let result = match (source(input), redirection(input)) {
+        (Some(path), _) => crate::run_file(state, Path::new(path)),
+        (None, Some((query, redirect))) => run_redirected(state, query, &redirect),
+        (None, None) => match pipe(input) {
+            Some((query, command)) => run_piped(state, query, command),
+            None => run_input(state, input),
+        },
+    };
+    if let Err(e) = result {
+        report_error(state.options.format, &e);
+        if matches!(e, LsqlError::Parse { .. }) && !matches!(state.options.format, OutputFormat::Json | OutputFormat::Ndjson) {
+            if let Some(fixed) = parser::did_you_mean(input) {
+                eprintln!("did you mean: {}?", fixed.bold());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "shell")]
+pub fn run(state: &mut State) -> ! {
+    let mut editor: Editor<LsqlHelper, DefaultHistory> = Editor::new().expect("Failed to start the shell");
+    editor.set_helper(Some(LsqlHelper { cwd: state.path.clone() }));
+    start(state);
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.cwd = state.path.clone();
+        }
+        match editor.readline(&prompt(state)) {
+            Ok(line) => {
+                let input = line.trim();
+                if input.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(input);
+                handle(state, input);
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => std::process::exit(0),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// without the `shell` feature lines are read as typed, with no editing,
+// history or completion
+#[cfg(not(feature = "shell"))]
+pub fn run(state: &mut State) -> ! {
+    start(state);
+    let stdin = std::io::stdin();
+    loop {
+        print!("{}", prompt(state));
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => std::process::exit(0),
+            Ok(_) => {
+                let input = line.trim();
+                if !input.is_empty() {
+                    handle(state, input);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "shell")]
+    fn replacements(pairs: Vec<Pair>) -> Vec<String> {
+        pairs.into_iter().map(|p| p.replacement).collect()
+    }
+
+    #[cfg(feature = "shell")]
+    #[test]
+    fn test_complete_keywords_and_fields() {
+        let helper = LsqlHelper { cwd: PathBuf::from(".") };
+        assert_eq!(replacements(helper.complete_word("sel")), vec!["select"]);
+        assert_eq!(replacements(helper.complete_word("WH")), vec!["WHERE"]);
+        assert_eq!(replacements(helper.complete_word("perm")), vec!["permanent", "permissions"]);
+    }
+
+    #[test]
+    fn test_redirection() {
+        let redirect = |path, append| Some(Redirect { path, append });
+        let split = |input| redirection(input).map(|(_, redirect)| redirect);
+        assert_eq!(split("select * from . where size > 1gb > big.txt"), redirect("big.txt", false));
+        assert_eq!(split("select path from . >> 'my list.txt'"), redirect("my list.txt", true));
+        assert_eq!(redirection("select * from . > out").map(|(query, _)| query), Some("select * from ."));
+        // comparisons are left alone
+        assert_eq!(split("select * from . where size > 1gb"), None);
+        assert_eq!(split("select * from . where name >= 'm'"), None);
+        assert_eq!(split("select * from . where name = 'a > b'"), None);
+        assert_eq!(split("select * from . > two words"), None);
+    }
+
+    #[test]
+    fn test_source() {
+        assert_eq!(source("source queries.lsql"), Some("queries.lsql"));
+        assert_eq!(source("SOURCE \"my queries.lsql\""), Some("my queries.lsql"));
+        assert_eq!(source("select * from source"), None);
+    }
+
+    #[test]
+    fn test_pipe() {
+        assert_eq!(pipe("select path from . where ext = \"log\" | xargs rm"), Some(("select path from . where ext = \"log\"", "xargs rm")));
+        assert_eq!(pipe("select name from . | sort | head -3"), Some(("select name from .", "sort | head -3")));
+        assert_eq!(pipe("select name from . where name = 'a|b'"), None);
+        assert_eq!(pipe("select name from . |"), None);
+    }
+
+    #[cfg(feature = "shell")]
+    #[test]
+    fn test_complete_paths() {
+        let helper = LsqlHelper { cwd: PathBuf::from(env!("CARGO_MANIFEST_DIR")) };
+        assert_eq!(replacements(helper.complete_path("sr")), vec!["src/"]);
+        assert!(replacements(helper.complete_path("src/pa")).contains(&"src/parser.rs".to_string()));
+    }
+}
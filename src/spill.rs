@@ -0,0 +1,332 @@
+// Sorting within a memory budget (`--max-memory`).
+//
+// Entries are buffered until the budget is used up, then sorted and written
+// to a temporary run file. When the input ends the runs are merged back in
+// order, so only one entry per run is held in memory while merging.
+//
+// Run files live in a directory of their own under the temporary directory,
+// readable by this user only and created fresh, so nobody else can plant a
+// file or symlink where a run is about to be written.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{DateTime, Utc};
+
+use crate::files::{compare_keys, FileInfo, FileType, SortKey};
+
+static DIRS: AtomicUsize = AtomicUsize::new(0);
+
+// the private directory a sort's runs go in, removed with them when dropped
+struct SpillDir(PathBuf);
+
+impl SpillDir {
+    fn create() -> io::Result<Self> {
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        loop {
+            let dir = std::env::temp_dir().join(format!(
+                "lsql-spill-{}-{}",
+                std::process::id(),
+                DIRS.fetch_add(1, Ordering::Relaxed)
+            ));
+            // a name someone else took first is passed over, never reused
+            match builder.create(&dir) {
+                Ok(()) => return Ok(SpillDir(dir)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub struct Sorter {
+    keys: Vec<SortKey>,
+    budget: u64,
+    buffer: Vec<FileInfo>,
+    buffered: u64,
+    dir: Option<SpillDir>,
+    runs: Vec<PathBuf>,
+}
+
+impl Sorter {
+    pub fn new(keys: &[SortKey], budget: u64) -> Self {
+        Sorter {
+            keys: keys.to_vec(),
+            budget,
+            buffer: Vec::new(),
+            buffered: 0,
+            dir: None,
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, file: FileInfo) -> io::Result<()> {
+        self.buffered += file.estimated_size();
+        self.buffer.push(file);
+        if self.buffered > self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    // how many runs went to disk
+    pub fn spilled(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn sort_buffer(&mut self) {
+        let keys = &self.keys;
+        self.buffer.sort_by(|a, b| compare_keys(a, b, keys));
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.sort_buffer();
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => self.dir.insert(SpillDir::create()?),
+        };
+        let path = dir.path().join(format!("run-{}", self.runs.len()));
+        let mut out = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(&path)?);
+        self.runs.push(path);
+        for file in self.buffer.drain(..) {
+            writeln!(out, "{}", encode(&file))?;
+        }
+        out.flush()?;
+        self.buffered = 0;
+        Ok(())
+    }
+
+    // every pushed entry, in order
+    pub fn finish(mut self) -> io::Result<Box<dyn Iterator<Item = io::Result<FileInfo>>>> {
+        if self.runs.is_empty() {
+            self.sort_buffer();
+            return Ok(Box::new(std::mem::take(&mut self.buffer).into_iter().map(Ok)));
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        let mut merge = Merge {
+            readers: Vec::new(),
+            heads: Vec::new(),
+            keys: std::mem::take(&mut self.keys),
+            _dir: self.dir.take(),
+        };
+        for run in &self.runs {
+            let mut lines = BufReader::new(File::open(run)?).lines();
+            merge.heads.push(next_entry(&mut lines)?);
+            merge.readers.push(lines);
+        }
+        Ok(Box::new(merge))
+    }
+}
+
+struct Merge {
+    readers: Vec<Lines<BufReader<File>>>,
+    heads: Vec<Option<FileInfo>>,
+    keys: Vec<SortKey>,
+    // the runs are removed once the merge is done with them
+    _dir: Option<SpillDir>,
+}
+
+impl Iterator for Merge {
+    type Item = io::Result<FileInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // on ties the earlier run wins, which keeps the sort stable
+        let mut smallest: Option<usize> = None;
+        for (index, head) in self.heads.iter().enumerate() {
+            let Some(head) = head else { continue };
+            let better = match smallest.and_then(|s| self.heads[s].as_ref()) {
+                Some(current) => compare_keys(head, current, &self.keys).is_lt(),
+                None => true,
+            };
+            if better {
+                smallest = Some(index);
+            }
+        }
+        let index = smallest?;
+        let next = match next_entry(&mut self.readers[index]) {
+            Ok(next) => next,
+            Err(e) => return Some(Err(e)),
+        };
+        std::mem::replace(&mut self.heads[index], next).map(Ok)
+    }
+}
+
+fn next_entry(lines: &mut Lines<BufReader<File>>) -> io::Result<Option<FileInfo>> {
+    match lines.next() {
+        Some(line) => decode(&line?).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+pub fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// a date as seconds and nanoseconds, which covers every date chrono can hold
+fn encode_date(date: &DateTime<Utc>) -> String {
+    format!("{}.{:09}", date.timestamp(), date.timestamp_subsec_nanos())
+}
+
+fn decode_date(text: &str) -> Option<DateTime<Utc>> {
+    let (seconds, nanos) = text.split_once('.')?;
+    DateTime::from_timestamp(seconds.parse().ok()?, nanos.parse().ok()?)
+}
+
+// one tab separated line per entry
+pub fn encode(file: &FileInfo) -> String {
+    [
+        file.size.to_string(),
+        encode_date(&file.modified),
+        encode_date(&file.accessed),
+        escape(&file.name),
+        file.file_type.as_str().to_string(),
+        escape(&file.path),
+        escape(&file.owner),
+        escape(&file.group),
+        file.mode.to_string(),
+        escape(&file.root),
+        file.compressed_size.map(|size| size.to_string()).unwrap_or_default(),
+        file.attributes.to_string(),
+        file.inode.to_string(),
+        file.nlink.to_string(),
+    ]
+    .join("\t")
+}
+
+pub fn decode(line: &str) -> io::Result<FileInfo> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "corrupt spill file");
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [size, modified, accessed, name, file_type, path, owner, group, mode, root, compressed_size, attributes, inode, nlink] = fields[..] else {
+        return Err(invalid());
+    };
+    Ok(FileInfo {
+        size: size.parse().map_err(|_| invalid())?,
+        modified: decode_date(modified).ok_or_else(invalid)?,
+        accessed: decode_date(accessed).ok_or_else(invalid)?,
+        name: unescape(name),
+        file_type: match file_type {
+            "dir" => FileType::Directory,
+            "file" => FileType::File,
+            _ => FileType::Other,
+        },
+        path: unescape(path),
+        owner: unescape(owner),
+        group: unescape(group),
+        mode: mode.parse().map_err(|_| invalid())?,
+        root: unescape(root),
+        compressed_size: match compressed_size {
+            "" => None,
+            size => Some(size.parse().map_err(|_| invalid())?),
+        },
+        attributes: attributes.parse().map_err(|_| invalid())?,
+        inode: inode.parse().map_err(|_| invalid())?,
+        nlink: nlink.parse().map_err(|_| invalid())?,
+        dir_size: Default::default(),
+        mime: Default::default(),
+        digests: Default::default(),
+        counts: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> FileInfo {
+        FileInfo {
+            size,
+            modified: chrono::Utc::now(),
+            accessed: chrono::Utc::now(),
+            name: name.to_string(),
+            file_type: FileType::File,
+            path: format!("./{}", name),
+            owner: "root".to_string(),
+            group: "wheel".to_string(),
+            mode: 0o644,
+            root: ".".to_string(),
+            compressed_size: None,
+            attributes: 0,
+            inode: 0,
+            nlink: 1,
+            dir_size: Default::default(),
+            mime: Default::default(),
+            digests: Default::default(),
+            counts: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_spilled_sort_matches_in_memory_sort() {
+        let sizes = [5, 3, 9, 1, 7, 3, 8, 2, 6, 4];
+        let keys = [SortKey { field: "size".to_string(), descending: false, natural: false }];
+        // a budget smaller than one entry spills every push
+        let mut sorter = Sorter::new(&keys, 1);
+        for (index, size) in sizes.iter().enumerate() {
+            sorter.push(file(&format!("f{}\twith tab", index), *size)).unwrap();
+        }
+        assert_eq!(sorter.spilled(), sizes.len());
+        let dir = sorter.dir.as_ref().unwrap().path().to_path_buf();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        let sorted: Vec<FileInfo> = sorter.finish().unwrap().map(Result::unwrap).collect();
+        let got: Vec<u64> = sorted.iter().map(|f| f.size).collect();
+        assert_eq!(got, [1, 2, 3, 3, 4, 5, 6, 7, 8, 9]);
+        // equal sizes keep their input order
+        assert_eq!(sorted[2].name, "f1\twith tab");
+        assert_eq!(sorted[3].name, "f5\twith tab");
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        // hidden and system, as Windows reports them
+        let original = FileInfo { attributes: 0x6, ..file("a\\b\nc", 42) };
+        let decoded = decode(&encode(&original)).unwrap();
+        assert_eq!(decoded.name, original.name);
+        assert!(decoded.is_hidden() && decoded.is_system());
+        assert_eq!(decoded.modified, original.modified);
+        assert_eq!(decoded.accessed, original.accessed);
+        // beyond what nanoseconds since 1970 fit in an i64
+        let far = DateTime::parse_from_rfc3339("2500-01-02T03:04:05.000000006Z").unwrap().with_timezone(&Utc);
+        let decoded = decode(&encode(&FileInfo { modified: far, ..file("far", 1) })).unwrap();
+        assert_eq!(decoded.modified, far);
+        assert!(decode("not a record").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+// `--stats`: what the last statement did, reported on stderr after it runs.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+static SCANNED: AtomicUsize = AtomicUsize::new(0);
+static TESTED: AtomicUsize = AtomicUsize::new(0);
+static MATCHED: AtomicUsize = AtomicUsize::new(0);
+static PRUNED: AtomicUsize = AtomicUsize::new(0);
+static UNREADABLE: AtomicUsize = AtomicUsize::new(0);
+
+// per pipeline stage: entries in, entries out and time spent, in stage order
+static STAGES: Mutex<Vec<(&str, usize, usize, Duration)>> = Mutex::new(Vec::new());
+
+pub fn reset() {
+    for counter in [&SCANNED, &TESTED, &MATCHED, &PRUNED, &UNREADABLE] {
+        counter.store(0, Ordering::Relaxed);
+    }
+    STAGES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+// a stage of the SELECT pipeline handled a batch
+pub fn stage(name: &'static str, entries_in: usize, entries_out: usize, elapsed: Duration) {
+    let mut stages = STAGES.lock().unwrap_or_else(|e| e.into_inner());
+    match stages.iter_mut().find(|(stage, ..)| *stage == name) {
+        Some((_, total_in, total_out, total)) => {
+            *total_in += entries_in;
+            *total_out += entries_out;
+            *total += elapsed;
+        }
+        None => stages.push((name, entries_in, entries_out, elapsed)),
+    }
+}
+
+// an entry was read from a directory, an archive or the session's listing
+pub fn scanned() {
+    SCANNED.fetch_add(1, Ordering::Relaxed);
+}
+
+// an entry was checked against the WHERE clause
+pub fn tested(matched: bool) {
+    TESTED.fetch_add(1, Ordering::Relaxed);
+    if matched {
+        MATCHED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// EXCLUDE skipped an entry, and everything below it
+pub fn pruned() {
+    PRUNED.fetch_add(1, Ordering::Relaxed);
+}
+
+// the walk could not read an entry or a directory and went on without it
+pub fn unreadable() {
+    UNREADABLE.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn report(elapsed: Duration) -> String {
+    let mut out = format!("scanned {} entries", SCANNED.load(Ordering::Relaxed));
+    let tested = TESTED.load(Ordering::Relaxed);
+    if tested > 0 {
+        out.push_str(&format!(", {} of {} matched", MATCHED.load(Ordering::Relaxed), tested));
+    }
+    let pruned = PRUNED.load(Ordering::Relaxed);
+    if pruned > 0 {
+        out.push_str(&format!(", {} excluded without walking below them", pruned));
+    }
+    let unreadable = UNREADABLE.load(Ordering::Relaxed);
+    if unreadable > 0 {
+        out.push_str(&format!(", {} unreadable skipped", unreadable));
+    }
+    out.push_str(&format!(" in {:.3}s", elapsed.as_secs_f64()));
+    for (name, entries_in, entries_out, spent) in STAGES.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        out.push_str(&format!(
+            "\n  {:<10} {:>8} in {:>8} out {:>8.3}s",
+            name,
+            entries_in,
+            entries_out,
+            spent.as_secs_f64()
+        ));
+    }
+    out
+}
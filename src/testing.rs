@@ -0,0 +1,38 @@
+// Helpers shared by the tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// a fresh directory under the system's temporary directory, removed with
+// everything in it when dropped, so a failing assertion leaves nothing behind
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // `name` tells apart the tests of one run, which share a process id
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("lsql-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(std::fs::canonicalize(&dir).unwrap())
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,252 @@
+// `lsql wizard`: build a statement step by step from prompts, print it and
+// run it once confirmed
+
+use std::io::{self, BufRead, Write};
+
+use colored::Colorize;
+
+use crate::files::{is_field, FIELDS};
+use crate::{run_input, State};
+
+const OPERATORS: &[&str] = &["=", "!=", "<", "<=", ">", ">=", "contains"];
+
+// what the statement does with the entries it finds
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Action {
+    #[default]
+    List,
+    Count,
+    Delete,
+}
+
+#[derive(Debug, Default)]
+pub struct Answers {
+    pub path: String,
+    pub action: Action,
+    pub columns: Vec<String>,
+    pub filters: Vec<(String, String, String)>,
+    pub order_by: Option<(String, bool)>,
+    pub limit: Option<usize>,
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn build_query(answers: &Answers) -> String {
+    let columns = if answers.columns.is_empty() {
+        "*".to_string()
+    } else {
+        answers.columns.join(", ")
+    };
+    let mut query = match answers.action {
+        Action::List => format!("select {} from {}", columns, quote(&answers.path)),
+        Action::Count => format!("select count(*) from {}", quote(&answers.path)),
+        Action::Delete => format!("delete from {}", quote(&answers.path)),
+    };
+    if !answers.filters.is_empty() {
+        let conditions: Vec<String> = answers
+            .filters
+            .iter()
+            .map(|(field, op, value)| format!("{} {} {}", field, op, quote(value)))
+            .collect();
+        query.push_str(&format!(" where {}", conditions.join(" and ")));
+    }
+    if let Some((field, descending)) = &answers.order_by {
+        let direction = if *descending { "desc" } else { "asc" };
+        query.push_str(&format!(" order by {} {}", field, direction));
+    }
+    if let Some(limit) = answers.limit {
+        query.push_str(&format!(" limit {}", limit));
+    }
+    query
+}
+
+struct Prompter<R> {
+    input: R,
+}
+
+impl<R: BufRead> Prompter<R> {
+    fn ask(&mut self, question: &str, default: &str) -> io::Result<String> {
+        if default.is_empty() {
+            print!("{} ", format!("{}:", question).cyan());
+        } else {
+            print!("{} ", format!("{} [{}]:", question, default).cyan());
+        }
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "wizard cancelled"));
+        }
+        let line = line.trim();
+        Ok(if line.is_empty() { default.to_string() } else { line.to_string() })
+    }
+
+    // ask until the answer passes `check`, which explains what is wrong
+    fn ask_valid<F>(&mut self, question: &str, default: &str, check: F) -> io::Result<String>
+    where
+        F: Fn(&str) -> Result<(), String>,
+    {
+        loop {
+            let answer = self.ask(question, default)?;
+            match check(&answer) {
+                Ok(()) => return Ok(answer),
+                Err(e) => eprintln!("{}", e.red()),
+            }
+        }
+    }
+
+    fn answers(&mut self) -> io::Result<Answers> {
+        let mut answers = Answers {
+            path: self.ask("Directory to search", ".")?,
+            ..Answers::default()
+        };
+        let action = self.ask_valid("Action (list/count/delete)", "list", |answer| {
+            match answer.to_lowercase().as_str() {
+                "list" | "count" | "delete" => Ok(()),
+                _ => Err("Answer list, count or delete".to_string()),
+            }
+        })?;
+        answers.action = match action.to_lowercase().as_str() {
+            "count" => Action::Count,
+            "delete" => Action::Delete,
+            _ => Action::List,
+        };
+
+        println!("Fields: {}", FIELDS.join(", "));
+        if answers.action == Action::List {
+            let columns = self.ask_valid("Columns to show, comma separated", "*", |answer| {
+                match answer.split(',').map(str::trim).find(|c| *c != "*" && !is_field(c)) {
+                    Some(unknown) => Err(format!("Unknown field '{}'", unknown)),
+                    None => Ok(()),
+                }
+            })?;
+            if columns != "*" {
+                answers.columns = columns.split(',').map(|c| c.trim().to_string()).collect();
+            }
+        }
+
+        loop {
+            // DELETE needs a WHERE clause, so at least one filter
+            let required = answers.action == Action::Delete && answers.filters.is_empty();
+            let field = self.ask_valid("Filter on field (empty to finish)", "", |answer| match answer {
+                "" if required => Err("Deleting needs at least one filter".to_string()),
+                "" => Ok(()),
+                field if is_field(field) => Ok(()),
+                field => Err(format!("Unknown field '{}'", field)),
+            })?;
+            if field.is_empty() {
+                break;
+            }
+            let operator = self.ask_valid(&format!("Operator ({})", OPERATORS.join(" ")), "=", |answer| {
+                if OPERATORS.contains(&answer.to_lowercase().as_str()) {
+                    Ok(())
+                } else {
+                    Err(format!("Unknown operator '{}'", answer))
+                }
+            })?;
+            let value = self.ask("Value", "")?;
+            answers.filters.push((field, operator, value));
+        }
+        if answers.action != Action::List {
+            return Ok(answers);
+        }
+
+        let order_field = self.ask_valid("Order by field (empty for none)", "", |answer| {
+            if answer.is_empty() || is_field(answer) {
+                Ok(())
+            } else {
+                Err(format!("Unknown field '{}'", answer))
+            }
+        })?;
+        if !order_field.is_empty() {
+            let direction = self.ask_valid("Direction (asc/desc)", "asc", |answer| {
+                match answer.to_lowercase().as_str() {
+                    "asc" | "desc" => Ok(()),
+                    _ => Err("Answer asc or desc".to_string()),
+                }
+            })?;
+            answers.order_by = Some((order_field, direction.eq_ignore_ascii_case("desc")));
+        }
+
+        let limit = self.ask_valid("Maximum number of results (empty for all)", "", |answer| {
+            if answer.is_empty() || answer.parse::<usize>().is_ok() {
+                Ok(())
+            } else {
+                Err("Enter a whole number".to_string())
+            }
+        })?;
+        answers.limit = limit.parse().ok();
+        Ok(answers)
+    }
+}
+
+pub fn run(state: &mut State) -> Result<(), Box<dyn std::error::Error>> {
+    run_from(state, io::stdin().lock()).map(|_| ())
+}
+
+// the wizard reading its answers from `input`: the statement is shown, then
+// run once confirmed. Deleting is only done on an explicit yes; returns the
+// statement and whether it ran
+fn run_from(state: &mut State, input: impl BufRead) -> Result<(String, bool), Box<dyn std::error::Error>> {
+    let mut prompter = Prompter { input };
+    let answers = prompter.answers()?;
+    let query = build_query(&answers);
+    println!("\n{}\n", query.green());
+    let default = if answers.action == Action::Delete { "n" } else { "y" };
+    let run_now = prompter.ask_valid("Run it now? (y/n)", default, |answer| match answer.to_lowercase().as_str() {
+        "y" | "yes" | "n" | "no" => Ok(()),
+        _ => Err("Answer y or n".to_string()),
+    })?;
+    let confirmed = run_now.eq_ignore_ascii_case("y") || run_now.eq_ignore_ascii_case("yes");
+    if confirmed {
+        run_input(state, &query)?;
+    }
+    Ok((query, confirmed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_answers_build_a_parseable_query() {
+        let input = b"./My Docs\nlist\nname, size\next\n=\nrs\nsize\n>\n1kb\n\nsize\ndesc\n10\n";
+        let mut prompter = Prompter { input: &input[..] };
+        let query = build_query(&prompter.answers().unwrap());
+        assert_eq!(
+            query,
+            "select name, size from \"./My Docs\" where ext = \"rs\" and size > \"1kb\" order by size desc limit 10"
+        );
+        let (remaining, commands) = parse(&query).unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(commands.len(), 1);
+    }
+
+    #[test]
+    fn test_confirm_and_run() {
+        let dir = crate::testing::TempDir::new("wizard");
+        for name in ["keep.txt", "old.log"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let options = crate::cli::Options { yes: true, ..Default::default() };
+        let mut state = State::at(&dir, options).unwrap();
+        let mut run = |input: &str| run_from(&mut state, input.as_bytes()).unwrap();
+
+        // listing runs on the default answer
+        let (query, ran) = run(".\n\nname\n\n\n\n\n");
+        assert_eq!((query.as_str(), ran), ("select name from \".\"", true));
+        let (query, ran) = run(".\ncount\next\n=\nlog\n\n\n");
+        assert_eq!((query.as_str(), ran), ("select count(*) from \".\" where ext = \"log\"", true));
+
+        // deleting asks for a filter, and the default answer runs nothing
+        let (query, ran) = run(".\ndelete\n\next\n=\nlog\n\n\n");
+        assert_eq!((query.as_str(), ran), ("delete from \".\" where ext = \"log\"", false));
+        assert!(dir.join("old.log").exists());
+        let (_, ran) = run(".\ndelete\next\n=\nlog\n\nmaybe\nyes\n");
+        assert!(ran);
+        assert!(!dir.join("old.log").exists());
+        assert!(dir.join("keep.txt").exists());
+    }
+}